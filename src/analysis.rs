@@ -1,12 +1,27 @@
-use opencv::{core, imgcodecs, imgproc, objdetect, prelude::*, types};
-use ort::{Environment, SessionBuilder};
+use opencv::{core, imgproc, objdetect, prelude::*, types};
+use ort::{Environment, Session};
+use rayon::prelude::*;
+use std::sync::{Arc, Mutex};
+use crate::performance::gpu::{is_out_of_memory, run_with_oom_fallback, warm_up_session, CpuFallback, GpuConfig, ProviderKind};
+use crate::performance::threading::{SessionPool, SessionPoolConfig};
 use serde::Serialize;
-use crate::face::{analyze_face, FaceAttributes};
+use crate::common::config::{Config, DetectionParams, InputSize, ModelInputSizes, ModelPaths};
+use crate::common::error::{FaceAnalyzerError, Result};
+use crate::common::types::{clamp_rect_to_image, BoundingBox};
+use crate::face::{analyze_face_with, AttributeDetectors, FaceAttributes};
+use crate::performance::timing::{PerfStats, Stage};
+use crate::processing::quality::{QualityAssessor, QualityMetrics};
+use crate::processing::input::{decode_gif_frames, read_image};
+use crate::processing::detectors::{DetectorFactory, DetectorType, FaceDetector};
 
 #[derive(Serialize)]
 pub struct FaceResult {
-    pub bbox: (i32, i32, i32, i32),
+    pub bbox: BoundingBox,
     pub attributes: Option<FaceAttributes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityMetrics>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,  // Added by post-processors
 }
 
 #[derive(Serialize)]
@@ -14,48 +29,479 @@ pub struct AnalysisResult {
     pub faces: Vec<FaceResult>,
 }
 
-pub fn analyze_image(image_path: &str) -> opencv::Result<(Mat, AnalysisResult)> {
-    let mut img = imgcodecs::imread(image_path, imgcodecs::IMREAD_COLOR)?;
-    if img.empty() {
-        eprintln!("Could not load image: {}", image_path);
-        std::process::exit(1);
-    }
-    let face_cascade = objdetect::CascadeClassifier::new(
-        "haarcascades/haarcascade_frontalface_default.xml",
-    )?;
-    let mut gray = Mat::default();
-    imgproc::cvt_color(&img, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
-    let mut faces = types::VectorOfRect::new();
-    face_cascade.detect_multi_scale(
-        &gray,
-        &mut faces,
-        1.1,
-        3,
-        0,
-        core::Size { width: 30, height: 30 },
-        core::Size { width: 0, height: 0 },
-    )?;
-    let environment = Environment::builder().with_name("face_attr").build().unwrap();
-    let session = SessionBuilder::new(&environment)
-        .unwrap()
-        .with_model_from_file("models/face_attributes.onnx")
-        .unwrap();
-    let mut results = Vec::new();
-    for face in faces.iter() {
-        imgproc::rectangle(
-            &mut img,
-            face,
-            core::Scalar::new(0.0, 255.0, 0.0, 0.0),
-            2,
-            imgproc::LINE_8,
+/// Per-frame results of a multi-frame image such as an animated GIF.
+#[derive(Serialize)]
+pub struct AnimatedAnalysisResult {
+    pub frames: Vec<AnalysisResult>,
+    pub truncated: bool,  // Frames past `AnimationConfig::max_frames` were skipped
+}
+
+pub struct AnalyzerConfig {
+    pub app: Config,      // Model paths, detector type and threshold
+    pub gpu: GpuConfig,           // Copied from `Config::gpu` by `from_config`
+    pub pool: SessionPoolConfig,  // Attribute sessions kept for parallel per-face inference
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self::from_config(Config::default())
+    }
+}
+
+impl AnalyzerConfig {
+    pub fn from_config(app: Config) -> Self {
+        Self {
+            gpu: app.gpu.clone(),
+            app,
+            pool: SessionPoolConfig::default(),
+        }
+    }
+
+    /// Use `SessionPoolConfig::single()` when images are analyzed one at a
+    /// time, so only one copy of the model is loaded.
+    pub fn with_pool(mut self, pool: SessionPoolConfig) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    pub fn models(&self) -> &ModelPaths {
+        &self.app.models
+    }
+}
+
+/// Analyze a single image using the config from `Config::load`. Loads the
+/// models on every call; use an `Analyzer` to process several images.
+pub fn analyze_image(image_path: &str) -> Result<(Mat, AnalysisResult)> {
+    analyze_image_with_config(image_path, &Config::load(None)?)
+}
+
+pub fn analyze_image_with_config(image_path: &str, config: &Config) -> Result<(Mat, AnalysisResult)> {
+    let config = AnalyzerConfig::from_config(config.clone()).with_pool(SessionPoolConfig::single());
+    Analyzer::with_config(&config)?.analyze(image_path)
+}
+
+/// Run attribute inference for each face in parallel, one pooled session
+/// per worker. Results keep the order of `faces`.
+pub fn analyze_faces(
+    img: &Mat,
+    faces: &[core::Rect],
+    pool: &SessionPool,
+    input_size: InputSize,
+) -> Result<Vec<FaceResult>> {
+    analyze_faces_with_stats(img, faces, pool, None, input_size, &AttributeDetectors::default(), &PerfStats::new())
+}
+
+/// `analyze_faces`, also running `detectors` on each face and adding the
+/// time of each `analyze_face_with` call to `stats`. A face that runs the
+/// GPU out of memory is retried on `cpu` when given.
+pub fn analyze_faces_with_stats(
+    img: &Mat,
+    faces: &[core::Rect],
+    pool: &SessionPool,
+    cpu: Option<&CpuFallback>,
+    input_size: InputSize,
+    detectors: &AttributeDetectors,
+    stats: &PerfStats,
+) -> Result<Vec<FaceResult>> {
+    let (faces, rois) = crop_faces(img, faces)?;
+    let assessor = QualityAssessor::default();
+    Ok(rois
+        .into_par_iter()
+        .zip(faces.par_iter())
+        .map(|(roi, face)| {
+            let attributes = pool
+                .with_session(|session| {
+                    infer_attributes(std::slice::from_ref(&roi), session, cpu, input_size, detectors, stats)
+                })
+                .pop()
+                .unwrap_or_else(|| Err(FaceAnalyzerError::decode("No attribute result for face")));
+            face_result(&roi, face, attributes, &assessor)
+        })
+        .collect())
+}
+
+/// `analyze_faces_with_stats` on a single session, one face after another.
+/// For callers that already hold a session, such as jobs queued with
+/// `SessionPool::run`.
+pub fn analyze_faces_on(
+    img: &Mat,
+    faces: &[core::Rect],
+    session: &Session,
+    cpu: Option<&CpuFallback>,
+    input_size: InputSize,
+    detectors: &AttributeDetectors,
+    stats: &PerfStats,
+) -> Result<Vec<FaceResult>> {
+    let (faces, rois) = crop_faces(img, faces)?;
+    let assessor = QualityAssessor::default();
+    let attributes = infer_attributes(&rois, session, cpu, input_size, detectors, stats);
+    Ok(rois
+        .iter()
+        .zip(&faces)
+        .zip(attributes)
+        .map(|((roi, face), attributes)| face_result(roi, face, attributes, &assessor))
+        .collect())
+}
+
+/// Attribute inference for each of `rois` on `session`, in order, through
+/// `run_with_oom_fallback`: a GPU out-of-memory error retries the remaining
+/// faces in smaller chunks and finally one at a time on `cpu`. Any other
+/// error stays in that face's slot.
+fn infer_attributes(
+    rois: &[Mat],
+    session: &Session,
+    cpu: Option<&CpuFallback>,
+    input_size: InputSize,
+    detectors: &AttributeDetectors,
+    stats: &PerfStats,
+) -> Vec<Result<FaceAttributes>> {
+    let run_chunk = |chunk: &[Mat]| -> anyhow::Result<Vec<Result<FaceAttributes>>> {
+        let mut results = Vec::with_capacity(chunk.len());
+        for roi in chunk {
+            let result = stats.time(Stage::Attributes, || analyze_face_with(roi, session, input_size, detectors));
+            match result {
+                Err(e) if is_out_of_memory(&e) => return Err(anyhow::anyhow!("{}", e)),
+                result => results.push(result),
+            }
+        }
+        Ok(results)
+    };
+    let run_cpu = |roi: &Mat| -> anyhow::Result<Result<FaceAttributes>> {
+        let Some(cpu) = cpu else {
+            return Ok(Err(FaceAnalyzerError::Other(anyhow::anyhow!(
+                "GPU out of memory and no CPU fallback configured"
+            ))));
+        };
+        Ok(cpu
+            .with_session(|session| {
+                stats.time(Stage::Attributes, || analyze_face_with(roi, session, input_size, detectors))
+            })
+            .unwrap_or_else(|e| Err(e.into())))
+    };
+
+    // Neither closure returns a non-OOM error, so this only fails if the
+    // fallback logic itself changes
+    run_with_oom_fallback(rois, rois.len(), run_chunk, run_cpu).unwrap_or_else(|e| {
+        rois.iter()
+            .map(|_| Err(FaceAnalyzerError::Other(anyhow::anyhow!("{}", e))))
+            .collect()
+    })
+}
+
+/// Clamp the boxes to the image and copy out a crop for each. Boxes reaching
+/// past the edge are cropped to the image; ones entirely outside it are
+/// dropped. Copying the crops means workers never touch the shared image.
+fn crop_faces(img: &Mat, faces: &[core::Rect]) -> Result<(Vec<core::Rect>, Vec<Mat>)> {
+    let faces: Vec<core::Rect> = faces
+        .iter()
+        .map(|face| clamp_rect_to_image(*face, img.cols(), img.rows()))
+        .filter(|face| face.area() > 0)
+        .collect();
+
+    let rois = faces
+        .iter()
+        .map(|face| Mat::roi(img, *face).and_then(|roi| roi.try_clone()))
+        .collect::<opencv::Result<Vec<Mat>>>()?;
+    Ok((faces, rois))
+}
+
+/// A failed attribute or quality step leaves that field empty rather than
+/// dropping the face. The quality metrics carry the eye state when
+/// landmarks are configured.
+fn face_result(
+    roi: &Mat,
+    face: &core::Rect,
+    attributes: Result<FaceAttributes>,
+    assessor: &QualityAssessor,
+) -> FaceResult {
+    let attributes = attributes
+        .map_err(|e| log::warn!("Attribute inference failed: {}", e))
+        .ok();
+    let eye_state = attributes.as_ref().and_then(|attributes| attributes.eye_state.as_ref());
+    let quality = assessor
+        .assess_quality(roi, face)
+        .map_err(|e| log::warn!("Quality assessment failed: {}", e))
+        .ok()
+        .map(|quality| match eye_state {
+            Some(eye_state) => quality.with_eye_state(eye_state),
+            None => quality,
+        });
+    FaceResult {
+        bbox: BoundingBox::from(*face),
+        attributes,
+        quality,
+        tags: Vec::new(),
+    }
+}
+
+/// Hook run after the standard pipeline. Implementations may mutate or
+/// augment the result, e.g. to apply business rules or add custom tags.
+pub trait PostProcessor: Send + Sync {
+    fn process(&self, image: &Mat, result: &mut AnalysisResult) -> anyhow::Result<()>;
+}
+
+/// Owns the detector, model sessions and post-processors so they are loaded
+/// once and reused for every image.
+pub struct Analyzer {
+    _environment: Arc<Environment>,
+    detector: Detector,
+    pool: SessionPool,
+    cpu_fallback: Option<CpuFallback>,  // Only when a GPU provider is configured
+    input_size: InputSize,
+    detectors: AttributeDetectors,  // Optional per-face models from `models` in the config
+    max_frames: usize,
+    post_processors: Vec<Box<dyn PostProcessor>>,
+}
+
+impl Analyzer {
+    pub fn new() -> Result<Self> {
+        Self::with_config(&AnalyzerConfig::default())
+    }
+
+    pub fn with_config(config: &AnalyzerConfig) -> Result<Self> {
+        let detector = Detector::new(&config.app)?;
+        let environment = Environment::builder().with_name("face_attr").build()?.into_arc();
+        let pool = SessionPool::build(&environment, &config.models().attributes, &config.gpu, &config.pool)?;
+        let input_size = pool.with_session(|session| {
+            ModelInputSizes::resolve(config.models().input_sizes.attributes, session)
+        });
+        let cpu_fallback = config
+            .gpu
+            .providers
+            .iter()
+            .any(|provider| *provider != ProviderKind::Cpu)
+            .then(|| CpuFallback::new(environment.clone(), config.models().attributes.clone()));
+        let detectors = AttributeDetectors::from_config(&config.app)?;
+
+        Ok(Self {
+            _environment: environment,
+            detector,
+            pool,
+            cpu_fallback,
+            input_size,
+            detectors,
+            max_frames: config.app.animation.max_frames,
+            post_processors: Vec::new(),
+        })
+    }
+
+    /// Register a hook. Hooks run in registration order.
+    pub fn with_post_processor(mut self, post_processor: Box<dyn PostProcessor>) -> Self {
+        self.post_processors.push(post_processor);
+        self
+    }
+
+    /// Detect and analyze every face, returning the image with the faces
+    /// boxed alongside the results.
+    pub fn analyze(&self, image_path: &str) -> Result<(Mat, AnalysisResult)> {
+        let mut img = read_image(image_path)?;
+
+        let result = self.analyze_mat(&img)?;
+
+        for face in &result.faces {
+            imgproc::rectangle(
+                &mut img,
+                face.bbox.rect(),
+                core::Scalar::new(0.0, 255.0, 0.0, 0.0),
+                2,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+        Ok((img, result))
+    }
+
+    /// Detect and analyze every face in an already decoded image.
+    pub fn analyze_mat(&self, img: &Mat) -> Result<AnalysisResult> {
+        self.analyze_mat_with_stats(img, &PerfStats::new())
+    }
+
+    /// `analyze_mat`, recording detection and attribute inference times.
+    pub fn analyze_mat_with_stats(&self, img: &Mat, stats: &PerfStats) -> Result<AnalysisResult> {
+        let faces = stats.time(Stage::Detection, || self.detector.detect(img))?;
+        let mut result = AnalysisResult {
+            faces: analyze_faces_with_stats(
+                img,
+                &faces,
+                &self.pool,
+                self.cpu_fallback.as_ref(),
+                self.input_size,
+                &self.detectors,
+                stats,
+            )?,
+        };
+        self.post_process(img, &mut result)?;
+        Ok(result)
+    }
+
+    /// `analyze_mat` with attribute inference on `session` instead of the
+    /// analyzer's own pool; see `analyze_faces_on`.
+    pub fn analyze_mat_on(&self, img: &Mat, session: &Session, stats: &PerfStats) -> Result<AnalysisResult> {
+        let faces = stats.time(Stage::Detection, || self.detector.detect(img))?;
+        let mut result = AnalysisResult {
+            faces: analyze_faces_on(
+                img,
+                &faces,
+                session,
+                self.cpu_fallback.as_ref(),
+                self.input_size,
+                &self.detectors,
+                stats,
+            )?,
+        };
+        self.post_process(img, &mut result)?;
+        Ok(result)
+    }
+
+    pub fn detector_type(&self) -> DetectorType {
+        self.detector.detector_type()
+    }
+
+    /// Run one dummy inference on every attribute session so the first
+    /// image does not pay for ONNX Runtime's lazy allocation.
+    pub fn warm_up(&self) -> Result<()> {
+        for result in self.pool.for_each_idle(|session| warm_up_session(session, self.input_size)) {
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Detect and analyze every face in each frame of an animated GIF, up
+    /// to the configured frame cap. Each frame is handled like a still image.
+    pub fn analyze_animated(&self, bytes: &[u8]) -> Result<AnimatedAnalysisResult> {
+        let (frames, truncated) = decode_gif_frames(bytes, self.max_frames)?;
+        let frames = frames
+            .iter()
+            .map(|frame| self.analyze_mat(frame))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(AnimatedAnalysisResult { frames, truncated })
+    }
+
+    pub fn post_process(&self, image: &Mat, result: &mut AnalysisResult) -> Result<()> {
+        Ok(run_post_processors(&self.post_processors, image, result)?)
+    }
+}
+
+/// Haar keeps its own cascade loaded across images; the other detector
+/// types go through `FaceDetector`.
+enum Detector {
+    Cascade(Mutex<objdetect::CascadeClassifier>, DetectionParams),
+    Model(FaceDetector),
+}
+
+impl Detector {
+    fn new(config: &Config) -> Result<Self> {
+        if config.detector != DetectorType::Haar {
+            return Ok(Detector::Model(DetectorFactory::from_config(config)?));
+        }
+
+        let cascade = objdetect::CascadeClassifier::new(&config.models.cascade)?;
+        if cascade.empty()? {
+            return Err(FaceAnalyzerError::detection(format!("Failed to load cascade: {}", config.models.cascade)));
+        }
+        Ok(Detector::Cascade(Mutex::new(cascade), config.detection.clone()))
+    }
+
+    fn detector_type(&self) -> DetectorType {
+        match self {
+            Detector::Cascade(..) => DetectorType::Haar,
+            Detector::Model(detector) => detector.detector_type(),
+        }
+    }
+
+    fn detect(&self, img: &Mat) -> Result<Vec<core::Rect>> {
+        let (cascade, params) = match self {
+            Detector::Cascade(cascade, params) => (cascade, params),
+            Detector::Model(detector) => {
+                return Ok(detector.detect(img)?.iter().map(|d| d.bbox.rect()).collect());
+            }
+        };
+
+        let mut gray = Mat::default();
+        imgproc::cvt_color(img, &mut gray, imgproc::COLOR_BGR2GRAY, 0)?;
+        let mut faces = types::VectorOfRect::new();
+        cascade.lock().unwrap().detect_multi_scale(
+            &gray,
+            &mut faces,
+            params.scale_factor as f64,
+            params.min_neighbors,
             0,
+            core::Size { width: params.min_face_size, height: params.min_face_size },
+            core::Size { width: 0, height: 0 },
         )?;
-        let face_roi = Mat::roi(&img, face)?;
-        let attributes = analyze_face(&face_roi, &session);
-        results.push(FaceResult {
-            bbox: (face.x, face.y, face.width, face.height),
-            attributes,
-        });
+        Ok(faces.to_vec())
     }
-    Ok((img, AnalysisResult { faces: results }))
-} 
\ No newline at end of file
+}
+
+fn run_post_processors(
+    post_processors: &[Box<dyn PostProcessor>],
+    image: &Mat,
+    result: &mut AnalysisResult,
+) -> anyhow::Result<()> {
+    for post_processor in post_processors {
+        post_processor.process(image, result)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TagEveryFace(&'static str);
+
+    impl PostProcessor for TagEveryFace {
+        fn process(&self, _image: &Mat, result: &mut AnalysisResult) -> anyhow::Result<()> {
+            for face in &mut result.faces {
+                face.tags.push(self.0.to_string());
+            }
+            Ok(())
+        }
+    }
+
+    /// Attribute inference over a synthetic 10-face image with a single
+    /// session and with the default pool. Needs the attribute model; run
+    /// with `cargo test --release bench_ten_faces -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_ten_faces() {
+        let environment = Environment::builder().with_name("bench").build().unwrap().into_arc();
+        let config = AnalyzerConfig::default();
+        let model = &config.models().attributes;
+        let input_size = config.models().input_sizes.attributes;
+        let image = Mat::new_rows_cols_with_default(240, 600, core::CV_8UC3, core::Scalar::all(128.0)).unwrap();
+        let faces: Vec<core::Rect> = (0..10)
+            .map(|i| core::Rect::new((i % 5) * 120, (i / 5) * 120, 112, 112))
+            .collect();
+
+        for sessions in [1, SessionPoolConfig::default().sessions] {
+            let pool_config = SessionPoolConfig { sessions, ..Default::default() };
+            let pool = SessionPool::build(&environment, model, &GpuConfig::cpu_only(), &pool_config).unwrap();
+            analyze_faces(&image, &faces, &pool, input_size).unwrap();
+
+            let start = std::time::Instant::now();
+            for _ in 0..10 {
+                assert_eq!(analyze_faces(&image, &faces, &pool, input_size).unwrap().len(), 10);
+            }
+            println!("{} session(s): {:.1} ms per image", sessions, start.elapsed().as_secs_f64() * 100.0);
+        }
+    }
+
+    #[test]
+    fn test_post_processor_tags_every_face() {
+        let post_processors: Vec<Box<dyn PostProcessor>> = vec![Box::new(TagEveryFace("reviewed"))];
+        let mut result = AnalysisResult {
+            faces: vec![
+                FaceResult { bbox: BoundingBox::new(0, 0, 40, 40), attributes: None, quality: None, tags: Vec::new() },
+                FaceResult { bbox: BoundingBox::new(60, 0, 40, 40), attributes: None, quality: None, tags: Vec::new() },
+            ],
+        };
+
+        run_post_processors(&post_processors, &Mat::default(), &mut result).unwrap();
+
+        let json = serde_json::to_value(&result).unwrap();
+        for face in json["faces"].as_array().unwrap() {
+            assert_eq!(face["tags"], serde_json::json!(["reviewed"]));
+        }
+    }
+}
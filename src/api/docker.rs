@@ -0,0 +1,124 @@
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::database::storage::Database;
+
+#[derive(Debug, Serialize)]
+pub struct ModelStatus {
+    pub path: String,
+    pub present: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: String,
+    pub database: bool,
+    pub upload_dir_writable: bool,
+    pub models: Vec<ModelStatus>,
+}
+
+impl HealthReport {
+    /// Ready only when the database answers; a missing model or read-only
+    /// upload dir degrades the service but still lets reads through.
+    pub fn is_ready(&self) -> bool {
+        self.database
+    }
+}
+
+/// Container liveness/readiness information for orchestrators.
+#[derive(Debug, Clone)]
+pub struct DockerHealth {
+    model_paths: Vec<String>,
+    upload_dir: String,
+}
+
+impl DockerHealth {
+    pub fn new(model_paths: Vec<String>, upload_dir: String) -> Self {
+        Self {
+            model_paths,
+            upload_dir,
+        }
+    }
+
+    pub fn model_status(&self) -> Vec<ModelStatus> {
+        self.model_paths
+            .iter()
+            .map(|path| ModelStatus {
+                path: path.clone(),
+                present: Path::new(path).is_file(),
+            })
+            .collect()
+    }
+
+    pub async fn upload_dir_writable(&self) -> bool {
+        let probe = Path::new(&self.upload_dir).join(format!(".health-{}", Uuid::new_v4()));
+        match tokio::fs::write(&probe, b"ok").await {
+            Ok(()) => {
+                let _ = tokio::fs::remove_file(&probe).await;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub async fn report(&self, database: &Database) -> HealthReport {
+        let database_ok = database.ping().await.is_ok();
+        let upload_dir_writable = self.upload_dir_writable().await;
+        let models = self.model_status();
+
+        let status = if !database_ok {
+            "unavailable"
+        } else if !upload_dir_writable || models.iter().any(|m| !m.present) {
+            "degraded"
+        } else {
+            "ok"
+        };
+
+        HealthReport {
+            status: status.to_string(),
+            database: database_ok,
+            upload_dir_writable,
+            models,
+        }
+    }
+}
+
+pub async fn health(
+    health: web::Data<DockerHealth>,
+    database: web::Data<Database>,
+) -> impl Responder {
+    let report = health.report(&database).await;
+    if report.is_ready() {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_model_and_upload_dir_status() {
+        let dir = tempdir().unwrap();
+        let model_path = dir.path().join("face_attributes.onnx");
+        std::fs::write(&model_path, b"onnx").unwrap();
+
+        let health = DockerHealth::new(
+            vec![
+                model_path.to_string_lossy().into_owned(),
+                dir.path().join("missing.onnx").to_string_lossy().into_owned(),
+            ],
+            dir.path().to_string_lossy().into_owned(),
+        );
+
+        let models = health.model_status();
+        assert!(models[0].present);
+        assert!(!models[1].present);
+        assert!(health.upload_dir_writable().await);
+    }
+}
@@ -0,0 +1,234 @@
+use actix_web::{web, HttpResponse};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Inference latency bucket bounds in seconds.
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+/// Upload size bucket bounds in bytes, 16 KiB to 16 MiB.
+const UPLOAD_BUCKETS: &[f64] = &[16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0];
+
+/// Where inference time is spent, used as the `stage` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InferenceStage {
+    Detection,
+    Embedding,
+    Analysis,  // A whole analyzer run: detection plus attributes, when an analyzer is configured
+}
+
+impl InferenceStage {
+    fn label(self) -> &'static str {
+        match self {
+            InferenceStage::Detection => "detection",
+            InferenceStage::Embedding => "embedding",
+            InferenceStage::Analysis => "analysis",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<u64>,  // Non-cumulative counts per bound; rendered cumulatively
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// `labels` is empty or a `key="value"` list without braces.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+        let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+/// Counters and histograms for the API server, rendered in the Prometheus
+/// text format by `/metrics`. Shared by every worker, so all state sits
+/// behind atomics or a mutex.
+#[derive(Debug)]
+pub struct ApiMetrics {
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,  // (method, route pattern, status)
+    faces_enrolled: AtomicU64,
+    detection_failures: AtomicU64,
+    inference_seconds: Mutex<BTreeMap<InferenceStage, Histogram>>,
+    upload_bytes: Mutex<Histogram>,
+}
+
+impl Default for ApiMetrics {
+    fn default() -> Self {
+        Self {
+            requests: Mutex::new(BTreeMap::new()),
+            faces_enrolled: AtomicU64::new(0),
+            detection_failures: AtomicU64::new(0),
+            inference_seconds: Mutex::new(BTreeMap::new()),
+            upload_bytes: Mutex::new(Histogram::new(UPLOAD_BUCKETS)),
+        }
+    }
+}
+
+impl ApiMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a finished request. `route` is the matched route pattern, such
+    /// as `/api/v1/faces/{id}`, so ids do not blow up the label set; `None`
+    /// for requests that matched no route.
+    pub fn record_request(&self, method: &str, route: Option<&str>, status: u16) {
+        let key = (method.to_string(), route.unwrap_or("unmatched").to_string(), status);
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    pub fn record_enrolled(&self) {
+        self.faces_enrolled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_detection_failure(&self) {
+        self.detection_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_inference(&self, stage: InferenceStage, elapsed: Duration) {
+        if let Ok(mut histograms) = self.inference_seconds.lock() {
+            histograms
+                .entry(stage)
+                .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+                .observe(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Run `f`, recording how long it took under `stage`.
+    pub fn time<T>(&self, stage: InferenceStage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.observe_inference(stage, start.elapsed());
+        result
+    }
+
+    pub fn observe_upload(&self, bytes: usize) {
+        if let Ok(mut histogram) = self.upload_bytes.lock() {
+            histogram.observe(bytes as f64);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP face_analyzer_http_requests_total HTTP requests by method, route and status.\n");
+        out.push_str("# TYPE face_analyzer_http_requests_total counter\n");
+        if let Ok(requests) = self.requests.lock() {
+            for ((method, route, status), count) in requests.iter() {
+                let _ = writeln!(
+                    out,
+                    "face_analyzer_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method,
+                    escape_label(route),
+                    status,
+                    count
+                );
+            }
+        }
+
+        out.push_str("# HELP face_analyzer_faces_enrolled_total Faces newly stored in the gallery.\n");
+        out.push_str("# TYPE face_analyzer_faces_enrolled_total counter\n");
+        let _ = writeln!(out, "face_analyzer_faces_enrolled_total {}", self.faces_enrolled.load(Ordering::Relaxed));
+
+        out.push_str("# HELP face_analyzer_detection_failures_total Face detection or analysis calls that returned an error.\n");
+        out.push_str("# TYPE face_analyzer_detection_failures_total counter\n");
+        let _ = writeln!(
+            out,
+            "face_analyzer_detection_failures_total {}",
+            self.detection_failures.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP face_analyzer_inference_seconds Model inference latency by stage.\n");
+        out.push_str("# TYPE face_analyzer_inference_seconds histogram\n");
+        if let Ok(histograms) = self.inference_seconds.lock() {
+            for (stage, histogram) in histograms.iter() {
+                histogram.render(
+                    &mut out,
+                    "face_analyzer_inference_seconds",
+                    &format!("stage=\"{}\"", stage.label()),
+                );
+            }
+        }
+
+        out.push_str("# HELP face_analyzer_upload_bytes Size of uploaded images.\n");
+        out.push_str("# TYPE face_analyzer_upload_bytes histogram\n");
+        if let Ok(histogram) = self.upload_bytes.lock() {
+            histogram.render(&mut out, "face_analyzer_upload_bytes", "");
+        }
+
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub async fn metrics(metrics: web::Data<ApiMetrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_histograms() {
+        let metrics = ApiMetrics::new();
+        metrics.record_request("GET", Some("/api/v1/faces/{id}"), 200);
+        metrics.record_request("GET", Some("/api/v1/faces/{id}"), 200);
+        metrics.record_request("GET", None, 404);
+        metrics.record_enrolled();
+        metrics.observe_inference(InferenceStage::Detection, Duration::from_millis(30));
+        metrics.observe_inference(InferenceStage::Analysis, Duration::from_millis(200));
+        metrics.observe_upload(100_000);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "face_analyzer_http_requests_total{method=\"GET\",route=\"/api/v1/faces/{id}\",status=\"200\"} 2"
+        ));
+        assert!(text.contains("route=\"unmatched\",status=\"404\"} 1"));
+        assert!(text.contains("face_analyzer_faces_enrolled_total 1\n"));
+        assert!(text.contains("face_analyzer_detection_failures_total 0\n"));
+        assert!(text.contains("face_analyzer_inference_seconds_bucket{stage=\"detection\",le=\"0.025\"} 0"));
+        assert!(text.contains("face_analyzer_inference_seconds_bucket{stage=\"detection\",le=\"0.05\"} 1"));
+        assert!(text.contains("face_analyzer_inference_seconds_count{stage=\"detection\"} 1"));
+        assert!(text.contains("face_analyzer_inference_seconds_count{stage=\"analysis\"} 1"));
+        assert!(text.contains("face_analyzer_upload_bytes_bucket{le=\"65536\"} 0"));
+        assert!(text.contains("face_analyzer_upload_bytes_bucket{le=\"+Inf\"} 1"));
+        assert!(text.contains("face_analyzer_upload_bytes_sum 100000\n"));
+    }
+}
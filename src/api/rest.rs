@@ -1,23 +1,114 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    dev::{Server, Service}, guard, http::{header::ContentDisposition, StatusCode}, middleware::Condition, web,
+    App, HttpResponse, HttpServer, ResponseError,
+};
 use actix_multipart::Multipart;
 use actix_cors::Cors;
 use serde::{Deserialize, Serialize};
-use futures::{StreamExt, TryStreamExt};
+use futures::{FutureExt, StreamExt, TryStreamExt};
 use uuid::Uuid;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Instant;
 use tokio::fs;
 use anyhow::Result;
+use opencv::{imgcodecs, prelude::*};
 
+use crate::attributes::{
+    emotion::Emotion,
+    landmarks::LandmarkDetector,
+    pose::{HeadPose, PoseEstimator},
+};
+use crate::api::{
+    docker::{self, DockerHealth},
+    error::{ApiError, ApiResultExt},
+    metrics::{self, ApiMetrics, InferenceStage},
+    runtime::{DetectionRuntime, RuntimeConfig},
+    websocket::{self, SharedWsManager, WsManager},
+};
+use crate::analysis::{AnalysisResult, Analyzer, FaceResult};
+use crate::face::FaceAttributes;
+use crate::processing::quality::{QualityAssessor, QualityMetrics};
+use crate::common::config::DetectorThresholds;
+use crate::common::error::FaceAnalyzerError;
+use crate::common::types::{crop_to_image, BoundingBox};
+use crate::security::anonymization::{AnonymizationMethod, Anonymizer};
+use crate::security::auth::{self, AuthConfig, Scope};
+use crate::processing::detectors::{DetectionResult, DetectorType, FaceDetector};
+use crate::processing::input;
+use crate::performance::optimization::BatchProcessor;
+use crate::performance::threading::SessionPool;
+use crate::performance::timing::{PerfStats, Stage, StageTiming};
 use crate::database::{
-    storage::Database,
-    embeddings::{FaceEmbedding, FaceMetadata, EmbeddingGenerator},
+    storage::{Database, SearchQuery, StoreOutcome},
+    embeddings::{
+        EmbeddingComparator, EmbeddingFormat, EmbeddingGenerator, EncodedEmbedding, FaceEmbedding,
+        FaceMetadata, Metric, StoredMetadata,
+    },
 };
+use crate::output::csv::CsvExportOptions;
 use crate::output::report::ReportGenerator;
+use crate::output::sink::ResultSink;
 
 #[derive(Deserialize)]
 pub struct AnalyzeQuery {
     min_confidence: Option<f32>,
     include_embeddings: Option<bool>,
+    embedding_format: Option<EmbeddingFormat>,
+    require_frontal: Option<bool>,
+    dedupe_threshold: Option<f32>,
+    emotion: Option<Emotion>,  // Attribute filters for listing, e.g. `?emotion=Happy&min_age=20`
+    min_age: Option<f32>,
+    max_age: Option<f32>,
+    gender: Option<String>,
+    debug: Option<bool>,  // Adds per-stage `timings` to the analyze response
+}
+
+/// `columns` is a comma-separated list of field names; `delimiter` is a
+/// single character or one of `comma`, `tab`, `semicolon`, `pipe`.
+#[derive(Deserialize)]
+pub struct CsvExportQuery {
+    columns: Option<String>,
+    delimiter: Option<String>,
+    include_embeddings: Option<bool>,
+    embedding_format: Option<EmbeddingFormat>,
+}
+
+impl CsvExportQuery {
+    fn options(&self) -> anyhow::Result<CsvExportOptions> {
+        let mut options = CsvExportOptions {
+            include_embeddings: self.include_embeddings.unwrap_or(false),
+            embedding_format: self.embedding_format.unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(columns) = &self.columns {
+            options.columns = CsvExportOptions::parse_columns(columns)?;
+        }
+        if let Some(delimiter) = &self.delimiter {
+            options.delimiter = CsvExportOptions::parse_delimiter(delimiter)?;
+        }
+        Ok(options)
+    }
+}
+
+impl AnalyzeQuery {
+    fn encode_embedding(&self, embedding: &[f32]) -> Option<EncodedEmbedding> {
+        self.include_embeddings
+            .unwrap_or(false)
+            .then(|| EncodedEmbedding::encode(embedding, self.embedding_format.unwrap_or_default()))
+    }
+
+    fn search_query(&self) -> SearchQuery {
+        SearchQuery {
+            min_confidence: self.min_confidence,
+            emotion: self.emotion.clone(),
+            min_age: self.min_age,
+            max_age: self.max_age,
+            gender: self.gender.clone(),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -26,7 +117,225 @@ pub struct AnalyzeResponse {
     name: Option<String>,
     tags: Vec<String>,
     confidence: f32,
-    embedding: Option<Vec<f32>>,
+    embedding: Option<EncodedEmbedding>,
+    duplicate: bool,
+}
+
+/// One face from an `/analyze` upload: its gallery entry alongside the box
+/// and attributes found in the image.
+#[derive(Serialize)]
+pub struct EnrolledFace {
+    #[serde(flatten)]
+    enrolled: AnalyzeResponse,
+    bbox: BoundingBox,
+    attributes: Option<FaceAttributes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<QualityMetrics>,
+}
+
+impl EnrolledFace {
+    fn new(face: FaceEmbedding, result: FaceResult, duplicate: bool, query: &AnalyzeQuery) -> Self {
+        Self {
+            enrolled: AnalyzeResponse {
+                embedding: query.encode_embedding(&face.embedding),
+                face_id: face.face_id,
+                name: face.metadata.name,
+                tags: face.metadata.tags,
+                confidence: face.metadata.confidence,
+                duplicate,
+            },
+            bbox: result.bbox,
+            attributes: result.attributes,
+            quality: result.quality,
+        }
+    }
+}
+
+/// `POST /analyze` result: one entry per face in the upload, in detection
+/// order, with `face_ids` listing the gallery id for each.
+#[derive(Serialize)]
+pub struct AnalyzeImageResponse {
+    face_ids: Vec<String>,
+    faces: Vec<EnrolledFace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<BTreeMap<Stage, StageTiming>>,  // Only with `?debug=true`
+}
+
+impl AnalyzeImageResponse {
+    fn new(faces: Vec<EnrolledFace>) -> Self {
+        Self {
+            face_ids: faces.iter().map(|face| face.enrolled.face_id.clone()).collect(),
+            faces,
+            timings: None,
+        }
+    }
+
+    fn with_timings(mut self, stats: &PerfStats) -> Self {
+        self.timings = Some(stats.snapshot());
+        self
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CompareQuery {
+    threshold: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct CompareResponse {
+    cosine_similarity: f32,
+    euclidean_distance: f32,
+    threshold: f32,
+    #[serde(rename = "match")]
+    is_match: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQueryParams {
+    threshold: Option<f32>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SearchMatch {
+    face_id: String,
+    similarity: f32,
+}
+
+#[derive(Serialize)]
+pub struct SearchGroup {
+    bbox: BoundingBox,
+    matches: Vec<SearchMatch>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnonymizeMode {
+    #[default]
+    Blur,
+    OvalBlur,
+    Pixelate,
+    BlackOut,
+}
+
+#[derive(Deserialize)]
+pub struct AnonymizeQuery {
+    mode: Option<AnonymizeMode>,
+    strength: Option<i32>,  // Blur kernel size or pixelation block size
+}
+
+/// Accepted `strength` values. Blur cost grows with the kernel size, so an
+/// unbounded value would let one request tie up a worker.
+const ANONYMIZE_STRENGTH: std::ops::RangeInclusive<i32> = 3..=201;
+
+impl AnonymizeQuery {
+    fn method(&self) -> Result<AnonymizationMethod, ApiError> {
+        if let Some(strength) = self.strength {
+            if !ANONYMIZE_STRENGTH.contains(&strength) {
+                return Err(ApiError::bad_request(format!(
+                    "strength must be between {} and {}",
+                    ANONYMIZE_STRENGTH.start(),
+                    ANONYMIZE_STRENGTH.end()
+                )));
+            }
+        }
+
+        Ok(match self.mode.unwrap_or_default() {
+            AnonymizeMode::Blur => AnonymizationMethod::Blur {
+                kernel_size: odd_kernel(self.strength.unwrap_or(51)),
+            },
+            AnonymizeMode::OvalBlur => AnonymizationMethod::OvalBlur {
+                kernel_size: odd_kernel(self.strength.unwrap_or(51)),
+                feather: 15,
+            },
+            AnonymizeMode::Pixelate => AnonymizationMethod::Pixelate {
+                block_size: self.strength.unwrap_or(12),
+            },
+            AnonymizeMode::BlackOut => AnonymizationMethod::BlackOut,
+        })
+    }
+}
+
+/// Gaussian kernels must be odd; `size` is already at least 3.
+fn odd_kernel(size: i32) -> i32 {
+    size | 1
+}
+
+#[derive(Deserialize)]
+pub struct ClusterQuery {
+    threshold: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct ClusterGroup {
+    representative: String,
+    size: usize,
+    face_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ClusterResponse {
+    count: usize,
+    sizes: Vec<usize>,
+    clusters: Vec<ClusterGroup>,
+}
+
+#[derive(Serialize)]
+pub struct CountResponse {
+    count: usize,
+    boxes: Vec<BoundingBox>,
+}
+
+impl CountResponse {
+    fn new(boxes: Vec<BoundingBox>) -> Self {
+        Self { count: boxes.len(), boxes }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PoseRejection {
+    yaw: f32,
+    pitch: f32,
+    roll: f32,
+    max_yaw: f32,
+    max_pitch: f32,
+    max_roll: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoseGateConfig {
+    pub enabled: bool,   // Reject non-frontal faces on enrollment by default
+    pub max_yaw: f32,    // Degrees
+    pub max_pitch: f32,  // Degrees
+    pub max_roll: f32,   // Degrees
+}
+
+impl Default for PoseGateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_yaw: 30.0,
+            max_pitch: 20.0,
+            max_roll: 20.0,
+        }
+    }
+}
+
+impl PoseGateConfig {
+    pub fn check(&self, pose: &HeadPose) -> Result<(), PoseRejection> {
+        if pose.within_limits(self.max_yaw, self.max_pitch, self.max_roll) {
+            return Ok(());
+        }
+
+        Err(PoseRejection {
+            yaw: pose.yaw,
+            pitch: pose.pitch,
+            roll: pose.roll,
+            max_yaw: self.max_yaw,
+            max_pitch: self.max_pitch,
+            max_roll: self.max_roll,
+        })
+    }
 }
 
 pub struct ApiConfig {
@@ -34,6 +343,12 @@ pub struct ApiConfig {
     pub port: u16,
     pub upload_dir: String,
     pub cors_origins: Vec<String>,
+    pub model_paths: Vec<String>,  // Reported by the health endpoint
+    pub shutdown_timeout_secs: u64,
+    pub max_upload_bytes: usize,
+    pub pose_gate: PoseGateConfig,
+    pub reject_closed_eyes: bool,  // Refuse enrollment when a face's landmarks show both eyes closed
+    pub auth: AuthConfig,
 }
 
 impl Default for ApiConfig {
@@ -43,15 +358,38 @@ impl Default for ApiConfig {
             port: 8080,
             upload_dir: "uploads".to_string(),
             cors_origins: vec!["http://localhost:3000".to_string()],
+            model_paths: vec![
+                "models/face_attributes.onnx".to_string(),
+                "models/face_embedding.onnx".to_string(),
+            ],
+            shutdown_timeout_secs: 30,
+            max_upload_bytes: 10 * 1024 * 1024,
+            pose_gate: PoseGateConfig::default(),
+            reject_closed_eyes: false,
+            auth: AuthConfig::default(),
         }
     }
 }
 
+type SharedResultSink = Arc<Mutex<Box<dyn ResultSink>>>;
+
+/// Images per batch in `ApiServer::analyze_batch`; progress is broadcast
+/// once per batch.
+const BATCH_JOB_SIZE: usize = 8;
+
 pub struct ApiServer {
     config: ApiConfig,
     database: Database,
     embedding_generator: EmbeddingGenerator,
     report_generator: ReportGenerator,
+    pose_estimator: Option<Arc<PoseEstimator>>,
+    analyzer: Option<Arc<Analyzer>>,
+    session_pool: Option<Arc<SessionPool>>,
+    landmark_detector: Option<Arc<LandmarkDetector>>,
+    detection: Arc<RwLock<DetectionRuntime>>,
+    ws_manager: SharedWsManager,
+    metrics: Arc<ApiMetrics>,
+    result_sink: Option<SharedResultSink>,
 }
 
 impl ApiServer {
@@ -66,18 +404,162 @@ impl ApiServer {
             database,
             embedding_generator,
             report_generator,
+            pose_estimator: None,
+            analyzer: None,
+            session_pool: None,
+            landmark_detector: None,
+            detection: Arc::new(RwLock::new(DetectionRuntime::new(FaceDetector::new(
+                DetectorType::Haar,
+                DetectorThresholds::default().for_type(DetectorType::Haar),
+                opencv::core::Size::new(30, 30),
+                1.1,
+            )))),
+            ws_manager: Arc::new(tokio::sync::Mutex::new(WsManager::new())),
+            metrics: Arc::new(ApiMetrics::new()),
+            result_sink: None,
         }
     }
 
+    /// Handle for broadcasting events to websocket clients from outside the
+    /// REST handlers, e.g. from a batch job.
+    pub fn ws_manager(&self) -> SharedWsManager {
+        self.ws_manager.clone()
+    }
+
+    /// Analyze `images` with the analyzer from `with_analyzer`, broadcasting
+    /// a `Progress` message for `job_id` to websocket clients as each batch
+    /// finishes. Results line up with `images`; a failed image keeps its
+    /// error in its slot.
+    pub async fn analyze_batch(
+        &self,
+        job_id: impl Into<String>,
+        images: Vec<Mat>,
+    ) -> Result<Vec<Result<AnalysisResult>>> {
+        let analyzer = self
+            .analyzer
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Batch analysis needs an analyzer; see with_analyzer"))?;
+        BatchProcessor::new(BATCH_JOB_SIZE, rayon::current_num_threads(), false)
+            .with_progress(job_id, self.ws_manager())
+            .process_images(images, move |image| Ok(analyzer.analyze_mat(image)?))
+            .await
+    }
+
+    pub fn with_face_detector(mut self, face_detector: FaceDetector) -> Self {
+        self.detection = Arc::new(RwLock::new(DetectionRuntime::new(face_detector)));
+        self
+    }
+
+    /// Attribute analysis for every face in `/analyze` uploads. Without it
+    /// the response lists detected boxes only.
+    pub fn with_analyzer(mut self, analyzer: Analyzer) -> Self {
+        self.analyzer = Some(Arc::new(analyzer));
+        self
+    }
+
+    /// Queue `/analyze` attribute inference on `session_pool`, a pool of
+    /// attribute model sessions, instead of running it on the request
+    /// thread. Detection and post-processing still come from `with_analyzer`,
+    /// which must also be set.
+    pub fn with_session_pool(mut self, session_pool: SessionPool) -> Self {
+        self.session_pool = Some(Arc::new(session_pool));
+        self
+    }
+
+    /// Also publish every `/analyze` result to `sink`, keyed by the upload's
+    /// file id, the same way the batch CLI publishes its results.
+    pub fn with_result_sink(mut self, sink: Box<dyn ResultSink>) -> Self {
+        self.result_sink = Some(Arc::new(Mutex::new(sink)));
+        self
+    }
+
+    pub fn with_pose_estimator(mut self, pose_estimator: PoseEstimator) -> Self {
+        self.pose_estimator = Some(Arc::new(pose_estimator));
+        self
+    }
+
+    /// Landmarks are used to align enrolled faces when the database has a
+    /// gallery template configured. A face whose landmarks cannot be found
+    /// is stored as an unaligned crop.
+    pub fn with_landmark_detector(mut self, landmark_detector: LandmarkDetector) -> Self {
+        self.landmark_detector = Some(Arc::new(landmark_detector));
+        self
+    }
+
     pub async fn run(&self) -> Result<()> {
+        self.build_server().await?.await?;
+        Ok(())
+    }
+
+    /// Run until SIGINT/SIGTERM, then stop accepting connections, let in-flight
+    /// requests finish (up to `shutdown_timeout_secs`) and close the database
+    /// pool so pending writes are flushed before returning.
+    pub async fn run_until_shutdown(&self) -> Result<()> {
+        let server = self.build_server().await?;
+        let handle = server.handle();
+        let server_task = tokio::spawn(server);
+
+        wait_for_shutdown_signal().await?;
+        log::info!("Shutdown signal received, draining connections...");
+
+        handle.stop(true).await;
+        server_task.await??;
+
+        self.database.close().await;
+        log::info!("Server shut down cleanly");
+        Ok(())
+    }
+
+    /// Run a dummy inference through the loaded models before accepting
+    /// requests, so the first upload does not see a cold-start spike. A
+    /// failure is logged and left for the first request to report.
+    fn warm_up(&self) {
+        let start = Instant::now();
+        if let Err(e) = self.embedding_generator.warm_up() {
+            log::warn!("Embedding model warm-up failed: {}", e);
+        }
+        if let Some(analyzer) = &self.analyzer {
+            if let Err(e) = analyzer.warm_up() {
+                log::warn!("Attribute model warm-up failed: {}", e);
+            }
+        }
+        log::info!("Models warmed up in {} ms", start.elapsed().as_millis());
+    }
+
+    async fn build_server(&self) -> Result<Server> {
         fs::create_dir_all(&self.config.upload_dir).await?;
+        self.warm_up();
 
         let database = web::Data::new(self.database.clone());
         let embedding_generator = web::Data::new(self.embedding_generator.clone());
         let report_generator = web::Data::new(self.report_generator.clone());
         let upload_dir = self.config.upload_dir.clone();
+        let pose_gate = web::Data::new(self.config.pose_gate.clone());
+        let pose_estimator = web::Data::new(self.pose_estimator.clone());
+        let quality_gate = web::Data::new(
+            QualityAssessor::default().with_closed_eye_rejection(self.config.reject_closed_eyes),
+        );
+        let analyzer = web::Data::new(self.analyzer.clone());
+        let session_pool = web::Data::new(self.session_pool.clone());
+        let landmark_detector = web::Data::new(self.landmark_detector.clone());
+        let detection = web::Data::from(self.detection.clone());
+        let upload_limits = web::Data::new(UploadLimits {
+            max_upload_bytes: self.config.max_upload_bytes,
+        });
+        let auth_config = web::Data::new(self.config.auth.clone());
+        let ws_manager = web::Data::new(self.ws_manager.clone());
+        let metrics = web::Data::from(self.metrics.clone());
+        let result_sink = web::Data::new(self.result_sink.clone());
+        let protect_reads = self.config.auth.protect_reads;
+        if self.config.auth.api_keys.is_empty() {
+            log::warn!("No API keys configured; write and admin endpoints will reject every request");
+        }
+        let health = web::Data::new(DockerHealth::new(
+            self.config.model_paths.clone(),
+            self.config.upload_dir.clone(),
+        ));
 
-        HttpServer::new(move || {
+        let server = HttpServer::new(move || {
             let cors = Cors::default()
                 .allowed_origin_fn(|origin, _req_head| {
                     true
@@ -86,131 +568,654 @@ impl ApiServer {
                 .allowed_headers(vec!["Authorization", "Content-Type"])
                 .max_age(3600);
 
+            let request_metrics = metrics.clone();
             App::new()
                 .wrap(cors)
+                .wrap_fn(move |req, srv| {
+                    let metrics = request_metrics.clone();
+                    let method = req.method().to_string();
+                    srv.call(req).map(move |res| {
+                        match &res {
+                            Ok(res) => metrics.record_request(
+                                &method,
+                                res.request().match_pattern().as_deref(),
+                                res.status().as_u16(),
+                            ),
+                            Err(e) => metrics.record_request(
+                                &method,
+                                None,
+                                e.as_response_error().status_code().as_u16(),
+                            ),
+                        }
+                        res
+                    })
+                })
                 .app_data(database.clone())
                 .app_data(embedding_generator.clone())
                 .app_data(report_generator.clone())
                 .app_data(web::Data::new(upload_dir.clone()))
+                .app_data(pose_gate.clone())
+                .app_data(pose_estimator.clone())
+                .app_data(quality_gate.clone())
+                .app_data(analyzer.clone())
+                .app_data(session_pool.clone())
+                .app_data(landmark_detector.clone())
+                .app_data(detection.clone())
+                .app_data(health.clone())
+                .app_data(upload_limits.clone())
+                .app_data(auth_config.clone())
+                .app_data(ws_manager.clone())
+                .app_data(metrics.clone())
+                .app_data(result_sink.clone())
+                .route("/metrics", web::get().to(metrics::metrics))
+                .route("/ws", web::get().to(websocket::ws_handler))
                 .service(
                     web::scope("/api/v1")
-                        .route("/analyze", web::post().to(analyze_image))
-                        .route("/faces", web::get().to(list_faces))
-                        .route("/faces/{id}", web::get().to(get_face))
-                        .route("/faces/{id}", web::put().to(update_face))
-                        .route("/faces/{id}", web::delete().to(delete_face))
-                        .route("/report/html", web::get().to(generate_html_report))
-                        .route("/report/csv", web::get().to(export_csv))
+                        .route("/health", web::get().to(docker::health))
+                        .service(
+                            web::resource("/analyze")
+                                .wrap(auth::require_scope(Scope::Write))
+                                .route(web::post().to(analyze_image)),
+                        )
+                        .service(
+                            web::resource("/faces/{id}")
+                                .guard(guard::Any(guard::Put()).or(guard::Delete()))
+                                .wrap(auth::require_scope(Scope::Write))
+                                .route(web::put().to(update_face))
+                                .route(web::delete().to(delete_face)),
+                        )
+                        .service(
+                            web::resource("/config")
+                                .wrap(auth::require_scope(Scope::Admin))
+                                .route(web::get().to(get_config))
+                                .route(web::post().to(update_config)),
+                        )
+                        .service(
+                            web::resource("/admin/cleanup")
+                                .wrap(auth::require_scope(Scope::Admin))
+                                .route(web::post().to(cleanup_faces)),
+                        )
+                        .service(
+                            web::scope("")
+                                .wrap(Condition::new(protect_reads, auth::require_scope(Scope::Read)))
+                                .route("/faces", web::get().to(list_faces))
+                                .route("/faces/{id}", web::get().to(get_face))
+                                .route("/faces/{id}/image", web::get().to(get_face_image))
+                                .route("/clusters", web::get().to(cluster_faces))
+                                .route("/compare", web::post().to(compare_faces))
+                                .route("/anonymize", web::post().to(anonymize_image))
+                                .route("/count", web::post().to(count_faces))
+                                .route("/search", web::post().to(search_faces))
+                                .route("/report/html", web::get().to(generate_html_report))
+                                .route("/report/pdf", web::get().to(generate_pdf_report))
+                                .route("/report/csv", web::get().to(export_csv)),
+                        )
                 )
         })
+        .disable_signals()
+        .shutdown_timeout(self.config.shutdown_timeout_secs)
         .bind((self.config.host.clone(), self.config.port))?
-        .run()
-        .await?;
+        .run();
 
-        Ok(())
+        Ok(server)
     }
 }
 
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = sigterm.recv() => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
 async fn analyze_image(
     mut payload: Multipart,
     query: web::Query<AnalyzeQuery>,
     database: web::Data<Database>,
     embedding_generator: web::Data<EmbeddingGenerator>,
     upload_dir: web::Data<String>,
-) -> impl Responder {
-    if let Ok(Some(mut field)) = payload.try_next().await {
-        let content_type = field.content_disposition().unwrap();
-        let filename = content_type.get_filename().unwrap();
-        let file_id = Uuid::new_v4();
-        let file_path = Path::new(&**upload_dir).join(file_id.to_string());
-
-        let mut f = web::block(|| std::fs::File::create(file_path.clone())).await.unwrap();
-        while let Some(chunk) = field.next().await {
-            let data = chunk.unwrap();
-            f = web::block(move || f.write_all(&data).map(|_| f)).await.unwrap();
+    pose_gate: web::Data<PoseGateConfig>,
+    pose_estimator: web::Data<Option<Arc<PoseEstimator>>>,
+    quality_gate: web::Data<QualityAssessor>,
+    analyzer: web::Data<Option<Arc<Analyzer>>>,
+    session_pool: web::Data<Option<Arc<SessionPool>>>,
+    detection: web::Data<RwLock<DetectionRuntime>>,
+    landmark_detector: web::Data<Option<Arc<LandmarkDetector>>>,
+    upload_limits: web::Data<UploadLimits>,
+    ws_manager: web::Data<SharedWsManager>,
+    metrics: web::Data<ApiMetrics>,
+    result_sink: web::Data<Option<SharedResultSink>>,
+) -> Result<HttpResponse, ApiError> {
+    let (upload, image, pose_estimator) = receive_gated_upload(
+        &mut payload,
+        Path::new(&**upload_dir),
+        upload_limits.max_upload_bytes,
+        query.require_frontal.unwrap_or(pose_gate.enabled),
+        pose_estimator.as_deref(),
+    )
+    .await?;
+    metrics.observe_upload(upload.size);
+    let file_path = upload.path;
+
+    let stats = Arc::new(PerfStats::new());
+    let analyzed = analyze_upload(
+        &image,
+        analyzer.as_ref().as_ref(),
+        session_pool.as_ref().as_deref(),
+        &detection,
+        &metrics,
+        &stats,
+    )
+    .await;
+    let faces = match analyzed {
+        Ok(faces) => faces,
+        Err(e) => {
+            let _ = fs::remove_file(&file_path).await;
+            return Err(e);
+        }
+    };
+    if let Some(estimator) = pose_estimator {
+        let gated = check_face_poses(&image, &faces, &pose_gate, |crop| {
+            estimator.estimate(crop).map(|pose| pose.head_pose)
+        });
+        if let Err(e) = gated {
+            let _ = fs::remove_file(&file_path).await;
+            return Err(e);
         }
+    }
+    if let Err(e) = check_face_quality(&faces, &quality_gate) {
+        let _ = fs::remove_file(&file_path).await;
+        return Err(e);
+    }
+    let analysis = AnalysisResult { faces };
+    publish_result(result_sink.as_ref().as_ref(), &upload.file_id.to_string(), &analysis);
 
-        let embedding = match embedding_generator.generate(&file_path.to_string_lossy()) {
-            Ok(emb) => emb,
-            Err(e) => return HttpResponse::BadRequest().json(format!("Failed to generate embedding: {}", e)),
+    let mut existing = match query.dedupe_threshold {
+        Some(_) => database
+            .search_faces(&Default::default())
+            .await
+            .or_internal("Failed to search faces")?,
+        None => Vec::new(),
+    };
+    let source_image = file_path.to_string_lossy().into_owned();
+    let detector = match analyzer.as_ref() {
+        Some(analyzer) => analyzer.detector_type(),
+        None => read_detection(&detection)?.detector().detector_type(),
+    };
+
+    let mut enrolled = Vec::with_capacity(analysis.faces.len());
+    for face_result in analysis.faces {
+        let Some(crop) = crop_to_image(&image, face_result.bbox.rect()).or_bad_request("Failed to crop face")? else {
+            continue;
         };
+        let embedding = stats
+            .time(Stage::Embedding, || {
+                metrics.time(InferenceStage::Embedding, || embedding_generator.generate(&crop))
+            })
+            .or_bad_request("Failed to generate embedding")?;
+
+        let duplicate = query
+            .dedupe_threshold
+            .and_then(|threshold| find_duplicate(&embedding, &existing, threshold));
+        if let Some(duplicate) = duplicate {
+            enrolled.push(EnrolledFace::new(duplicate.clone(), face_result, true, &query));
+            continue;
+        }
 
         let face = FaceEmbedding {
-            face_id: file_id.to_string(),
+            face_id: Uuid::new_v4().to_string(),
             embedding,
             metadata: FaceMetadata {
                 name: None,
-                tags: vec![],
+                tags: face_result.tags.clone(),
                 timestamp: chrono::Utc::now(),
-                source_image: file_path.to_string_lossy().into_owned(),
+                source_image: source_image.clone(),
                 confidence: 1.0,
+                details: StoredMetadata {
+                    detector: Some(detector),
+                    quality: face_result.quality.clone(),
+                    attributes: face_result.attributes.clone(),
+                    ..Default::default()
+                },
             },
         };
 
-        if let Err(e) = database.store_face(face.clone()).await {
-            return HttpResponse::InternalServerError().json(format!("Failed to store face: {}", e));
+        let bbox = face_result.bbox;
+        let landmarks = landmark_detector.as_ref().as_ref().and_then(|detector| match detector.detect(&crop) {
+            Ok(landmarks) => Some(landmarks.translated(bbox.x as f32, bbox.y as f32)),
+            Err(e) => {
+                log::warn!("Landmark detection failed, storing the face unaligned: {}", e);
+                None
+            }
+        });
+        let stored = match landmarks {
+            Some(landmarks) => database.store_face_normalized(face.clone(), &image, &landmarks, bbox).await,
+            None => database.store_face_crop(face.clone(), &crop, bbox).await,
+        };
+
+        match stored.or_internal("Failed to store face")? {
+            StoreOutcome::Inserted => {
+                metrics.record_enrolled();
+                websocket::notify_face_detected(&ws_manager, face.clone()).await;
+                // Later faces in this upload are checked against this one too.
+                if query.dedupe_threshold.is_some() {
+                    existing.push(face.clone());
+                }
+                enrolled.push(EnrolledFace::new(face, face_result, false, &query));
+            }
+            StoreOutcome::Duplicate(existing_id) => {
+                let existing = database
+                    .get_face(&existing_id)
+                    .await
+                    .or_internal("Failed to get face")?
+                    .ok_or_else(|| ApiError::internal("Duplicate face disappeared"))?;
+                enrolled.push(EnrolledFace::new(existing, face_result, true, &query));
+            }
         }
+    }
 
-        let response = AnalyzeResponse {
-            face_id: face.face_id,
-            name: face.metadata.name,
-            tags: face.metadata.tags,
-            confidence: face.metadata.confidence,
-            embedding: query.include_embeddings.unwrap_or(false).then(|| face.embedding),
+    // Every stored face has its own copy of its crop, so nothing refers to
+    // the upload any more.
+    let _ = fs::remove_file(&file_path).await;
+
+    let mut response = AnalyzeImageResponse::new(enrolled);
+    if query.debug.unwrap_or(false) {
+        response = response.with_timings(&stats);
+    }
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Hand the result to the configured sink. A failing sink is logged rather
+/// than failing the request, whose faces are still stored and returned.
+fn publish_result(sink: Option<&SharedResultSink>, key: &str, result: &AnalysisResult) {
+    let Some(sink) = sink else { return };
+    let published = match sink.lock() {
+        Ok(mut sink) => sink.publish(key, result),
+        Err(_) => Err(anyhow::anyhow!("Result sink lock is poisoned")),
+    };
+    if let Err(e) = published {
+        log::warn!("Failed to publish result for {}: {}", key, e);
+    }
+}
+
+/// Every face in the upload with its attributes, or just the detected boxes
+/// when no attribute analyzer is configured. When nothing is detected the
+/// whole upload is treated as one face, so pre-cropped face images still
+/// enroll. With a session pool the analysis is queued on it rather than run
+/// on the request thread.
+async fn analyze_upload(
+    image: &Mat,
+    analyzer: Option<&Arc<Analyzer>>,
+    session_pool: Option<&SessionPool>,
+    detection: &RwLock<DetectionRuntime>,
+    metrics: &ApiMetrics,
+    stats: &Arc<PerfStats>,
+) -> Result<Vec<FaceResult>, ApiError> {
+    let faces = match analyzer {
+        Some(analyzer) => {
+            let start = Instant::now();
+            let analyzed = match session_pool {
+                Some(session_pool) => {
+                    let analyzer = analyzer.clone();
+                    let image = image.try_clone().or_internal("Failed to copy image")?;
+                    let stats = stats.clone();
+                    session_pool
+                        .run(move |session| analyzer.analyze_mat_on(&image, session, &stats))
+                        .await
+                        .map_err(FaceAnalyzerError::from)
+                        .and_then(|result| result)
+                }
+                None => analyzer.analyze_mat_with_stats(image, stats),
+            };
+            metrics.observe_inference(InferenceStage::Analysis, start.elapsed());
+            analyzed
+                .map_err(|e| {
+                    metrics.record_detection_failure();
+                    e
+                })
+                .or_internal("Failed to analyze image")?
+                .faces
+        }
+        None => {
+            let detection = read_detection(detection)?;
+            stats
+                .time(Stage::Detection, || detect_faces(image, &detection, metrics))
+                .or_internal("Failed to detect faces")?
+                .into_iter()
+                .map(|detection| FaceResult {
+                    bbox: detection.bbox,
+                    attributes: None,
+                    quality: None,
+                    tags: Vec::new(),
+                })
+                .collect()
+        }
+    };
+
+    if !faces.is_empty() {
+        return Ok(faces);
+    }
+    Ok(vec![FaceResult {
+        bbox: BoundingBox::new(0, 0, image.cols(), image.rows()),
+        attributes: None,
+        quality: None,
+        tags: Vec::new(),
+    }])
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    pub max_upload_bytes: usize,
+}
+
+enum UploadError {
+    TooLarge,
+    Stream(String),
+    Io(String),
+}
+
+const ALLOWED_IMAGE_TYPES: [&str; 6] = ["image/jpeg", "image/png", "image/bmp", "image/webp", "image/heic", "image/heif"];
+
+fn is_allowed_image_type(content_type: Option<&mime::Mime>) -> bool {
+    content_type
+        .map(|mime| ALLOWED_IMAGE_TYPES.contains(&mime.essence_str()))
+        .unwrap_or(false)
+}
+
+fn unsupported_image_type_error() -> ApiError {
+    ApiError::unsupported_media_type(format!("Only {} uploads are accepted", ALLOWED_IMAGE_TYPES.join(", ")))
+}
+
+struct Upload {
+    file_id: Uuid,
+    path: PathBuf,
+    size: usize,  // Bytes written
+}
+
+/// Name for the stored upload. The client filename is reduced to its last
+/// path component and prefixed with the id; when the part has no filename
+/// the id alone is used.
+fn upload_filename(content_disposition: Option<&ContentDisposition>, file_id: Uuid) -> String {
+    content_disposition
+        .and_then(|disposition| disposition.get_filename())
+        .and_then(|name| Path::new(name).file_name())
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.is_empty())
+        .map(|name| format!("{}_{}", file_id, name))
+        .unwrap_or_else(|| file_id.to_string())
+}
+
+/// Save and decode an `/analyze` upload. When `require_frontal` is set this
+/// also returns the estimator for the pose gate, failing before anything is
+/// saved if none is configured. The saved file is removed if it does not
+/// decode.
+async fn receive_gated_upload<'a>(
+    payload: &mut Multipart,
+    upload_dir: &Path,
+    max_upload_bytes: usize,
+    require_frontal: bool,
+    pose_estimator: Option<&'a PoseEstimator>,
+) -> Result<(Upload, Mat, Option<&'a PoseEstimator>), ApiError> {
+    let pose_estimator = match (require_frontal, pose_estimator) {
+        (false, _) => None,
+        (true, Some(estimator)) => Some(estimator),
+        (true, None) => return Err(pose_unavailable_error()),
+    };
+
+    let upload = receive_upload(payload, upload_dir, max_upload_bytes).await?;
+    match input::read_image(&upload.path.to_string_lossy()) {
+        Ok(image) => Ok((upload, image, pose_estimator)),
+        Err(e) => {
+            let _ = fs::remove_file(&upload.path).await;
+            Err(unreadable_image_error(e))
+        }
+    }
+}
+
+/// Save the first multipart part under `upload_dir`. Malformed multipart
+/// data is reported as a 400 rather than panicking the worker.
+async fn receive_upload(
+    payload: &mut Multipart,
+    upload_dir: &Path,
+    max_upload_bytes: usize,
+) -> Result<Upload, ApiError> {
+    let mut field = match payload.try_next().await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Err(ApiError::bad_request("Multipart form data contains no parts")),
+        Err(e) => return Err(ApiError::bad_request(format!("Invalid multipart form data: {}", e))),
+    };
+
+    if !is_allowed_image_type(field.content_type()) {
+        return Err(unsupported_image_type_error());
+    }
+
+    let file_id = Uuid::new_v4();
+    let path = upload_dir.join(upload_filename(field.content_disposition(), file_id));
+
+    let size = match save_upload(&mut field, &path, max_upload_bytes).await {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = fs::remove_file(&path).await;
+            return Err(match e {
+            UploadError::TooLarge => ApiError::payload_too_large(format!(
+                "Upload exceeds the {} byte limit",
+                max_upload_bytes
+            )),
+            UploadError::Stream(e) => ApiError::bad_request(format!("Failed to read upload: {}", e)),
+                UploadError::Io(e) => ApiError::internal(format!("Failed to save upload: {}", e)),
+            });
+        }
+    };
+
+    Ok(Upload { file_id, path, size })
+}
+
+/// Stream a multipart field to disk, stopping as soon as the running total
+/// exceeds `max_bytes`. The caller removes the partial file on error.
+async fn save_upload(
+    field: &mut actix_multipart::Field,
+    file_path: &Path,
+    max_bytes: usize,
+) -> std::result::Result<usize, UploadError> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = fs::File::create(file_path)
+        .await
+        .map_err(|e| UploadError::Io(e.to_string()))?;
+    let mut written = 0;
+
+    while let Some(chunk) = field.next().await {
+        let data = chunk.map_err(|e| UploadError::Stream(e.to_string()))?;
+        written += data.len();
+        if written > max_bytes {
+            return Err(UploadError::TooLarge);
+        }
+        file.write_all(&data)
+            .await
+            .map_err(|e| UploadError::Io(e.to_string()))?;
+    }
+
+    file.flush().await.map_err(|e| UploadError::Io(e.to_string()))?;
+    Ok(written)
+}
+
+/// Buffer a multipart field and decode it, giving up with a 413 as soon as
+/// it grows past `max_upload_bytes`.
+async fn read_image_field(
+    field: &mut actix_multipart::Field,
+    max_upload_bytes: usize,
+    metrics: &ApiMetrics,
+) -> Result<Mat, ApiError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let data = chunk.or_bad_request("Failed to read upload")?;
+        if bytes.len() + data.len() > max_upload_bytes {
+            return Err(ApiError::payload_too_large(format!(
+                "Upload exceeds the {} byte limit",
+                max_upload_bytes
+            )));
+        }
+        bytes.extend_from_slice(&data);
+    }
+    metrics.observe_upload(bytes.len());
+
+    input::decode_image(&bytes).map_err(unreadable_image_error)
+}
+
+fn unreadable_image_error(error: FaceAnalyzerError) -> ApiError {
+    ApiError::bad_request(format!("Uploaded file is not a readable image: {}", error))
+}
+
+fn find_duplicate<'a>(
+    embedding: &[f32],
+    existing: &'a [FaceEmbedding],
+    threshold: f32,
+) -> Option<&'a FaceEmbedding> {
+    let (face_id, _) = EmbeddingComparator::find_matches(embedding, existing, threshold, Metric::Cosine)
+        .into_iter()
+        .next()?;
+    existing.iter().find(|face| face.face_id == face_id)
+}
+
+/// Estimate the head pose of each detected face on its own crop and reject
+/// the upload if any face is outside the gate's limits.
+fn check_face_poses(
+    image: &Mat,
+    faces: &[FaceResult],
+    gate: &PoseGateConfig,
+    mut estimate: impl FnMut(&Mat) -> Result<HeadPose>,
+) -> Result<(), ApiError> {
+    for face in faces {
+        let Some(crop) = crop_to_image(image, face.bbox.rect()).or_bad_request("Failed to crop face")? else {
+            continue;
         };
+        let pose = estimate(&crop).or_internal("Failed to estimate pose")?;
+        gate.check(&pose).map_err(pose_rejected_error)?;
+    }
+    Ok(())
+}
 
-        HttpResponse::Ok().json(response)
-    } else {
-        HttpResponse::BadRequest().body("Invalid multipart form data")
+/// Reject the upload if any face's quality metrics fail `assessor`, e.g.
+/// closed eyes with `ApiConfig::reject_closed_eyes`. Faces without metrics
+/// pass.
+fn check_face_quality(faces: &[FaceResult], assessor: &QualityAssessor) -> Result<(), ApiError> {
+    match faces.iter().filter_map(|face| face.quality.as_ref()).find(|quality| !assessor.accepts(quality)) {
+        Some(quality) => Err(ApiError::unprocessable("quality_rejected", "Face does not meet the enrollment quality gate")
+            .with_details(quality.get_quality_description())),
+        None => Ok(()),
     }
 }
 
+fn pose_unavailable_error() -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "pose_unavailable",
+        "Frontal pose is required but no pose model is configured",
+    )
+}
+
+fn pose_rejected_error(rejection: PoseRejection) -> ApiError {
+    ApiError::unprocessable("pose_rejected", "Face is not frontal enough for enrollment")
+        .with_details(rejection)
+}
+
 async fn list_faces(
     database: web::Data<Database>,
     query: web::Query<AnalyzeQuery>,
-) -> impl Responder {
-    let faces = match database.search_faces(&Default::default()).await {
-        Ok(faces) => faces,
-        Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to list faces: {}", e)),
-    };
+) -> Result<HttpResponse, ApiError> {
+    let faces = database
+        .search_faces(&query.search_query())
+        .await
+        .or_internal("Failed to list faces")?;
 
     let responses: Vec<AnalyzeResponse> = faces
         .into_iter()
-        .filter(|face| {
-            query.min_confidence
-                .map(|min| face.metadata.confidence >= min)
-                .unwrap_or(true)
-        })
         .map(|face| AnalyzeResponse {
             face_id: face.face_id,
             name: face.metadata.name,
             tags: face.metadata.tags,
             confidence: face.metadata.confidence,
-            embedding: query.include_embeddings.unwrap_or(false).then(|| face.embedding),
+            embedding: query.encode_embedding(&face.embedding),
+            duplicate: false,
         })
         .collect();
 
-    HttpResponse::Ok().json(responses)
+    Ok(HttpResponse::Ok().json(responses))
 }
 
 async fn get_face(
     id: web::Path<String>,
     query: web::Query<AnalyzeQuery>,
     database: web::Data<Database>,
-) -> impl Responder {
-    match database.get_face(&id).await {
-        Ok(Some(face)) => {
-            let response = AnalyzeResponse {
-                face_id: face.face_id,
-                name: face.metadata.name,
-                tags: face.metadata.tags,
-                confidence: face.metadata.confidence,
-                embedding: query.include_embeddings.unwrap_or(false).then(|| face.embedding),
-            };
-            HttpResponse::Ok().json(response)
+) -> Result<HttpResponse, ApiError> {
+    let face = database
+        .get_face(&id)
+        .await
+        .or_internal("Failed to get face")?
+        .ok_or_else(|| ApiError::not_found("Face not found"))?;
+
+    let response = AnalyzeResponse {
+        face_id: face.face_id,
+        name: face.metadata.name,
+        tags: face.metadata.tags,
+        confidence: face.metadata.confidence,
+        embedding: query.encode_embedding(&face.embedding),
+        duplicate: false,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Serve the stored image for a face, decrypted when storage is encrypted.
+/// A row whose file has gone missing is reported as 404 rather than 500.
+async fn get_face_image(
+    id: web::Path<String>,
+    database: web::Data<Database>,
+) -> Result<HttpResponse, ApiError> {
+    let face = database
+        .get_face(&id)
+        .await
+        .or_internal("Failed to get face")?
+        .ok_or_else(|| ApiError::not_found("Face not found"))?;
+
+    let data = match database.read_image(&face.metadata.source_image).await {
+        Ok(data) => data,
+        Err(err) if is_missing_file(&err) => {
+            return Err(ApiError::not_found("Face image not found"));
         }
-        Ok(None) => HttpResponse::NotFound().body("Face not found"),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to get face: {}", e)),
+        Err(err) => return Err(err).or_internal("Failed to read face image"),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(stored_image_content_type(&data))
+        .body(data))
+}
+
+fn is_missing_file(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .map(|err| err.kind() == std::io::ErrorKind::NotFound)
+        .unwrap_or(false)
+}
+
+/// Stored images keep the format they were uploaded in, so sniff it rather
+/// than assuming JPEG.
+fn stored_image_content_type(data: &[u8]) -> &'static str {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::Png) => "image/png",
+        Ok(image::ImageFormat::Bmp) => "image/bmp",
+        Ok(image::ImageFormat::WebP) => "image/webp",
+        _ => "image/jpeg",
     }
 }
 
@@ -224,59 +1229,733 @@ async fn update_face(
     id: web::Path<String>,
     update: web::Json<FaceUpdate>,
     database: web::Data<Database>,
-) -> impl Responder {
+    ws_manager: web::Data<SharedWsManager>,
+) -> Result<HttpResponse, ApiError> {
     let updates = crate::database::storage::FaceUpdates {
         name: update.name.clone(),
         tags: update.tags.clone(),
         confidence: None,
     };
 
-    match database.update_face(&id, updates).await {
-        Ok(()) => HttpResponse::Ok().finish(),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to update face: {}", e)),
+    database.update_face(&id, updates).await.or_internal("Failed to update face")?;
+
+    if let Some(face) = database.get_face(&id).await.or_internal("Failed to get face")? {
+        websocket::notify_face_updated(&ws_manager, face).await;
     }
+    Ok(HttpResponse::Ok().finish())
 }
 
 async fn delete_face(
     id: web::Path<String>,
     database: web::Data<Database>,
-) -> impl Responder {
-    match database.delete_face(&id).await {
-        Ok(()) => HttpResponse::Ok().finish(),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to delete face: {}", e)),
+    ws_manager: web::Data<SharedWsManager>,
+) -> Result<HttpResponse, ApiError> {
+    database.delete_face(&id).await.or_internal("Failed to delete face")?;
+    websocket::notify_face_deleted(&ws_manager, id.into_inner()).await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn compare_faces(
+    mut payload: Multipart,
+    query: web::Query<CompareQuery>,
+    detection: web::Data<RwLock<DetectionRuntime>>,
+    embedding_generator: web::Data<EmbeddingGenerator>,
+    upload_limits: web::Data<UploadLimits>,
+    metrics: web::Data<ApiMetrics>,
+) -> Result<HttpResponse, ApiError> {
+    let mut images = Vec::with_capacity(2);
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let image = read_image_field(&mut field, upload_limits.max_upload_bytes, &metrics)
+            .await
+            .map_err(|e| {
+                ApiError::new(e.status_code(), e.code(), format!("Image {}: {}", images.len() + 1, e.message()))
+            })?;
+        images.push(image);
+    }
+
+    if images.len() != 2 {
+        return Err(ApiError::bad_request("Expected exactly two image parts"));
     }
+
+    let detection = read_detection(&detection)?;
+    let mut embeddings = Vec::with_capacity(2);
+    for (i, image) in images.iter().enumerate() {
+        let embedding = largest_face_embedding(image, &detection, &embedding_generator, &metrics)
+            .or_bad_request("Failed to generate embedding")?
+            .ok_or_else(|| ApiError::bad_request(format!("No face detected in image {}", i + 1)))?;
+        embeddings.push(embedding);
+    }
+
+    Ok(HttpResponse::Ok().json(compare_embeddings(
+        &embeddings[0],
+        &embeddings[1],
+        query.threshold.unwrap_or(0.5),
+    )))
 }
 
-async fn generate_html_report(
+/// Face count and boxes only. Skips attribute and embedding inference and
+/// stores nothing, so it is a cheap first call before a full `/analyze`.
+async fn count_faces(
+    mut payload: Multipart,
+    detection: web::Data<RwLock<DetectionRuntime>>,
+    upload_limits: web::Data<UploadLimits>,
+    metrics: web::Data<ApiMetrics>,
+) -> Result<HttpResponse, ApiError> {
+    let mut field = match payload.try_next().await {
+        Ok(Some(field)) => field,
+        _ => return Err(ApiError::bad_request("Invalid multipart form data")),
+    };
+    let image = read_image_field(&mut field, upload_limits.max_upload_bytes, &metrics).await?;
+
+    let boxes = detect_faces(&image, &*read_detection(&detection)?, &metrics)
+        .or_internal("Failed to detect faces")?
+        .into_iter()
+        .map(|detection| detection.bbox)
+        .collect();
+    Ok(HttpResponse::Ok().json(CountResponse::new(boxes)))
+}
+
+async fn anonymize_image(
+    mut payload: Multipart,
+    query: web::Query<AnonymizeQuery>,
+    detection: web::Data<RwLock<DetectionRuntime>>,
+    upload_limits: web::Data<UploadLimits>,
+    metrics: web::Data<ApiMetrics>,
+) -> Result<HttpResponse, ApiError> {
+    let mut field = match payload.try_next().await {
+        Ok(Some(field)) => field,
+        _ => return Err(ApiError::bad_request("Invalid multipart form data")),
+    };
+    let image = read_image_field(&mut field, upload_limits.max_upload_bytes, &metrics).await?;
+
+    let detections = detect_faces(&image, &*read_detection(&detection)?, &metrics)
+        .or_internal("Failed to detect faces")?;
+    let redacted = Anonymizer::new(query.method()?)
+        .anonymize_image(&image, &detections)
+        .or_internal("Failed to anonymize image")?;
+
+    let mut buffer = opencv::core::Vector::<u8>::new();
+    imgcodecs::imencode(".jpg", &redacted, &mut buffer, &opencv::core::Vector::new())
+        .or_internal("Failed to encode image")?;
+
+    Ok(HttpResponse::Ok().content_type("image/jpeg").body(buffer.to_vec()))
+}
+
+async fn search_faces(
+    mut payload: Multipart,
+    query: web::Query<SearchQueryParams>,
     database: web::Data<Database>,
-    report_generator: web::Data<ReportGenerator>,
-) -> impl Responder {
-    let faces = match database.search_faces(&Default::default()).await {
-        Ok(faces) => faces,
-        Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to get faces: {}", e)),
+    detection: web::Data<RwLock<DetectionRuntime>>,
+    embedding_generator: web::Data<EmbeddingGenerator>,
+    upload_limits: web::Data<UploadLimits>,
+    metrics: web::Data<ApiMetrics>,
+) -> Result<HttpResponse, ApiError> {
+    let mut field = match payload.try_next().await {
+        Ok(Some(field)) => field,
+        _ => return Err(ApiError::bad_request("Invalid multipart form data")),
     };
+    let image = read_image_field(&mut field, upload_limits.max_upload_bytes, &metrics).await?;
+
+    let detections = detect_faces(&image, &*read_detection(&detection)?, &metrics)
+        .or_internal("Failed to detect faces")?;
 
-    match report_generator.generate_html_report(&faces, "Face Analysis Report").await {
-        Ok(path) => HttpResponse::Ok().json(path),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to generate report: {}", e)),
+    let mut query_faces = Vec::with_capacity(detections.len());
+    for detection in detections {
+        let Some(crop) = crop_to_image(&image, detection.bbox.rect()).or_bad_request("Failed to crop face")? else {
+            continue;
+        };
+        let embedding = metrics
+            .time(InferenceStage::Embedding, || embedding_generator.generate(&crop))
+            .or_bad_request("Failed to generate embedding")?;
+        query_faces.push((detection.bbox, embedding));
     }
+
+    let gallery = database
+        .search_faces(&Default::default())
+        .await
+        .or_internal("Failed to get faces")?;
+
+    Ok(HttpResponse::Ok().json(match_query_faces(
+        &query_faces,
+        &gallery,
+        query.threshold.unwrap_or(0.5),
+        query.limit.unwrap_or(5),
+    )))
+}
+
+fn match_query_faces(
+    query_faces: &[(BoundingBox, Vec<f32>)],
+    gallery: &[FaceEmbedding],
+    threshold: f32,
+    limit: usize,
+) -> Vec<SearchGroup> {
+    query_faces
+        .iter()
+        .map(|(bbox, embedding)| SearchGroup {
+            bbox: *bbox,
+            matches: EmbeddingComparator::find_matches(embedding, gallery, threshold, Metric::Cosine)
+                .into_iter()
+                .take(limit)
+                .map(|(face_id, similarity)| SearchMatch { face_id, similarity })
+                .collect(),
+        })
+        .collect()
+}
+
+fn largest_face_embedding(
+    image: &Mat,
+    detection: &DetectionRuntime,
+    embedding_generator: &EmbeddingGenerator,
+    metrics: &ApiMetrics,
+) -> Result<Option<Vec<f32>>> {
+    let largest = detect_faces(image, detection, metrics)?
+        .into_iter()
+        .max_by_key(|detection| detection.bbox.area());
+
+    let face_roi = match largest {
+        Some(detection) => crop_to_image(image, detection.bbox.rect())?,
+        None => None,
+    };
+    match face_roi {
+        Some(face_roi) => {
+            let embedding = metrics.time(InferenceStage::Embedding, || embedding_generator.generate(&face_roi))?;
+            Ok(Some(embedding))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Run the detector, recording its latency and counting failures.
+fn detect_faces(image: &Mat, detection: &DetectionRuntime, metrics: &ApiMetrics) -> Result<Vec<DetectionResult>> {
+    metrics
+        .time(InferenceStage::Detection, || detection.detect(image))
+        .map_err(|e| {
+            metrics.record_detection_failure();
+            e
+        })
+}
+
+fn read_detection(
+    detection: &RwLock<DetectionRuntime>,
+) -> Result<RwLockReadGuard<'_, DetectionRuntime>, ApiError> {
+    detection
+        .read()
+        .map_err(|_| ApiError::internal("Detection settings lock is poisoned"))
+}
+
+async fn get_config(
+    detection: web::Data<RwLock<DetectionRuntime>>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(read_detection(&detection)?.config()))
+}
+
+/// Replace the detection settings for every subsequent request. Invalid
+/// settings are rejected without touching the current ones.
+async fn update_config(
+    config: web::Json<RuntimeConfig>,
+    detection: web::Data<RwLock<DetectionRuntime>>,
+) -> Result<HttpResponse, ApiError> {
+    let mut runtime = detection
+        .write()
+        .map_err(|_| ApiError::internal("Detection settings lock is poisoned"))?;
+    runtime
+        .apply(config.into_inner())
+        .or_bad_request("Invalid configuration")?;
+    log::info!(
+        "Detection settings updated: {:?} at confidence {}",
+        runtime.detector().detector_type(),
+        runtime.detector().confidence_threshold()
+    );
+    Ok(HttpResponse::Ok().json(runtime.config()))
+}
+
+fn compare_embeddings(first: &[f32], second: &[f32], threshold: f32) -> CompareResponse {
+    let cosine_similarity = EmbeddingComparator::cosine_similarity(first, second);
+    CompareResponse {
+        cosine_similarity,
+        euclidean_distance: EmbeddingComparator::euclidean_distance(first, second),
+        threshold,
+        is_match: cosine_similarity >= threshold,
+    }
+}
+
+async fn cluster_faces(
+    query: web::Query<ClusterQuery>,
+    database: web::Data<Database>,
+) -> Result<HttpResponse, ApiError> {
+    let faces = database
+        .search_faces(&Default::default())
+        .await
+        .or_internal("Failed to get faces")?;
+
+    Ok(HttpResponse::Ok().json(build_clusters(&faces, query.threshold.unwrap_or(0.6))))
+}
+
+fn build_clusters(faces: &[FaceEmbedding], threshold: f32) -> ClusterResponse {
+    let clusters: Vec<ClusterGroup> = EmbeddingComparator::cluster_embeddings(faces, threshold)
+        .into_iter()
+        .map(|face_ids| {
+            let members: Vec<&FaceEmbedding> = faces
+                .iter()
+                .filter(|face| face_ids.contains(&face.face_id))
+                .collect();
+            let representative = EmbeddingComparator::medoid(&members)
+                .map(|face| face.face_id.clone())
+                .unwrap_or_else(|| face_ids[0].clone());
+
+            ClusterGroup {
+                representative,
+                size: face_ids.len(),
+                face_ids,
+            }
+        })
+        .collect();
+
+    ClusterResponse {
+        count: clusters.len(),
+        sizes: clusters.iter().map(|c| c.size).collect(),
+        clusters,
+    }
+}
+
+#[derive(Deserialize)]
+struct CleanupQuery {
+    days: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct CleanupResponse {
+    deleted: u64,
+}
+
+async fn cleanup_faces(
+    query: web::Query<CleanupQuery>,
+    database: web::Data<Database>,
+) -> Result<HttpResponse, ApiError> {
+    let days = query.days.unwrap_or(30);
+    if days < 0 {
+        return Err(ApiError::bad_request("days must not be negative"));
+    }
+
+    let deleted = database
+        .cleanup_old_faces(days)
+        .await
+        .or_internal("Failed to clean up faces")?;
+    Ok(HttpResponse::Ok().json(CleanupResponse { deleted }))
+}
+
+async fn generate_html_report(
+    database: web::Data<Database>,
+    report_generator: web::Data<ReportGenerator>,
+) -> Result<HttpResponse, ApiError> {
+    let faces = database
+        .search_faces(&Default::default())
+        .await
+        .or_internal("Failed to get faces")?;
+
+    let path = report_generator
+        .generate_html_report(&faces, "Face Analysis Report")
+        .await
+        .or_internal("Failed to generate report")?;
+    Ok(HttpResponse::Ok().json(path))
+}
+
+async fn generate_pdf_report(
+    database: web::Data<Database>,
+    report_generator: web::Data<ReportGenerator>,
+) -> Result<HttpResponse, ApiError> {
+    let faces = database
+        .search_faces(&Default::default())
+        .await
+        .or_internal("Failed to get faces")?;
+
+    let path = report_generator
+        .generate_pdf_report(&faces, "Face Analysis Report")
+        .await
+        .or_internal("Failed to generate report")?;
+    Ok(HttpResponse::Ok().json(path))
 }
 
 async fn export_csv(
-    query: web::Query<AnalyzeQuery>,
+    query: web::Query<CsvExportQuery>,
     database: web::Data<Database>,
     report_generator: web::Data<ReportGenerator>,
-) -> impl Responder {
-    let faces = match database.search_faces(&Default::default()).await {
-        Ok(faces) => faces,
-        Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to get faces: {}", e)),
-    };
+) -> Result<HttpResponse, ApiError> {
+    let options = query.options().or_bad_request("Invalid CSV export options")?;
+    let faces = database
+        .search_faces(&Default::default())
+        .await
+        .or_internal("Failed to get faces")?;
 
-    match report_generator
-        .export_csv(&faces, query.include_embeddings.unwrap_or(false))
+    let path = report_generator
+        .export_csv(&faces, &options)
         .await
-    {
-        Ok(path) => HttpResponse::Ok().json(path),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to export CSV: {}", e)),
+        .or_internal("Failed to export CSV")?;
+    Ok(HttpResponse::Ok().json(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_pose() -> HeadPose {
+        HeadPose {
+            yaw: 75.0,
+            pitch: 5.0,
+            roll: 2.0,
+            yaw_confidence: 0.9,
+            pitch_confidence: 0.9,
+            roll_confidence: 0.9,
+        }
+    }
+
+    #[test]
+    fn test_pose_gate_rejects_profile_face() {
+        let gate = PoseGateConfig { enabled: true, ..Default::default() };
+        let rejection = gate.check(&profile_pose()).unwrap_err();
+        assert_eq!(rejection.yaw, 75.0);
+
+        let error = pose_rejected_error(rejection);
+        assert_eq!(error.code(), "pose_rejected");
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_check_face_poses_estimates_each_crop() {
+        let image = Mat::new_rows_cols_with_default(120, 200, opencv::core::CV_8UC3, opencv::core::Scalar::all(0.0))
+            .unwrap();
+        let face = |x, y, width, height| FaceResult {
+            bbox: BoundingBox::new(x, y, width, height),
+            attributes: None,
+            quality: None,
+            tags: Vec::new(),
+        };
+        // The second face runs off the right edge and is estimated on its clamped crop
+        let faces = vec![face(10, 10, 40, 50), face(170, 20, 60, 60)];
+        let gate = PoseGateConfig { enabled: true, ..Default::default() };
+        let frontal = HeadPose { yaw: 3.0, ..profile_pose() };
+
+        let mut crops = Vec::new();
+        let passed = check_face_poses(&image, &faces, &gate, |crop| {
+            crops.push((crop.cols(), crop.rows()));
+            Ok(frontal.clone())
+        });
+        assert!(passed.is_ok());
+        assert_eq!(crops, vec![(40, 50), (30, 60)]);
+
+        let rejected = check_face_poses(&image, &faces, &gate, |crop| {
+            Ok(if crop.cols() == 30 { profile_pose() } else { frontal.clone() })
+        })
+        .unwrap_err();
+        assert_eq!(rejected.code(), "pose_rejected");
+        assert_eq!(rejected.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let failed = check_face_poses(&image, &faces, &gate, |_| Err(anyhow::anyhow!("bad crop"))).unwrap_err();
+        assert_eq!(failed.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_closed_eyes_fail_the_enrollment_quality_gate() {
+        let face = |eyes_closed| FaceResult {
+            bbox: BoundingBox::new(0, 0, 40, 40),
+            attributes: None,
+            quality: Some(QualityMetrics {
+                brightness: 0.5,
+                contrast: 0.5,
+                sharpness: 0.5,
+                blur_score: 0.8,
+                face_size: 1.0,
+                face_angle: 0.0,
+                occlusion: 0.0,
+                symmetry: 0.9,
+                overall_score: 0.7,
+                eyes_closed,
+            }),
+            tags: Vec::new(),
+        };
+        let faces = vec![face(Some(false)), face(Some(true))];
+
+        assert!(check_face_quality(&faces, &QualityAssessor::default()).is_ok());
+        let strict = QualityAssessor::default().with_closed_eye_rejection(true);
+        let error = check_face_quality(&faces, &strict).unwrap_err();
+        assert_eq!(error.code(), "quality_rejected");
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(check_face_quality(&[face(None)], &strict).is_ok());
+    }
+
+    fn gallery_face(face_id: &str, embedding: Vec<f32>) -> FaceEmbedding {
+        FaceEmbedding {
+            face_id: face_id.to_string(),
+            embedding,
+            metadata: FaceMetadata {
+                name: None,
+                tags: vec![],
+                timestamp: chrono::Utc::now(),
+                source_image: format!("{}.jpg", face_id),
+                confidence: 1.0,
+                details: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_find_duplicate_returns_existing_face() {
+        let existing = vec![
+            gallery_face("a", vec![1.0, 0.0, 0.0]),
+            gallery_face("b", vec![0.0, 1.0, 0.0]),
+        ];
+
+        let duplicate = find_duplicate(&[0.0, 0.99, 0.01], &existing, 0.9).unwrap();
+        assert_eq!(duplicate.face_id, "b");
+        assert!(find_duplicate(&[0.0, 0.0, 1.0], &existing, 0.9).is_none());
+    }
+
+    #[test]
+    fn test_find_duplicate_sees_faces_enrolled_from_the_same_upload() {
+        let mut existing = vec![gallery_face("a", vec![1.0, 0.0, 0.0])];
+        let crop = vec![0.0, 1.0, 0.0];
+        assert!(find_duplicate(&crop, &existing, 0.9).is_none());
+
+        existing.push(gallery_face("b", crop.clone()));
+        let duplicate = find_duplicate(&crop, &existing, 0.9).unwrap();
+        assert_eq!(duplicate.face_id, "b");
+    }
+
+    #[test]
+    fn test_build_clusters_picks_medoid() {
+        let faces = vec![
+            gallery_face("a1", vec![1.0, 0.0, 0.0]),
+            gallery_face("a2", vec![0.9, 0.3, 0.0]),
+            gallery_face("a3", vec![0.95, 0.15, 0.0]),
+            gallery_face("b1", vec![0.0, 0.0, 1.0]),
+        ];
+
+        let response = build_clusters(&faces, 0.8);
+        assert_eq!(response.count, 2);
+        assert_eq!(response.sizes, vec![3, 1]);
+        assert_eq!(response.clusters[0].representative, "a3");
+    }
+
+    #[test]
+    fn test_compare_embeddings_threshold() {
+        let same = compare_embeddings(&[1.0, 0.0], &[0.9, 0.1], 0.5);
+        assert!(same.is_match);
+        assert!(same.euclidean_distance > 0.0);
+
+        let different = compare_embeddings(&[1.0, 0.0], &[0.0, 1.0], 0.5);
+        assert!(!different.is_match);
+        assert_eq!(different.cosine_similarity, 0.0);
+    }
+
+    #[test]
+    fn test_match_query_faces_groups_per_face() {
+        let gallery = vec![
+            gallery_face("alice", vec![1.0, 0.0, 0.0]),
+            gallery_face("bob", vec![0.0, 1.0, 0.0]),
+        ];
+        let query_faces = vec![
+            (BoundingBox::new(10, 20, 50, 50), vec![0.95, 0.05, 0.0]),
+            (BoundingBox::new(200, 30, 60, 60), vec![0.0, 0.98, 0.1]),
+        ];
+
+        let groups = match_query_faces(&query_faces, &gallery, 0.8, 5);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].bbox, BoundingBox::new(10, 20, 50, 50));
+        assert_eq!(groups[0].matches[0].face_id, "alice");
+        assert_eq!(groups[1].bbox, BoundingBox::new(200, 30, 60, 60));
+        assert_eq!(groups[1].matches[0].face_id, "bob");
+        assert_eq!(groups[1].matches.len(), 1);
+    }
+
+    #[test]
+    fn test_count_response_lists_boxes() {
+        let boxes = vec![BoundingBox::new(10, 20, 50, 50), BoundingBox::new(200, 30, 60, 60)];
+        let json = serde_json::to_value(CountResponse::new(boxes)).unwrap();
+        assert_eq!(json["count"], 2);
+        assert_eq!(json["boxes"].as_array().unwrap().len(), 2);
+
+        let empty = serde_json::to_value(CountResponse::new(Vec::new())).unwrap();
+        assert_eq!(empty, serde_json::json!({ "count": 0, "boxes": [] }));
+    }
+
+    #[test]
+    fn test_stored_image_content_type_is_sniffed() {
+        assert_eq!(stored_image_content_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(stored_image_content_type(b"\xff\xd8\xff\xe0...."), "image/jpeg");
+        assert_eq!(stored_image_content_type(b"BM......"), "image/bmp");
+
+        let missing = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(is_missing_file(&missing));
+        assert!(!is_missing_file(&anyhow::anyhow!("decryption failed")));
+    }
+
+    #[test]
+    fn test_upload_content_type_allowlist() {
+        assert!(is_allowed_image_type(Some(&mime::IMAGE_JPEG)));
+        assert!(is_allowed_image_type(Some(&mime::IMAGE_PNG)));
+        assert!(is_allowed_image_type(Some(&"image/bmp".parse().unwrap())));
+        assert!(is_allowed_image_type(Some(&"image/heic".parse().unwrap())));
+        assert!(!is_allowed_image_type(Some(&mime::TEXT_PLAIN)));
+        assert!(!is_allowed_image_type(None));
+
+        let message = unsupported_image_type_error().message().to_string();
+        assert!(ALLOWED_IMAGE_TYPES.iter().all(|mime| message.contains(mime)));
+    }
+
+    fn multipart_payload(part: &str) -> Multipart {
+        multipart_payload_bytes(part.as_bytes())
+    }
+
+    fn multipart_payload_bytes(part: &[u8]) -> Multipart {
+        use actix_web::{
+            error::PayloadError,
+            http::header::{self, HeaderMap, HeaderValue},
+            web::Bytes,
+        };
+
+        let boundary = "face-analyzer-test";
+        let mut body = format!("--{}\r\n", boundary).into_bytes();
+        body.extend_from_slice(part);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(&format!("multipart/form-data; boundary={}", boundary)).unwrap(),
+        );
+        let stream = futures::stream::once(async move { Ok::<_, PayloadError>(Bytes::from(body)) });
+        Multipart::new(&headers, stream)
+    }
+
+    #[actix_web::test]
+    async fn test_upload_without_filename_uses_generated_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut payload = multipart_payload(
+            "Content-Disposition: form-data; name=\"image\"\r\nContent-Type: image/png\r\n\r\nnot-a-png",
+        );
+
+        let upload = receive_upload(&mut payload, dir.path(), 1024).await.unwrap();
+        assert_eq!(
+            upload.path.file_name().unwrap().to_str().unwrap(),
+            upload.file_id.to_string()
+        );
+        assert!(upload.path.exists());
+    }
+
+    fn png_part() -> Vec<u8> {
+        let image = Mat::new_rows_cols_with_default(64, 64, opencv::core::CV_8UC3, opencv::core::Scalar::all(128.0))
+            .unwrap();
+        let mut png = opencv::core::Vector::<u8>::new();
+        imgcodecs::imencode(".png", &image, &mut png, &opencv::core::Vector::new()).unwrap();
+
+        let mut part =
+            b"Content-Disposition: form-data; name=\"image\"; filename=\"face.png\"\r\nContent-Type: image/png\r\n\r\n"
+                .to_vec();
+        part.extend_from_slice(png.as_slice());
+        part
+    }
+
+    #[actix_web::test]
+    async fn test_frontal_upload_without_pose_model_is_not_implemented() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut payload = multipart_payload_bytes(&png_part());
+        let error = receive_gated_upload(&mut payload, dir.path(), 1 << 20, true, None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.code(), "pose_unavailable");
+        assert_eq!(error.status_code(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let mut payload = multipart_payload_bytes(&png_part());
+        let (upload, image, estimator) = receive_gated_upload(&mut payload, dir.path(), 1 << 20, false, None)
+            .await
+            .unwrap();
+        assert!(estimator.is_none());
+        assert!(upload.path.exists());
+        assert_eq!((image.cols(), image.rows()), (64, 64));
+    }
+
+    #[actix_web::test]
+    async fn test_undecodable_upload_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut payload = multipart_payload(
+            "Content-Disposition: form-data; name=\"image\"; filename=\"face.png\"\r\nContent-Type: image/png\r\n\r\nnot-a-png",
+        );
+
+        let error = receive_gated_upload(&mut payload, dir.path(), 1024, false, None).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_malformed_multipart_is_bad_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut payload = multipart_payload("Content-Type: image/png\r\n\r\nno-disposition");
+
+        let error = receive_upload(&mut payload, dir.path(), 1024).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_image_field_is_payload_too_large() {
+        let mut payload = multipart_payload(
+            "Content-Disposition: form-data; name=\"image\"\r\nContent-Type: image/png\r\n\r\n0123456789abcdef",
+        );
+        let mut field = payload.try_next().await.unwrap().unwrap();
+
+        let error = read_image_field(&mut field, 8, &ApiMetrics::new()).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_publish_result_reaches_sink() {
+        struct KeySink(Arc<Mutex<Vec<String>>>);
+
+        impl ResultSink for KeySink {
+            fn publish(&mut self, key: &str, _result: &AnalysisResult) -> anyhow::Result<()> {
+                self.0.lock().unwrap().push(key.to_string());
+                Ok(())
+            }
+        }
+
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let sink: SharedResultSink = Arc::new(Mutex::new(Box::new(KeySink(keys.clone()))));
+        let result = AnalysisResult { faces: Vec::new() };
+
+        publish_result(Some(&sink), "upload-1", &result);
+        publish_result(None, "upload-2", &result);
+
+        assert_eq!(*keys.lock().unwrap(), vec!["upload-1".to_string()]);
+    }
+
+    #[test]
+    fn test_anonymize_query_defaults_to_blur() {
+        let query: AnonymizeQuery = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(matches!(query.method(), Ok(AnonymizationMethod::Blur { kernel_size: 51 })));
+
+        let query: AnonymizeQuery =
+            serde_json::from_value(serde_json::json!({ "mode": "oval_blur", "strength": 20 })).unwrap();
+        assert!(matches!(query.method(), Ok(AnonymizationMethod::OvalBlur { kernel_size: 21, .. })));
+    }
+
+    #[test]
+    fn test_anonymize_query_rejects_out_of_range_strength() {
+        for strength in [0, 2, 202, i32::MAX] {
+            let query: AnonymizeQuery =
+                serde_json::from_value(serde_json::json!({ "mode": "blur", "strength": strength })).unwrap();
+            assert_eq!(query.method().err().unwrap().status_code(), StatusCode::BAD_REQUEST);
+        }
+
+        let query: AnonymizeQuery =
+            serde_json::from_value(serde_json::json!({ "mode": "pixelate", "strength": 201 })).unwrap();
+        assert!(matches!(query.method(), Ok(AnonymizationMethod::Pixelate { block_size: 201 })));
+    }
+
+    #[test]
+    fn test_pose_gate_accepts_frontal_face() {
+        let gate = PoseGateConfig::default();
+        let frontal = HeadPose { yaw: 5.0, ..profile_pose() };
+        assert!(gate.check(&frontal).is_ok());
+    }
+}
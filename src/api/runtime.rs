@@ -0,0 +1,147 @@
+use opencv::{core, prelude::*};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+
+use crate::common::types::{clamp_rect_to_image, BoundingBox};
+use crate::processing::detectors::{DetectionResult, DetectorType, FaceDetector};
+use crate::processing::preprocessing::{ImagePreprocessor, PreprocessingConfig};
+
+/// Detection settings exposed through `/api/v1/config`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    pub detector_type: DetectorType,
+    pub confidence_threshold: f32,
+    pub min_face_size: i32,                   // Pixels, applied to both sides
+    pub scale_factor: f32,                    // Haar image pyramid step, > 1.0
+    pub preprocess: bool,                     // Run `preprocessing` on uploads before detection
+    pub preprocessing: PreprocessingConfig,
+}
+
+impl RuntimeConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.confidence_threshold) {
+            anyhow::bail!("confidence_threshold must be between 0 and 1");
+        }
+        if self.min_face_size < 1 {
+            anyhow::bail!("min_face_size must be at least 1");
+        }
+        if self.scale_factor <= 1.0 {
+            anyhow::bail!("scale_factor must be greater than 1");
+        }
+        let preprocessing = &self.preprocessing;
+        if !(-1.0..=1.0).contains(&preprocessing.brightness) {
+            anyhow::bail!("preprocessing.brightness must be between -1 and 1");
+        }
+        if !(0.0..=3.0).contains(&preprocessing.contrast) {
+            anyhow::bail!("preprocessing.contrast must be between 0 and 3");
+        }
+        if preprocessing.blur_size > 1 && preprocessing.blur_size % 2 == 0 {
+            anyhow::bail!("preprocessing.blur_size must be odd");
+        }
+        Ok(())
+    }
+}
+
+/// The detector and optional preprocessing used by the detection endpoints.
+/// The server keeps it behind a lock so admins can reconfigure it without a
+/// restart.
+pub struct DetectionRuntime {
+    detector: FaceDetector,
+    preprocess: bool,
+    preprocessing: PreprocessingConfig,
+}
+
+impl DetectionRuntime {
+    pub fn new(detector: FaceDetector) -> Self {
+        Self {
+            detector,
+            preprocess: false,
+            preprocessing: PreprocessingConfig::default(),
+        }
+    }
+
+    pub fn detector(&self) -> &FaceDetector {
+        &self.detector
+    }
+
+    /// Detect faces, preprocessing the image first when enabled. Boxes are
+    /// in the coordinates of `image` either way, clamped to its bounds so
+    /// they can be cropped directly.
+    pub fn detect(&self, image: &Mat) -> Result<Vec<DetectionResult>> {
+        let detections = if self.preprocess {
+            let processed = ImagePreprocessor::new(self.preprocessing.clone()).process(image)?;
+            self.detector.detect(&processed)?
+        } else {
+            self.detector.detect(image)?
+        };
+
+        Ok(detections
+            .into_iter()
+            .filter_map(|mut detection| {
+                let rect = clamp_rect_to_image(detection.bbox.rect(), image.cols(), image.rows());
+                detection.bbox = BoundingBox::from(rect);
+                (rect.area() > 0).then_some(detection)
+            })
+            .collect())
+    }
+
+    pub fn config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            detector_type: self.detector.detector_type(),
+            confidence_threshold: self.detector.confidence_threshold(),
+            min_face_size: self.detector.min_face_size().width,
+            scale_factor: self.detector.scale_factor(),
+            preprocess: self.preprocess,
+            preprocessing: self.preprocessing.clone(),
+        }
+    }
+
+    /// Replace the settings, keeping the detector's model paths.
+    pub fn apply(&mut self, config: RuntimeConfig) -> Result<()> {
+        config.validate()?;
+        self.detector = FaceDetector::new(
+            config.detector_type,
+            config.confidence_threshold,
+            core::Size::new(config.min_face_size, config.min_face_size),
+            config.scale_factor,
+        )
+        .with_min_neighbors(self.detector.min_neighbors())
+        .with_model_paths(self.detector.model_paths().clone());
+        self.preprocess = config.preprocess;
+        self.preprocessing = config.preprocessing;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> DetectionRuntime {
+        DetectionRuntime::new(FaceDetector::new(DetectorType::Haar, 0.5, core::Size::new(30, 30), 1.1))
+    }
+
+    #[test]
+    fn test_apply_round_trips_and_rejects_invalid_settings() {
+        let mut runtime = runtime();
+        let mut config = runtime.config();
+        config.detector_type = DetectorType::DNN;
+        config.confidence_threshold = 0.7;
+        config.min_face_size = 48;
+        config.preprocess = true;
+        config.preprocessing.sharpen = true;
+        runtime.apply(config).unwrap();
+
+        let applied = runtime.config();
+        assert_eq!(applied.detector_type, DetectorType::DNN);
+        assert_eq!(applied.confidence_threshold, 0.7);
+        assert_eq!(applied.min_face_size, 48);
+        assert!(applied.preprocess);
+        assert!(applied.preprocessing.sharpen);
+
+        let mut invalid = runtime.config();
+        invalid.scale_factor = 1.0;
+        assert!(runtime.apply(invalid).is_err());
+        assert_eq!(runtime.config().scale_factor, applied.scale_factor);
+    }
+}
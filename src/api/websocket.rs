@@ -2,25 +2,107 @@ use actix::{Actor, StreamHandler, Handler, Message, ActorContext};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use uuid::Uuid;
 
 use crate::database::embeddings::FaceEmbedding;
 
-#[derive(Message, Serialize, Deserialize)]
+#[derive(Message, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub enum WsMessage {
     FaceDetected(FaceEmbedding),
     FaceUpdated(FaceEmbedding),
     FaceDeleted(String),
+    Progress { job_id: String, done: u64, total: u64 },
     Error(String),
 }
 
+/// Wire format negotiated per connection with `?format=json|msgpack`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WsFormat {
+    #[default]
+    Json,     // Text frames
+    Msgpack,  // Binary frames, roughly half the size for embeddings
+}
+
+#[derive(Deserialize)]
+pub struct WsParams {
+    format: Option<WsFormat>,
+}
+
+enum WsFrame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+fn encode_message(msg: &WsMessage, format: WsFormat) -> anyhow::Result<WsFrame> {
+    Ok(match format {
+        WsFormat::Json => WsFrame::Text(serde_json::to_string(msg)?),
+        WsFormat::Msgpack => WsFrame::Binary(rmp_serde::to_vec_named(msg)?),
+    })
+}
+
+impl WsMessage {
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            WsMessage::FaceDetected(_) => "face_detected",
+            WsMessage::FaceUpdated(_) => "face_updated",
+            WsMessage::FaceDeleted(_) => "face_deleted",
+            WsMessage::Progress { .. } => "progress",
+            WsMessage::Error(_) => "error",
+        }
+    }
+
+    fn tags(&self) -> Option<&[String]> {
+        match self {
+            WsMessage::FaceDetected(face) | WsMessage::FaceUpdated(face) => Some(&face.metadata.tags),
+            _ => None,
+        }
+    }
+}
+
+/// Per-connection subscription. `None` means no restriction. The tag filter
+/// only applies to events that carry a face; other events pass it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WsFilter {
+    pub events: Option<HashSet<String>>,
+    pub tags: Option<HashSet<String>>,
+}
+
+impl WsFilter {
+    pub fn matches(&self, msg: &WsMessage) -> bool {
+        let event_ok = self
+            .events
+            .as_ref()
+            .map_or(true, |events| events.contains(msg.event_name()));
+        let tags_ok = match (&self.tags, msg.tags()) {
+            (Some(wanted), Some(tags)) => tags.iter().any(|tag| wanted.contains(tag)),
+            _ => true,
+        };
+        event_ok && tags_ok
+    }
+}
+
+/// Text control messages accepted from clients, e.g.
+/// `{"action": "subscribe", "events": ["face_detected"], "tags": ["camera-1"]}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum ControlMessage {
+    Subscribe {
+        events: Option<HashSet<String>>,
+        tags: Option<HashSet<String>>,
+    },
+    Unsubscribe,
+}
+
 pub struct WsConnection {
     id: String,
     tx: broadcast::Sender<WsMessage>,
+    format: WsFormat,
+    filter: WsFilter,
 }
 
 impl Actor for WsConnection {
@@ -42,9 +124,18 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConnection {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-            Ok(ws::Message::Text(text)) => {
-                println!("Received message: {}", text);
-            }
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<ControlMessage>(&text) {
+                Ok(ControlMessage::Subscribe { events, tags }) => {
+                    self.filter = WsFilter { events, tags };
+                }
+                Ok(ControlMessage::Unsubscribe) => self.filter = WsFilter::default(),
+                Err(e) => {
+                    let error = WsMessage::Error(format!("Invalid control message: {}", e));
+                    if let Ok(WsFrame::Text(data)) = encode_message(&error, WsFormat::Json) {
+                        ctx.text(data);
+                    }
+                }
+            },
             Ok(ws::Message::Close(reason)) => {
                 ctx.close(reason);
                 ctx.stop();
@@ -58,12 +149,20 @@ impl Handler<WsMessage> for WsConnection {
     type Result = ();
 
     fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
-        if let Ok(data) = serde_json::to_string(&msg) {
-            ctx.text(data);
+        if !self.filter.matches(&msg) {
+            return;
+        }
+
+        match encode_message(&msg, self.format) {
+            Ok(WsFrame::Text(data)) => ctx.text(data),
+            Ok(WsFrame::Binary(data)) => ctx.binary(data),
+            Err(e) => log::error!("Failed to encode websocket message for {}: {}", self.id, e),
         }
     }
 }
 
+pub type SharedWsManager = Arc<tokio::sync::Mutex<WsManager>>;
+
 pub struct WsManager {
     connections: HashMap<String, broadcast::Sender<WsMessage>>,
 }
@@ -96,12 +195,18 @@ impl WsManager {
 pub async fn ws_handler(
     req: HttpRequest,
     stream: web::Payload,
+    params: web::Query<WsParams>,
     manager: web::Data<Arc<tokio::sync::Mutex<WsManager>>>,
 ) -> Result<HttpResponse, Error> {
     let mut ws_manager = manager.lock().await;
     let (id, tx) = ws_manager.create_connection();
 
-    let ws = WsConnection { id, tx };
+    let ws = WsConnection {
+        id,
+        tx,
+        format: params.format.unwrap_or_default(),
+        filter: WsFilter::default(),
+    };
     let resp = ws::start(ws, &req, stream)?;
     Ok(resp)
 }
@@ -130,10 +235,75 @@ pub async fn notify_face_deleted(
     ws_manager.broadcast(WsMessage::FaceDeleted(face_id));
 }
 
+pub async fn notify_progress(
+    manager: &Arc<tokio::sync::Mutex<WsManager>>,
+    job_id: String,
+    done: u64,
+    total: u64,
+) {
+    let ws_manager = manager.lock().await;
+    ws_manager.broadcast(WsMessage::Progress { job_id, done, total });
+}
+
 pub async fn notify_error(
     manager: &Arc<tokio::sync::Mutex<WsManager>>,
     error: String,
 ) {
     let ws_manager = manager.lock().await;
     ws_manager.broadcast(WsMessage::Error(error));
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(tag: &str) -> FaceEmbedding {
+        FaceEmbedding {
+            face_id: "face-1".to_string(),
+            embedding: (0..512).map(|i| (i as f32 * 0.731).sin()).collect(),
+            metadata: crate::database::embeddings::FaceMetadata {
+                name: Some("Alice".to_string()),
+                tags: vec![tag.to_string()],
+                timestamp: chrono::Utc::now(),
+                source_image: "alice.jpg".to_string(),
+                confidence: 0.98,
+                details: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_subscription_filter() {
+        let control = r#"{"action": "subscribe", "events": ["face_detected"], "tags": ["camera-1"]}"#;
+        let filter = match serde_json::from_str::<ControlMessage>(control).unwrap() {
+            ControlMessage::Subscribe { events, tags } => WsFilter { events, tags },
+            ControlMessage::Unsubscribe => panic!("Expected a subscribe message"),
+        };
+
+        assert!(filter.matches(&WsMessage::FaceDetected(face("camera-1"))));
+        assert!(!filter.matches(&WsMessage::FaceDetected(face("camera-2"))));
+        assert!(!filter.matches(&WsMessage::FaceUpdated(face("camera-1"))));
+        assert!(!filter.matches(&WsMessage::FaceDeleted("face-1".to_string())));
+        assert!(WsFilter::default().matches(&WsMessage::FaceDeleted("face-1".to_string())));
+    }
+
+    #[test]
+    fn test_msgpack_frames_are_binary_and_smaller() {
+        let msg = WsMessage::FaceDetected(face("camera-1"));
+
+        let json = match encode_message(&msg, WsFormat::Json).unwrap() {
+            WsFrame::Text(text) => text,
+            WsFrame::Binary(_) => panic!("JSON should be sent as text"),
+        };
+        let packed = match encode_message(&msg, WsFormat::Msgpack).unwrap() {
+            WsFrame::Binary(bytes) => bytes,
+            WsFrame::Text(_) => panic!("MessagePack should be sent as binary"),
+        };
+
+        assert!(packed.len() < json.len());
+        match rmp_serde::from_slice::<WsMessage>(&packed).unwrap() {
+            WsMessage::FaceDetected(decoded) => assert_eq!(decoded.embedding.len(), 512),
+            _ => panic!("Unexpected message variant"),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use opencv::prelude::*;
+use ort::{Session, Value};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::performance::gpu::{build_session_with, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AccessoryFlag {
+    pub present: bool,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Accessories {
+    pub eyeglasses: AccessoryFlag,
+    pub sunglasses: AccessoryFlag,
+    pub headwear: AccessoryFlag,
+}
+
+impl Accessories {
+    /// Names of the accessories detected, e.g. for tagging or search.
+    pub fn present(&self) -> Vec<&'static str> {
+        [
+            ("eyeglasses", self.eyeglasses),
+            ("sunglasses", self.sunglasses),
+            ("headwear", self.headwear),
+        ]
+        .into_iter()
+        .filter(|(_, flag)| flag.present)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+/// Multi-label accessory classifier run on the face ROI. The model outputs
+/// one logit per label in the order eyeglasses, sunglasses, headwear.
+pub struct AccessoryDetector {
+    session: Session,
+    input_size: InputSize,
+    threshold: f32,
+}
+
+impl AccessoryDetector {
+    pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.accessories` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.accessories))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
+        let environment = ort::Environment::builder()
+            .with_name("accessory_detection")
+            .build()?
+            .into_arc();
+
+        let session = build_session_with(&environment, model_path, gpu)?;
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().accessories, &session);
+
+        Ok(Self {
+            session,
+            input_size,
+            threshold: 0.5,
+        })
+    }
+
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
+    }
+
+    /// Minimum per-label probability for `present`, 0.5 by default.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn detect(&self, face_mat: &Mat) -> Result<Accessories> {
+        let processed_tensor = self.preprocess_image(face_mat)?;
+
+        let outputs = self.session.run(vec![processed_tensor])?;
+
+        self.postprocess_output(&outputs)
+    }
+
+    fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
+    }
+
+    fn postprocess_output(&self, outputs: &[Value]) -> Result<Accessories> {
+        if let Some(Value::Tensor(tensor)) = outputs.first() {
+            let logits: Vec<f32> = tensor.data::<f32>()?.iter().copied().collect();
+            decode_accessories(&logits, self.threshold)
+        } else {
+            Err(anyhow::anyhow!("Invalid output type"))
+        }
+    }
+}
+
+fn decode_accessories(logits: &[f32], threshold: f32) -> Result<Accessories> {
+    if logits.len() != 3 {
+        return Err(anyhow::anyhow!("Expected 3 accessory logits, got {}", logits.len()));
+    }
+    let flag = |logit: f32| {
+        let confidence = 1.0 / (1.0 + (-logit).exp());
+        AccessoryFlag {
+            present: confidence >= threshold,
+            confidence,
+        }
+    };
+    Ok(Accessories {
+        eyeglasses: flag(logits[0]),
+        sunglasses: flag(logits[1]),
+        headwear: flag(logits[2]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_accessories_is_multi_label() {
+        let accessories = decode_accessories(&[-4.0, 3.0, 2.0], 0.5).unwrap();
+        assert!(!accessories.eyeglasses.present);
+        assert!(accessories.sunglasses.confidence > 0.9);
+        assert_eq!(accessories.present(), vec!["sunglasses", "headwear"]);
+
+        assert!(decode_accessories(&[0.0, 0.0], 0.5).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use opencv::prelude::*;
 use ort::{Session, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use crate::performance::gpu::{build_session_with, GpuConfig};
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Emotion {
     Happy,
     Sad,
@@ -14,7 +15,7 @@ pub enum Emotion {
     Neutral,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmotionPrediction {
     pub emotion: Emotion,
     pub confidence: f32,
@@ -26,12 +27,16 @@ pub struct EmotionDetector {
 
 impl EmotionDetector {
     pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
         let environment = ort::Environment::builder()
             .with_name("emotion_detection")
-            .build()?;
+            .build()?
+            .into_arc();
         
-        let session = ort::SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)?;
+        let session = build_session_with(&environment, model_path, gpu)?;
 
         Ok(Self { session })
     }
@@ -1,9 +1,10 @@
 use opencv::prelude::*;
 use ort::{Session, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use crate::performance::gpu::{build_session_with, GpuConfig};
 
-#[derive(Debug, Serialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum EthnicGroup {
     EastAsian,
     SouthAsian,
@@ -14,7 +15,7 @@ pub enum EthnicGroup {
     Other,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EthnicityPrediction {
     pub primary_ethnicity: EthnicGroup,
     pub confidence: f32,
@@ -27,12 +28,16 @@ pub struct EthnicityEstimator {
 
 impl EthnicityEstimator {
     pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
         let environment = ort::Environment::builder()
             .with_name("ethnicity_estimation")
-            .build()?;
+            .build()?
+            .into_arc();
         
-        let session = ort::SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)?;
+        let session = build_session_with(&environment, model_path, gpu)?;
 
         Ok(Self { session })
     }
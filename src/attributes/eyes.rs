@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use crate::attributes::landmarks::{FacialLandmark, FacialLandmarks};
+
+/// Eye aspect ratio below which an eye counts as closed. Open eyes sit
+/// around 0.25-0.35 and drop towards 0.1 mid-blink.
+pub const EAR_CLOSED_THRESHOLD: f32 = 0.2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EyeState {
+    pub left_eye_open: bool,
+    pub right_eye_open: bool,
+    pub ear: f32,  // Mean eye aspect ratio of both eyes
+}
+
+impl EyeState {
+    /// Derive the eye state from landmark eye contours. `None` when either
+    /// eye is missing points.
+    pub fn from_landmarks(landmarks: &FacialLandmarks) -> Option<Self> {
+        let left = eye_aspect_ratio(&landmarks.left_eye)?;
+        let right = eye_aspect_ratio(&landmarks.right_eye)?;
+        Some(Self {
+            left_eye_open: left >= EAR_CLOSED_THRESHOLD,
+            right_eye_open: right >= EAR_CLOSED_THRESHOLD,
+            ear: (left + right) / 2.0,
+        })
+    }
+
+    pub fn both_closed(&self) -> bool {
+        !self.left_eye_open && !self.right_eye_open
+    }
+}
+
+/// Eye aspect ratio (Soukupová and Čech, 2016) of an eye contour listed
+/// clockwise from the outer corner, as in the 68-point layout `p1..p6`:
+/// `(|p2 - p6| + |p3 - p5|) / (2 |p1 - p4|)`. Contours with more points use
+/// every opposing pair. Needs an even number of at least four points.
+pub fn eye_aspect_ratio(eye: &[FacialLandmark]) -> Option<f32> {
+    let n = eye.len();
+    if n < 4 || n % 2 != 0 {
+        return None;
+    }
+
+    let horizontal = distance(&eye[0], &eye[n / 2]);
+    if horizontal <= f32::EPSILON {
+        return None;
+    }
+    let pairs = n / 2 - 1;
+    let vertical: f32 = (1..=pairs).map(|i| distance(&eye[i], &eye[n - i])).sum();
+    Some(vertical / (pairs as f32 * horizontal))
+}
+
+fn distance(a: &FacialLandmark, b: &FacialLandmark) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Counts blinks across consecutive frames of one tracked face. A blink is
+/// the EAR staying below the threshold for at least `min_closed_frames` and
+/// then reopening, which ignores single-frame landmark jitter.
+#[derive(Debug, Clone)]
+pub struct BlinkCounter {
+    threshold: f32,
+    min_closed_frames: u32,
+    closed_frames: u32,
+    blinks: u32,
+}
+
+impl Default for BlinkCounter {
+    fn default() -> Self {
+        Self {
+            threshold: EAR_CLOSED_THRESHOLD,
+            min_closed_frames: 2,
+            closed_frames: 0,
+            blinks: 0,
+        }
+    }
+}
+
+impl BlinkCounter {
+    pub fn new(threshold: f32, min_closed_frames: u32) -> Self {
+        Self {
+            threshold,
+            min_closed_frames,
+            ..Default::default()
+        }
+    }
+
+    /// Feed the next frame's EAR. Returns true when it completes a blink.
+    pub fn update(&mut self, ear: f32) -> bool {
+        if ear < self.threshold {
+            self.closed_frames += 1;
+            return false;
+        }
+        let blinked = self.closed_frames >= self.min_closed_frames;
+        self.closed_frames = 0;
+        if blinked {
+            self.blinks += 1;
+        }
+        blinked
+    }
+
+    pub fn blinks(&self) -> u32 {
+        self.blinks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eye(cx: f32, height: f32) -> Vec<FacialLandmark> {
+        // Outer corner, two upper lid points, inner corner, two lower lid points
+        [(-15.0, 0.0), (-5.0, -height), (5.0, -height), (15.0, 0.0), (5.0, height), (-5.0, height)]
+            .iter()
+            .map(|&(x, y)| FacialLandmark { x: cx + x, y: 50.0 + y, confidence: 1.0 })
+            .collect()
+    }
+
+    #[test]
+    fn test_eye_aspect_ratio_separates_open_and_closed() {
+        let open = eye_aspect_ratio(&eye(0.0, 5.0)).unwrap();
+        let closed = eye_aspect_ratio(&eye(0.0, 1.0)).unwrap();
+        assert!((open - 10.0 / 30.0).abs() < 1e-5);
+        assert!(open > EAR_CLOSED_THRESHOLD);
+        assert!(closed < EAR_CLOSED_THRESHOLD);
+        assert_eq!(eye_aspect_ratio(&eye(0.0, 5.0)[..3]), None);
+    }
+
+    #[test]
+    fn test_blink_needs_consecutive_closed_frames() {
+        let mut counter = BlinkCounter::default();
+        let frames = [0.3, 0.1, 0.3, 0.1, 0.08, 0.3, 0.3];
+        let completed: Vec<bool> = frames.iter().map(|&ear| counter.update(ear)).collect();
+        assert_eq!(completed, vec![false, false, false, false, false, true, false]);
+        assert_eq!(counter.blinks(), 1);
+    }
+}
@@ -1,17 +1,23 @@
 use opencv::prelude::*;
 use ort::{Session, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::performance::gpu::{build_session_with, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
 use ndarray::Array2;
 
-#[derive(Debug, Serialize, Clone)]
+/// Points in the 68-point iBUG 300-W layout the landmark model outputs.
+const LANDMARK_COUNT: usize = 68;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FacialLandmark {
     pub x: f32,
     pub y: f32,
     pub confidence: f32,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FacialLandmarks {
     pub jaw_line: Vec<FacialLandmark>,
     
@@ -27,20 +33,79 @@ pub struct FacialLandmarks {
     pub inner_lips: Vec<FacialLandmark>,
 }
 
+impl FacialLandmarks {
+    pub fn points(&self) -> Vec<&FacialLandmark> {
+        self.jaw_line.iter()
+            .chain(self.left_eye.iter())
+            .chain(self.right_eye.iter())
+            .chain(self.left_eyebrow.iter())
+            .chain(self.right_eyebrow.iter())
+            .chain(self.nose_bridge.iter())
+            .chain(std::iter::once(&self.nose_tip))
+            .chain(self.outer_lips.iter())
+            .chain(self.inner_lips.iter())
+            .collect()
+    }
+
+    /// The same landmarks shifted by `(dx, dy)`, e.g. from face crop to
+    /// source image coordinates.
+    pub fn translated(&self, dx: f32, dy: f32) -> Self {
+        let shift = |points: &[FacialLandmark]| -> Vec<FacialLandmark> {
+            points
+                .iter()
+                .map(|p| FacialLandmark { x: p.x + dx, y: p.y + dy, confidence: p.confidence })
+                .collect()
+        };
+        Self {
+            jaw_line: shift(&self.jaw_line),
+            left_eye: shift(&self.left_eye),
+            right_eye: shift(&self.right_eye),
+            left_eyebrow: shift(&self.left_eyebrow),
+            right_eyebrow: shift(&self.right_eyebrow),
+            nose_bridge: shift(&self.nose_bridge),
+            nose_tip: shift(std::slice::from_ref(&self.nose_tip)).remove(0),
+            outer_lips: shift(&self.outer_lips),
+            inner_lips: shift(&self.inner_lips),
+        }
+    }
+}
+
+/// 68-point landmark regressor run on the face ROI. Expects one output of
+/// `x, y` pairs in the iBUG 300-W order, normalized to `0.0..1.0` of the
+/// input as PFLD-style models produce. Points are returned in ROI pixels.
 pub struct LandmarkDetector {
     session: Session,
+    input_size: InputSize,
 }
 
 impl LandmarkDetector {
     pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.landmarks` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.landmarks))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
         let environment = ort::Environment::builder()
             .with_name("landmark_detection")
-            .build()?;
+            .build()?
+            .into_arc();
         
-        let session = ort::SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)?;
+        let session = build_session_with(&environment, model_path, gpu)?;
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().landmarks, &session);
+
+        Ok(Self { session, input_size })
+    }
 
-        Ok(Self { session })
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
     }
 
     pub fn detect(&self, face_mat: &Mat) -> Result<FacialLandmarks> {
@@ -48,18 +113,77 @@ impl LandmarkDetector {
         
         let outputs = self.session.run(vec![processed_tensor])?;
         
-        self.postprocess_output(&outputs)
+        self.postprocess_output(&outputs, face_mat.cols() as f32, face_mat.rows() as f32)
     }
 
     fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
-        unimplemented!("Image preprocessing for landmark detection")
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
     }
 
-    fn postprocess_output(&self, outputs: &[Value]) -> Result<FacialLandmarks> {
-        unimplemented!("Output processing for landmark detection")
+    fn postprocess_output(&self, outputs: &[Value], width: f32, height: f32) -> Result<FacialLandmarks> {
+        if let Some(Value::Tensor(tensor)) = outputs.first() {
+            let coords: Vec<f32> = tensor.data::<f32>()?.iter().copied().collect();
+            decode_landmarks(&coords, width, height)
+        } else {
+            Err(anyhow::anyhow!("Invalid output type"))
+        }
     }
 
     pub fn draw_landmarks(&self, image: &mut Mat, landmarks: &FacialLandmarks) -> Result<()> {
         unimplemented!("Landmark visualization")
     }
-} 
\ No newline at end of file
+}
+
+/// Group normalized 68-point coordinates into facial features, scaled to a
+/// `width` x `height` ROI. The subject's right eye and eyebrow (points
+/// 36-41 and 17-21) appear on the image left.
+fn decode_landmarks(coords: &[f32], width: f32, height: f32) -> Result<FacialLandmarks> {
+    if coords.len() != LANDMARK_COUNT * 2 {
+        return Err(anyhow::anyhow!(
+            "Expected {} landmark coordinates, got {}",
+            LANDMARK_COUNT * 2,
+            coords.len()
+        ));
+    }
+
+    let point = |i: usize| FacialLandmark {
+        x: coords[2 * i] * width,
+        y: coords[2 * i + 1] * height,
+        confidence: 1.0,
+    };
+    let range = |points: std::ops::Range<usize>| points.map(point).collect::<Vec<_>>();
+    Ok(FacialLandmarks {
+        jaw_line: range(0..17),
+        right_eyebrow: range(17..22),
+        left_eyebrow: range(22..27),
+        nose_bridge: range(27..31),
+        nose_tip: point(30),
+        right_eye: range(36..42),
+        left_eye: range(42..48),
+        outer_lips: range(48..60),
+        inner_lips: range(60..68),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_landmarks_groups_and_scales_points() {
+        let coords: Vec<f32> = (0..LANDMARK_COUNT)
+            .flat_map(|i| [i as f32 / 100.0, 0.5])
+            .collect();
+        let landmarks = decode_landmarks(&coords, 200.0, 100.0).unwrap();
+
+        // The lower nose points 31-35 have no group; point 30 is both in the bridge and the tip
+        assert_eq!(landmarks.points().len(), 64);
+        assert_eq!(landmarks.jaw_line.len(), 17);
+        assert_eq!((landmarks.right_eye.len(), landmarks.left_eye.len()), (6, 6));
+        assert!((landmarks.right_eye[0].x - 72.0).abs() < 1e-4);
+        assert!((landmarks.outer_lips[6].x - 108.0).abs() < 1e-4);
+        assert!((landmarks.nose_tip.y - 50.0).abs() < 1e-4);
+
+        assert!(decode_landmarks(&coords[..10], 200.0, 100.0).is_err());
+    }
+}
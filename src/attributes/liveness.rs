@@ -0,0 +1,90 @@
+use opencv::prelude::*;
+use ort::{Session, Value};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::face::binary_score;
+use crate::performance::gpu::{build_session_with, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LivenessResult {
+    pub is_live: bool,
+    pub score: f32,  // Probability the face is a live person rather than a photo or screen
+}
+
+/// Anti-spoofing classifier run on the face ROI. Expects a model with either
+/// a single live probability output or two `[spoof, live]` logits.
+pub struct LivenessDetector {
+    session: Session,
+    input_size: InputSize,
+    threshold: f32,
+}
+
+impl LivenessDetector {
+    pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.liveness` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.liveness))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
+        let environment = ort::Environment::builder()
+            .with_name("liveness_detection")
+            .build()?
+            .into_arc();
+
+        let session = build_session_with(&environment, model_path, gpu)?;
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().liveness, &session);
+
+        Ok(Self {
+            session,
+            input_size,
+            threshold: 0.5,
+        })
+    }
+
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
+    }
+
+    /// Minimum score for `is_live`. Access control deployments usually want
+    /// this higher than the default 0.5.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn detect(&self, face_mat: &Mat) -> Result<LivenessResult> {
+        let processed_tensor = self.preprocess_image(face_mat)?;
+
+        let outputs = self.session.run(vec![processed_tensor])?;
+
+        self.postprocess_output(&outputs)
+    }
+
+    fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
+    }
+
+    fn postprocess_output(&self, outputs: &[Value]) -> Result<LivenessResult> {
+        if let Some(Value::Tensor(tensor)) = outputs.first() {
+            let scores: Vec<f32> = tensor.data::<f32>()?.iter().copied().collect();
+            let score = binary_score(&scores)
+                .ok_or_else(|| anyhow::anyhow!("Empty liveness output"))?;
+            Ok(LivenessResult {
+                is_live: score >= self.threshold,
+                score,
+            })
+        } else {
+            Err(anyhow::anyhow!("Invalid output type"))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use opencv::prelude::*;
+use ort::{Session, Value};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::face::binary_score;
+use crate::performance::gpu::{build_session_with, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaskPrediction {
+    pub wearing_mask: bool,
+    pub confidence: f32,  // Probability the lower face is covered by a mask
+}
+
+/// Face-mask classifier run on the face ROI. Expects a model with either a
+/// single mask probability output or two `[no_mask, mask]` logits.
+pub struct MaskDetector {
+    session: Session,
+    input_size: InputSize,
+    threshold: f32,
+}
+
+impl MaskDetector {
+    pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.mask` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.mask))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
+        let environment = ort::Environment::builder()
+            .with_name("mask_detection")
+            .build()?
+            .into_arc();
+
+        let session = build_session_with(&environment, model_path, gpu)?;
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().mask, &session);
+
+        Ok(Self {
+            session,
+            input_size,
+            threshold: 0.5,
+        })
+    }
+
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
+    }
+
+    /// Minimum score for `wearing_mask`, 0.5 by default.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn detect(&self, face_mat: &Mat) -> Result<MaskPrediction> {
+        let processed_tensor = self.preprocess_image(face_mat)?;
+
+        let outputs = self.session.run(vec![processed_tensor])?;
+
+        self.postprocess_output(&outputs)
+    }
+
+    fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
+    }
+
+    fn postprocess_output(&self, outputs: &[Value]) -> Result<MaskPrediction> {
+        if let Some(Value::Tensor(tensor)) = outputs.first() {
+            let scores: Vec<f32> = tensor.data::<f32>()?.iter().copied().collect();
+            let confidence = binary_score(&scores)
+                .ok_or_else(|| anyhow::anyhow!("Empty mask output"))?;
+            Ok(MaskPrediction {
+                wearing_mask: confidence >= self.threshold,
+                confidence,
+            })
+        } else {
+            Err(anyhow::anyhow!("Invalid output type"))
+        }
+    }
+}
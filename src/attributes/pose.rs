@@ -1,9 +1,18 @@
 use opencv::prelude::*;
 use ort::{Session, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::face::softmax;
+use crate::performance::gpu::{build_session_with, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
 
-#[derive(Debug, Serialize, Clone)]
+/// Width of one angle bin, in degrees, for models with binned outputs.
+const POSE_BIN_DEGREES: f32 = 3.0;
+/// Angle at the lower edge of the first bin, in degrees.
+const POSE_BIN_START: f32 = -99.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct HeadPose {
     pub yaw: f32,
     pub pitch: f32,
@@ -13,27 +22,57 @@ pub struct HeadPose {
     pub roll_confidence: f32,
 }
 
-#[derive(Debug, Serialize)]
+impl HeadPose {
+    pub fn within_limits(&self, max_yaw: f32, max_pitch: f32, max_roll: f32) -> bool {
+        self.yaw.abs() <= max_yaw &&
+        self.pitch.abs() <= max_pitch &&
+        self.roll.abs() <= max_roll
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PoseEstimation {
     pub head_pose: HeadPose,
     pub face_direction: String,
     pub is_frontal: bool,
 }
 
+/// Head pose regressor run on the face ROI. Expects a model with either one
+/// `[yaw, pitch, roll]` output in degrees, or three outputs of angle-bin
+/// logits (yaw, pitch, roll) as produced by Hopenet-style models.
 pub struct PoseEstimator {
     session: Session,
+    input_size: InputSize,
 }
 
 impl PoseEstimator {
     pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.pose` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.pose))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
         let environment = ort::Environment::builder()
             .with_name("pose_estimation")
-            .build()?;
+            .build()?
+            .into_arc();
         
-        let session = ort::SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)?;
+        let session = build_session_with(&environment, model_path, gpu)?;
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().pose, &session);
 
-        Ok(Self { session })
+        Ok(Self { session, input_size })
+    }
+
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
     }
 
     pub fn estimate(&self, face_mat: &Mat) -> Result<PoseEstimation> {
@@ -45,11 +84,24 @@ impl PoseEstimator {
     }
 
     fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
-        unimplemented!("Image preprocessing for pose estimation")
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
     }
 
     fn postprocess_output(&self, outputs: &[Value]) -> Result<PoseEstimation> {
-        unimplemented!("Output processing for pose estimation")
+        let mut values = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            match output {
+                Value::Tensor(tensor) => values.push(tensor.data::<f32>()?.iter().copied().collect()),
+                _ => return Err(anyhow::anyhow!("Invalid output type")),
+            }
+        }
+
+        let head_pose = decode_head_pose(&values)?;
+        Ok(PoseEstimation {
+            face_direction: self.get_face_direction(&head_pose),
+            is_frontal: self.is_frontal(&head_pose),
+            head_pose,
+        })
     }
 
     pub fn draw_pose_axes(&self, image: &mut Mat, pose: &HeadPose) -> Result<()> {
@@ -91,8 +143,71 @@ impl PoseEstimator {
     }
 
     fn is_frontal(&self, pose: &HeadPose) -> bool {
-        pose.yaw.abs() <= 30.0 && 
-        pose.pitch.abs() <= 20.0 && 
-        pose.roll.abs() <= 20.0
+        pose.within_limits(30.0, 20.0, 20.0)
     }
-} 
\ No newline at end of file
+}
+
+/// Yaw, pitch and roll from the model outputs: taken as degrees from a
+/// single output of three values or three single-value outputs, otherwise
+/// as the softmax expectation over the angle bins of three outputs.
+fn decode_head_pose(outputs: &[Vec<f32>]) -> Result<HeadPose> {
+    match outputs {
+        [angles] if angles.len() == 3 => Ok(HeadPose {
+            yaw: angles[0],
+            pitch: angles[1],
+            roll: angles[2],
+            yaw_confidence: 1.0,
+            pitch_confidence: 1.0,
+            roll_confidence: 1.0,
+        }),
+        [yaw, pitch, roll] if yaw.len() == 1 && pitch.len() == 1 && roll.len() == 1 => {
+            decode_head_pose(&[vec![yaw[0], pitch[0], roll[0]]])
+        }
+        [yaw, pitch, roll, ..] => {
+            let (yaw, yaw_confidence) = decode_angle_bins(yaw)?;
+            let (pitch, pitch_confidence) = decode_angle_bins(pitch)?;
+            let (roll, roll_confidence) = decode_angle_bins(roll)?;
+            Ok(HeadPose { yaw, pitch, roll, yaw_confidence, pitch_confidence, roll_confidence })
+        }
+        _ => Err(anyhow::anyhow!("Unexpected pose output shape")),
+    }
+}
+
+/// Expected angle over the bins and the probability of the likeliest bin.
+fn decode_angle_bins(logits: &[f32]) -> Result<(f32, f32)> {
+    if logits.is_empty() {
+        return Err(anyhow::anyhow!("Empty pose output"));
+    }
+    let probabilities = softmax(logits);
+    let expected_bin: f32 = probabilities.iter().enumerate().map(|(bin, p)| bin as f32 * p).sum();
+    let confidence = probabilities.iter().copied().fold(0.0, f32::max);
+    Ok((expected_bin * POSE_BIN_DEGREES + POSE_BIN_START, confidence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_head_pose_from_degrees_and_bins() {
+        let pose = decode_head_pose(&[vec![12.5, -4.0, 1.0]]).unwrap();
+        assert_eq!((pose.yaw, pose.pitch, pose.roll), (12.5, -4.0, 1.0));
+
+        // All mass on bin 33 is 33 * 3 - 99 = 0 degrees; on bin 43, 30 degrees
+        let one_hot = |bin: usize| {
+            let mut logits = vec![-50.0; 66];
+            logits[bin] = 50.0;
+            logits
+        };
+        let pose = decode_head_pose(&[one_hot(43), one_hot(33), one_hot(33)]).unwrap();
+        assert!((pose.yaw - 30.0).abs() < 1e-3);
+        assert!(pose.pitch.abs() < 1e-3 && pose.roll.abs() < 1e-3);
+        assert!(pose.yaw_confidence > 0.99);
+
+        let pose = decode_head_pose(&[vec![-20.0], vec![3.0], vec![0.5]]).unwrap();
+        assert_eq!((pose.yaw, pose.pitch, pose.roll), (-20.0, 3.0, 0.5));
+
+        assert!(decode_head_pose(&[vec![1.0, 2.0]]).is_err());
+        assert!(decode_head_pose(&[vec![], vec![], vec![]]).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::attributes::landmarks::{FacialLandmark, FacialLandmarks};
+
+/// Mouth width, relative to the distance between the eye centers, of a
+/// relaxed mouth and of a full smile.
+const NEUTRAL_MOUTH_WIDTH: f32 = 0.75;
+const FULL_SMILE_MOUTH_WIDTH: f32 = 1.1;
+/// Height of the mouth corners above the lip center, relative to mouth
+/// width, at a full smile.
+const FULL_SMILE_CORNER_LIFT: f32 = 0.15;
+
+/// Continuous smile score in `0.0..=1.0` from mouth geometry, independent of
+/// the emotion classifier.
+///
+/// Two cues are averaged, each mapped linearly from a relaxed mouth (0) to a
+/// full smile (1) and clamped:
+/// - width: the distance between the mouth corners (`outer_lips` points 0
+///   and n/2) divided by the distance between the eye centers, so the score
+///   does not depend on face size;
+/// - curvature: how far the corners sit above the mean of `inner_lips`,
+///   divided by the mouth width. Image y grows downwards, so raised corners
+///   give a positive lift.
+///
+/// Returns `None` when the eyes or lips are missing points.
+pub fn smile_intensity(landmarks: &FacialLandmarks) -> Option<f32> {
+    let outer = &landmarks.outer_lips;
+    if outer.len() < 4 || outer.len() % 2 != 0 || landmarks.inner_lips.is_empty() {
+        return None;
+    }
+
+    let interocular = distance(centroid(&landmarks.left_eye)?, centroid(&landmarks.right_eye)?);
+    let (left_corner, right_corner) = (&outer[0], &outer[outer.len() / 2]);
+    let mouth_width = distance((left_corner.x, left_corner.y), (right_corner.x, right_corner.y));
+    if interocular <= f32::EPSILON || mouth_width <= f32::EPSILON {
+        return None;
+    }
+
+    let (_, lip_center_y) = centroid(&landmarks.inner_lips)?;
+    let corner_y = (left_corner.y + right_corner.y) / 2.0;
+    let corner_lift = (lip_center_y - corner_y) / mouth_width;
+
+    let width_score = ramp(mouth_width / interocular, NEUTRAL_MOUTH_WIDTH, FULL_SMILE_MOUTH_WIDTH);
+    let curve_score = ramp(corner_lift, 0.0, FULL_SMILE_CORNER_LIFT);
+    Some((width_score + curve_score) / 2.0)
+}
+
+/// Linear map of `value` from `low..high` onto `0..1`, clamped.
+fn ramp(value: f32, low: f32, high: f32) -> f32 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+fn centroid(points: &[FacialLandmark]) -> Option<(f32, f32)> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f32;
+    Some((
+        points.iter().map(|p| p.x).sum::<f32>() / n,
+        points.iter().map(|p| p.y).sum::<f32>() / n,
+    ))
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> FacialLandmark {
+        FacialLandmark { x, y, confidence: 1.0 }
+    }
+
+    /// Eyes 40px apart at y=40 and a 12-point outer lip contour whose corners
+    /// are `width` apart and raised `lift` pixels above the lip center at y=100.
+    fn face(width: f32, lift: f32) -> FacialLandmarks {
+        let half = width / 2.0;
+        let outer_lips = (0..12)
+            .map(|i| {
+                let angle = std::f32::consts::PI * i as f32 / 6.0;
+                let x = 50.0 - half * angle.cos();
+                // Corners (i = 0, 6) are raised; the lips bulge 6px around the center
+                let y = if i % 6 == 0 { 100.0 - lift } else { 100.0 + 6.0 * angle.sin() };
+                point(x, y)
+            })
+            .collect();
+        let inner_lips = vec![point(45.0, 99.0), point(55.0, 99.0), point(55.0, 101.0), point(45.0, 101.0)];
+
+        FacialLandmarks {
+            jaw_line: Vec::new(),
+            left_eye: vec![point(30.0, 40.0)],
+            right_eye: vec![point(70.0, 40.0)],
+            left_eyebrow: Vec::new(),
+            right_eyebrow: Vec::new(),
+            nose_bridge: Vec::new(),
+            nose_tip: point(50.0, 70.0),
+            outer_lips,
+            inner_lips,
+        }
+    }
+
+    #[test]
+    fn test_smile_intensity_on_synthetic_mouths() {
+        let neutral = smile_intensity(&face(30.0, 0.0)).unwrap();
+        let slight = smile_intensity(&face(36.0, 2.0)).unwrap();
+        let full = smile_intensity(&face(44.0, 6.6)).unwrap();
+
+        assert!(neutral < 0.05, "neutral {}", neutral);
+        assert!(slight > neutral && slight < full, "slight {}", slight);
+        assert!(full > 0.95, "full {}", full);
+    }
+
+    #[test]
+    fn test_smile_intensity_needs_eyes_and_lips() {
+        let mut missing = face(30.0, 0.0);
+        missing.left_eye.clear();
+        assert_eq!(smile_intensity(&missing), None);
+
+        let mut missing = face(30.0, 0.0);
+        missing.outer_lips.truncate(3);
+        assert_eq!(smile_intensity(&missing), None);
+    }
+}
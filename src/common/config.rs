@@ -0,0 +1,341 @@
+use anyhow::{Context, Result};
+use ort::Session;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::performance::gpu::GpuConfig;
+use crate::processing::detectors::DetectorType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl InputSize {
+    pub const fn new(width: i32, height: i32) -> Self {
+        Self { width, height }
+    }
+
+    /// Spatial size declared by the model's first input, assuming NCHW layout.
+    /// Returns `None` when the model uses dynamic dimensions.
+    pub fn from_session(session: &Session) -> Option<Self> {
+        let input = session.inputs.first()?;
+        if input.dimensions.len() != 4 {
+            return None;
+        }
+        let height = input.dimensions[2]?;
+        let width = input.dimensions[3]?;
+        Some(Self::new(width as i32, height as i32))
+    }
+}
+
+/// Expected input size for each model. Sizes declared in the model metadata
+/// take precedence; these values are used for models with dynamic inputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelInputSizes {
+    pub attributes: InputSize,
+    pub embedding: InputSize,
+    pub emotion: InputSize,
+    pub landmarks: InputSize,
+    pub pose: InputSize,
+    pub ethnicity: InputSize,
+    pub liveness: InputSize,
+    pub mask: InputSize,
+    pub accessories: InputSize,
+}
+
+impl Default for ModelInputSizes {
+    fn default() -> Self {
+        Self {
+            attributes: InputSize::new(62, 62),
+            embedding: InputSize::new(112, 112),
+            emotion: InputSize::new(64, 64),
+            landmarks: InputSize::new(112, 112),
+            pose: InputSize::new(224, 224),
+            ethnicity: InputSize::new(224, 224),
+            liveness: InputSize::new(80, 80),
+            mask: InputSize::new(128, 128),
+            accessories: InputSize::new(128, 128),
+        }
+    }
+}
+
+impl ModelInputSizes {
+    pub fn resolve(configured: InputSize, session: &Session) -> InputSize {
+        InputSize::from_session(session).unwrap_or(configured)
+    }
+}
+
+/// Minimum detection confidence for each detector type. Scores are not
+/// comparable across detectors (Haar always reports 1.0), so each type gets
+/// its own default instead of sharing one global threshold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectorThresholds {
+    pub haar: f32,
+    pub dnn: f32,
+    pub mtcnn: f32,
+    pub retinaface: f32,
+}
+
+impl Default for DetectorThresholds {
+    fn default() -> Self {
+        Self {
+            haar: 0.0,        // Haar has no real score; keep every detection
+            dnn: 0.5,
+            mtcnn: 0.9,
+            retinaface: 0.8,
+        }
+    }
+}
+
+impl DetectorThresholds {
+    pub fn for_type(&self, detector_type: DetectorType) -> f32 {
+        match detector_type {
+            DetectorType::Haar => self.haar,
+            DetectorType::DNN => self.dnn,
+            DetectorType::MTCNN => self.mtcnn,
+            DetectorType::RetinaFace => self.retinaface,
+        }
+    }
+}
+
+/// Image pyramid settings for the Haar cascade (`detect_multi_scale`).
+/// `min_face_size` also applies to the other detectors.
+///
+/// These trade recall against precision and speed: a `scale_factor` closer
+/// to 1.0 and a smaller `min_face_size` find small or tightly cropped faces
+/// but take longer, and a lower `min_neighbors` accepts weaker detections,
+/// which also lets through more false positives. If faces are missed, try
+/// `scale_factor = 1.05` and `min_neighbors = 2` first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionParams {
+    pub scale_factor: f32,  // Pyramid step between scales, > 1.0
+    pub min_neighbors: i32,  // Overlapping candidates needed to keep a detection
+    pub min_face_size: i32,  // Smallest face side in pixels
+}
+
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            scale_factor: 1.1,
+            min_neighbors: 3,
+            min_face_size: 30,
+        }
+    }
+}
+
+/// Multi-frame input such as animated GIFs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationConfig {
+    pub max_frames: usize,  // Frames analyzed per animation; later frames are skipped
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self { max_frames: 30 }
+    }
+}
+
+/// Model files used by the analyzer and detectors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPaths {
+    pub attributes: String,
+    pub cascade: String,
+    pub dnn_model: String,
+    pub dnn_config: String,
+    pub liveness: Option<String>,  // Anti-spoofing model; `liveness` is left unset without one
+    pub mask: Option<String>,      // Face-mask classifier; `mask` is left unset without one
+    pub accessories: Option<String>,  // Glasses and headwear classifier; `accessories` is left unset without one
+    pub landmarks: Option<String>,  // 68-point landmark model; needed for `eye_state` and `smile_intensity`
+    pub input_sizes: ModelInputSizes,  // Used for models whose inputs have dynamic dimensions
+}
+
+impl Default for ModelPaths {
+    fn default() -> Self {
+        Self {
+            attributes: "models/face_attributes.onnx".to_string(),
+            cascade: "haarcascades/haarcascade_frontalface_default.xml".to_string(),
+            dnn_model: "models/res10_300x300_ssd_iter_140000.caffemodel".to_string(),
+            dnn_config: "models/deploy.prototxt".to_string(),
+            liveness: None,
+            mask: None,
+            accessories: None,
+            landmarks: None,
+            input_sizes: ModelInputSizes::default(),
+        }
+    }
+}
+
+/// Where the CLI writes its results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub image_path: String,  // Annotated image for single-image runs
+    pub json_path: String,   // JSON results for single-image runs
+    pub batch_dir: String,   // Root of the annotated/, json/, faces/ and failed/ batch outputs
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            image_path: "images/output.jpg".to_string(),
+            json_path: "output.json".to_string(),
+            batch_dir: "batch_output".to_string(),
+        }
+    }
+}
+
+impl OutputConfig {
+    pub fn batch_subdir(&self, name: &str) -> PathBuf {
+        Path::new(&self.batch_dir).join(name)
+    }
+}
+
+/// Environment variable naming the config file to load.
+pub const CONFIG_ENV: &str = "FACE_ANALYZER_CONFIG";
+/// Config file picked up from the working directory when `CONFIG_ENV` is unset.
+pub const DEFAULT_CONFIG_FILE: &str = "face_analyzer.toml";
+
+/// Top-level application settings, read from a TOML (or JSON) file and
+/// then overridden by `FACE_ANALYZER_*` environment variables. Every field
+/// has a default, so a config file only needs to list what it changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub models: ModelPaths,
+    pub detector: DetectorType,
+    pub confidence_threshold: Option<f32>,  // Overrides the per-detector threshold
+    pub thresholds: DetectorThresholds,
+    pub detection: DetectionParams,
+    pub animation: AnimationConfig,
+    pub output: OutputConfig,
+    pub gpu: GpuConfig,  // Execution providers for every ONNX model
+}
+
+impl Config {
+    /// Load `path` if given, otherwise the file named by `FACE_ANALYZER_CONFIG`
+    /// or `face_analyzer.toml` when present, then apply environment overrides.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let env_path = std::env::var(CONFIG_ENV).ok().map(PathBuf::from);
+        let path = path.map(Path::to_path_buf).or(env_path).or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+            default.exists().then_some(default)
+        });
+
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_overrides(|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    /// Parse a config file, choosing JSON or TOML by extension.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let is_json = path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("json"));
+        let config = if is_json {
+            serde_json::from_str(&contents).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&contents).map_err(anyhow::Error::from)
+        };
+        config.with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Apply `FACE_ANALYZER_*` overrides. `lookup` is `std::env::var` outside tests.
+    pub fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let strings = [
+            ("FACE_ANALYZER_ATTRIBUTES_MODEL", &mut self.models.attributes),
+            ("FACE_ANALYZER_CASCADE", &mut self.models.cascade),
+            ("FACE_ANALYZER_DNN_MODEL", &mut self.models.dnn_model),
+            ("FACE_ANALYZER_DNN_CONFIG", &mut self.models.dnn_config),
+            ("FACE_ANALYZER_OUTPUT_DIR", &mut self.output.batch_dir),
+        ];
+        for (key, field) in strings {
+            if let Some(value) = lookup(key) {
+                *field = value;
+            }
+        }
+
+        if let Some(value) = lookup("FACE_ANALYZER_DETECTOR") {
+            self.detector = value.parse()?;
+        }
+        if let Some(value) = lookup("FACE_ANALYZER_CONFIDENCE") {
+            let threshold = value
+                .parse()
+                .with_context(|| format!("FACE_ANALYZER_CONFIDENCE is not a number: {}", value))?;
+            self.confidence_threshold = Some(threshold);
+        }
+        Ok(())
+    }
+
+    /// Effective detection threshold for the configured detector.
+    pub fn confidence_threshold(&self) -> f32 {
+        self.confidence_threshold
+            .unwrap_or_else(|| self.thresholds.for_type(self.detector))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::gpu::ProviderKind;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_partial_toml_keeps_defaults_and_env_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("face_analyzer.toml");
+        std::fs::write(
+            &path,
+            "detector = \"dnn\"\n\n[models]\nattributes = \"/opt/models/attrs.onnx\"\n\n\
+             [models.input_sizes]\nembedding = { width = 160, height = 160 }\n\n\
+             [gpu]\nproviders = [\"cuda\", \"cpu\"]\n",
+        )
+        .unwrap();
+
+        let mut config = Config::from_file(&path).unwrap();
+        assert_eq!(config.detector, DetectorType::DNN);
+        assert_eq!(config.models.attributes, "/opt/models/attrs.onnx");
+        assert_eq!(config.models.cascade, ModelPaths::default().cascade);
+        assert_eq!(config.models.input_sizes.embedding, InputSize::new(160, 160));
+        assert_eq!(config.models.input_sizes.attributes, InputSize::new(62, 62));
+        assert_eq!(config.confidence_threshold(), DetectorThresholds::default().dnn);
+        assert_eq!(config.gpu.providers, vec![ProviderKind::Cuda, ProviderKind::Cpu]);
+        assert_eq!(config.gpu.device_id, 0);
+
+        let env: HashMap<&str, &str> = [
+            ("FACE_ANALYZER_DETECTOR", "haar"),
+            ("FACE_ANALYZER_CONFIDENCE", "0.25"),
+            ("FACE_ANALYZER_OUTPUT_DIR", "/tmp/out"),
+        ]
+        .into_iter()
+        .collect();
+        config.apply_overrides(|key| env.get(key).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.detector, DetectorType::Haar);
+        assert_eq!(config.confidence_threshold(), 0.25);
+        assert_eq!(config.output.batch_subdir("json"), Path::new("/tmp/out/json"));
+    }
+
+    #[test]
+    fn test_json_config_and_bad_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{"confidence_threshold": 0.7}"#).unwrap();
+        let mut config = Config::from_file(&path).unwrap();
+        assert_eq!(config.confidence_threshold(), 0.7);
+        assert_eq!(config.models.input_sizes.embedding, InputSize::new(112, 112));
+        assert_eq!(config.gpu.providers, GpuConfig::default().providers);
+
+        assert!(config
+            .apply_overrides(|key| (key == "FACE_ANALYZER_DETECTOR").then(|| "yolo".to_string()))
+            .is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+/// Crate-wide error type, so callers can match on what went wrong instead
+/// of juggling `opencv::Result`, `anyhow::Result` and `Option`.
+#[derive(Debug)]
+pub enum FaceAnalyzerError {
+    Io(std::io::Error),
+    OpenCv(opencv::Error),
+    Ort(ort::OrtError),
+    Database(sqlx::Error),
+    Detection(String),  // Detector misconfigured or unavailable
+    Decode(String),     // Unreadable input or unexpected model output
+    Other(anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, FaceAnalyzerError>;
+
+impl FaceAnalyzerError {
+    pub fn detection(message: impl Into<String>) -> Self {
+        FaceAnalyzerError::Detection(message.into())
+    }
+
+    pub fn decode(message: impl Into<String>) -> Self {
+        FaceAnalyzerError::Decode(message.into())
+    }
+}
+
+impl fmt::Display for FaceAnalyzerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaceAnalyzerError::Io(e) => write!(f, "I/O error: {}", e),
+            FaceAnalyzerError::OpenCv(e) => write!(f, "OpenCV error: {}", e),
+            FaceAnalyzerError::Ort(e) => write!(f, "ONNX Runtime error: {}", e),
+            FaceAnalyzerError::Database(e) => write!(f, "Database error: {}", e),
+            FaceAnalyzerError::Detection(message) => write!(f, "Detection error: {}", message),
+            FaceAnalyzerError::Decode(message) => write!(f, "Decode error: {}", message),
+            FaceAnalyzerError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FaceAnalyzerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FaceAnalyzerError::Io(e) => Some(e),
+            FaceAnalyzerError::OpenCv(e) => Some(e),
+            FaceAnalyzerError::Ort(e) => Some(e),
+            FaceAnalyzerError::Database(e) => Some(e),
+            FaceAnalyzerError::Other(e) => Some(e.as_ref()),
+            FaceAnalyzerError::Detection(_) | FaceAnalyzerError::Decode(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FaceAnalyzerError {
+    fn from(e: std::io::Error) -> Self {
+        FaceAnalyzerError::Io(e)
+    }
+}
+
+impl From<opencv::Error> for FaceAnalyzerError {
+    fn from(e: opencv::Error) -> Self {
+        FaceAnalyzerError::OpenCv(e)
+    }
+}
+
+impl From<ort::OrtError> for FaceAnalyzerError {
+    fn from(e: ort::OrtError) -> Self {
+        FaceAnalyzerError::Ort(e)
+    }
+}
+
+impl From<sqlx::Error> for FaceAnalyzerError {
+    fn from(e: sqlx::Error) -> Self {
+        FaceAnalyzerError::Database(e)
+    }
+}
+
+impl From<anyhow::Error> for FaceAnalyzerError {
+    fn from(e: anyhow::Error) -> Self {
+        // Keep the specific variant when the error started out as one of ours
+        match e.downcast::<FaceAnalyzerError>() {
+            Ok(e) => e,
+            Err(e) => FaceAnalyzerError::Other(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_round_trip_through_anyhow() {
+        let original = FaceAnalyzerError::decode("model returned 3 outputs");
+        let wrapped: anyhow::Error = original.into();
+        match FaceAnalyzerError::from(wrapped) {
+            FaceAnalyzerError::Decode(message) => assert_eq!(message, "model returned 3 outputs"),
+            other => panic!("unexpected variant: {:?}", other),
+        }
+
+        let io: FaceAnalyzerError = std::io::Error::new(std::io::ErrorKind::NotFound, "gone").into();
+        assert!(matches!(io, FaceAnalyzerError::Io(_)));
+        assert_eq!(io.to_string(), "I/O error: gone");
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use env_logger::{Builder, Env};
+use log::LevelFilter;
+
+/// Level used when neither `--log-level` nor `RUST_LOG` is set.
+pub const DEFAULT_LEVEL: &str = "info";
+
+pub fn parse_level(level: &str) -> Result<LevelFilter> {
+    level
+        .parse()
+        .map_err(|_| anyhow!("Unknown log level: {} (expected off, error, warn, info, debug or trace)", level))
+}
+
+/// Install the global logger. An explicit `level` (from `--log-level`)
+/// applies to every module; otherwise `RUST_LOG` is honoured, falling back
+/// to `info`. Call once at startup.
+pub fn init(level: Option<&str>) -> Result<()> {
+    let mut builder = match level {
+        Some(level) => {
+            let mut builder = Builder::new();
+            builder.filter_level(parse_level(level)?);
+            builder
+        }
+        None => Builder::from_env(Env::default().default_filter_or(DEFAULT_LEVEL)),
+    };
+    builder
+        .format_timestamp_millis()
+        .try_init()
+        .map_err(|e| anyhow!("Failed to initialize logging: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("WARN").unwrap(), LevelFilter::Warn);
+        assert_eq!(parse_level("debug").unwrap(), LevelFilter::Debug);
+        assert!(parse_level("loud").is_err());
+    }
+}
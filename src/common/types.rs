@@ -0,0 +1,226 @@
+use opencv::core;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::attributes::landmarks::FacialLandmark;
+
+/// Axis-aligned face box in pixel coordinates.
+///
+/// Serialized with named fields. Older results stored the box as an
+/// `[x, y, width, height]` array, which is still accepted when reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BoundingBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl BoundingBox {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn area(&self) -> i32 {
+        self.width * self.height
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(
+            self.x as f32 + self.width as f32 / 2.0,
+            self.y as f32 + self.height as f32 / 2.0,
+        )
+    }
+
+    pub fn rect(&self) -> core::Rect {
+        core::Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+impl From<core::Rect> for BoundingBox {
+    fn from(rect: core::Rect) -> Self {
+        Self::new(rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+impl From<BoundingBox> for core::Rect {
+    fn from(bbox: BoundingBox) -> Self {
+        bbox.rect()
+    }
+}
+
+/// `rect` intersected with a `cols` x `rows` image, so a face detected at
+/// the edge is cropped to its visible part instead of failing `Mat::roi`.
+/// The result has zero area when `rect` lies entirely outside the image.
+pub fn clamp_rect_to_image(rect: core::Rect, cols: i32, rows: i32) -> core::Rect {
+    let (cols, rows) = (cols.max(0), rows.max(0));
+    let left = rect.x.clamp(0, cols);
+    let top = rect.y.clamp(0, rows);
+    let right = rect.x.saturating_add(rect.width).clamp(left, cols);
+    let bottom = rect.y.saturating_add(rect.height).clamp(top, rows);
+    core::Rect::new(left, top, right - left, bottom - top)
+}
+
+/// Copy of the part of `rect` that lies inside `image`, or `None` when the
+/// box is entirely outside it. Use this rather than `Mat::roi` on raw
+/// detector output, which fails for boxes that cross the image edge.
+pub fn crop_to_image(image: &core::Mat, rect: core::Rect) -> opencv::Result<Option<core::Mat>> {
+    let rect = clamp_rect_to_image(rect, image.cols(), image.rows());
+    if rect.area() == 0 {
+        return Ok(None);
+    }
+    core::Mat::roi(image, rect).and_then(|roi| roi.try_clone()).map(Some)
+}
+
+impl From<(i32, i32, i32, i32)> for BoundingBox {
+    fn from((x, y, width, height): (i32, i32, i32, i32)) -> Self {
+        Self::new(x, y, width, height)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoundingBox {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Named {
+            x: i32,
+            y: i32,
+            width: i32,
+            height: i32,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Named(Named),
+            Tuple(i32, i32, i32, i32),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Named(b) => BoundingBox::new(b.x, b.y, b.width, b.height),
+            Repr::Tuple(x, y, width, height) => BoundingBox::new(x, y, width, height),
+        })
+    }
+}
+
+/// Sub-pixel image position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<core::Point2f> for Point {
+    fn from(p: core::Point2f) -> Self {
+        Self::new(p.x, p.y)
+    }
+}
+
+impl From<core::Point> for Point {
+    fn from(p: core::Point) -> Self {
+        Self::new(p.x as f32, p.y as f32)
+    }
+}
+
+impl From<Point> for core::Point2f {
+    fn from(p: Point) -> Self {
+        core::Point2f::new(p.x, p.y)
+    }
+}
+
+impl From<Point> for core::Point {
+    fn from(p: Point) -> Self {
+        core::Point::new(p.x.round() as i32, p.y.round() as i32)
+    }
+}
+
+/// A single detected facial keypoint.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Landmark {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "default_confidence")]
+    pub confidence: f32,  // 1.0 when the detector doesn't report one
+}
+
+fn default_confidence() -> f32 {
+    1.0
+}
+
+impl Landmark {
+    pub fn new(x: f32, y: f32, confidence: f32) -> Self {
+        Self { x, y, confidence }
+    }
+
+    pub fn point(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+}
+
+impl From<core::Point2f> for Landmark {
+    fn from(p: core::Point2f) -> Self {
+        Self::new(p.x, p.y, default_confidence())
+    }
+}
+
+impl From<&FacialLandmark> for Landmark {
+    fn from(l: &FacialLandmark) -> Self {
+        Self::new(l.x, l.y, l.confidence)
+    }
+}
+
+impl From<Landmark> for core::Point2f {
+    fn from(l: Landmark) -> Self {
+        core::Point2f::new(l.x, l.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounding_box_serializes_named_and_reads_legacy_tuple() {
+        let bbox = BoundingBox::new(100, 0, 40, 40);
+        let json = serde_json::to_string(&bbox).unwrap();
+        assert_eq!(json, r#"{"x":100,"y":0,"width":40,"height":40}"#);
+
+        assert_eq!(serde_json::from_str::<BoundingBox>(&json).unwrap(), bbox);
+        assert_eq!(serde_json::from_str::<BoundingBox>("[100,0,40,40]").unwrap(), bbox);
+    }
+
+    #[test]
+    fn test_opencv_conversions_round_trip() {
+        let rect = core::Rect::new(3, 4, 50, 60);
+        assert_eq!(core::Rect::from(BoundingBox::from(rect)), rect);
+        assert_eq!(BoundingBox::from(rect).center(), Point::new(28.0, 34.0));
+
+        let landmark: Landmark = serde_json::from_str(r#"{"x":1.5,"y":2.5}"#).unwrap();
+        assert_eq!(landmark.confidence, 1.0);
+        assert_eq!(core::Point2f::from(landmark), core::Point2f::new(1.5, 2.5));
+    }
+
+    #[test]
+    fn test_clamp_rect_to_image() {
+        let clamp = |x, y, w, h| clamp_rect_to_image(core::Rect::new(x, y, w, h), 100, 80);
+        assert_eq!(clamp(10, 10, 20, 20), core::Rect::new(10, 10, 20, 20));
+        assert_eq!(clamp(-5, 70, 20, 20), core::Rect::new(0, 70, 15, 10));
+        assert_eq!(clamp(90, -10, 50, 200), core::Rect::new(90, 0, 10, 80));
+        assert_eq!(clamp(120, 10, 20, 20).area(), 0);
+    }
+
+    #[test]
+    fn test_crop_to_image_handles_out_of_bounds_boxes() {
+        let image = core::Mat::new_rows_cols_with_default(80, 100, core::CV_8UC3, core::Scalar::all(0.0)).unwrap();
+
+        let crop = crop_to_image(&image, core::Rect::new(90, 70, 50, 50)).unwrap().unwrap();
+        assert_eq!((crop.cols(), crop.rows()), (10, 10));
+        let crop = crop_to_image(&image, core::Rect::new(-20, -5, 40, 40)).unwrap().unwrap();
+        assert_eq!((crop.cols(), crop.rows()), (20, 35));
+        assert!(crop_to_image(&image, core::Rect::new(120, 10, 20, 20)).unwrap().is_none());
+    }
+}
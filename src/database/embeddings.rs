@@ -1,8 +1,17 @@
-use opencv::{core, prelude::*};
+use opencv::prelude::*;
 use ort::{Session, Value};
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::performance::gpu::{build_session_with, warm_up_session, GpuConfig};
+use crate::processing::preprocessing::image_to_chw;
 use ndarray::{Array1, Array2};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use crate::attributes::landmarks::FacialLandmarks;
+use crate::common::types::BoundingBox;
+use crate::face::FaceAttributes;
+use crate::processing::detectors::DetectorType;
+use crate::processing::quality::QualityMetrics;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FaceEmbedding {
@@ -18,28 +27,146 @@ pub struct FaceMetadata {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub source_image: String,
     pub confidence: f32,
+    #[serde(default, skip_serializing_if = "StoredMetadata::is_empty")]
+    pub details: StoredMetadata,  // Persisted in the `metadata` JSONB column
+}
+
+/// Analysis results kept with a stored face. Fields that were not computed
+/// are left out of the JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoredMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<BoundingBox>,  // Face box in the source image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub landmarks: Option<FacialLandmarks>,  // In source image coordinates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_copy: Option<String>,  // Full source image kept alongside an aligned chip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detector: Option<DetectorType>,  // Detector that found the face
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<FaceAttributes>,
+}
+
+impl StoredMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.bbox.is_none()
+            && self.landmarks.is_none()
+            && self.source_copy.is_none()
+            && self.detector.is_none()
+            && self.quality.is_none()
+            && self.attributes.is_none()
+    }
+
+    /// Parse the `metadata` column. Rows written before it was populated
+    /// hold `null` and give empty metadata.
+    pub fn from_json(value: Option<serde_json::Value>) -> Result<Self> {
+        let mut metadata: Self = match value {
+            None | Some(serde_json::Value::Null) => return Ok(Self::default()),
+            Some(value) => serde_json::from_value(value)?,
+        };
+        if let Some(attributes) = &mut metadata.attributes {
+            attributes.restore_unreliable();
+        }
+        Ok(metadata)
+    }
+}
+
+/// How embeddings are written in API and export output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingFormat {
+    #[default]
+    Floats,  // JSON array of numbers
+    Base64,  // Little-endian f32 bytes, base64 encoded; exact and ~3x smaller
+}
+
+/// An embedding in either output format. Deserializes from a float array or
+/// a base64 string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EncodedEmbedding {
+    Floats(Vec<f32>),
+    Base64(String),
+}
+
+impl EncodedEmbedding {
+    pub fn encode(embedding: &[f32], format: EmbeddingFormat) -> Self {
+        match format {
+            EmbeddingFormat::Floats => EncodedEmbedding::Floats(embedding.to_vec()),
+            EmbeddingFormat::Base64 => EncodedEmbedding::Base64(embedding_to_base64(embedding)),
+        }
+    }
+
+    pub fn decode(&self) -> Result<Vec<f32>> {
+        match self {
+            EncodedEmbedding::Floats(values) => Ok(values.clone()),
+            EncodedEmbedding::Base64(encoded) => embedding_from_base64(encoded),
+        }
+    }
+}
+
+pub fn embedding_to_base64(embedding: &[f32]) -> String {
+    let bytes: Vec<u8> = embedding.iter().flat_map(|value| value.to_le_bytes()).collect();
+    BASE64.encode(bytes)
+}
+
+pub fn embedding_from_base64(encoded: &str) -> Result<Vec<f32>> {
+    let bytes = BASE64.decode(encoded)?;
+    if bytes.len() % 4 != 0 {
+        return Err(anyhow::anyhow!(
+            "Base64 embedding has {} bytes, expected a multiple of 4",
+            bytes.len()
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
 }
 
 pub struct EmbeddingGenerator {
     session: Session,
     embedding_size: usize,
+    input_size: InputSize,
 }
 
 impl EmbeddingGenerator {
     pub fn new(model_path: &str) -> Result<Self> {
+        Self::with_gpu_config(model_path, &GpuConfig::default())
+    }
+
+    /// Load `model_path` with the execution providers and the
+    /// `models.input_sizes.embedding` fallback size from `config`.
+    pub fn from_config(model_path: &str, config: &Config) -> Result<Self> {
+        Ok(Self::with_gpu_config(model_path, &config.gpu)?.with_input_size(config.models.input_sizes.embedding))
+    }
+
+    pub fn with_gpu_config(model_path: &str, gpu: &GpuConfig) -> Result<Self> {
         let environment = ort::Environment::builder()
             .with_name("face_embedding")
-            .build()?;
+            .build()?
+            .into_arc();
         
-        let session = ort::SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)?;
+        let session = build_session_with(&environment, model_path, gpu)?;
+
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().embedding, &session);
 
         Ok(Self {
             session,
             embedding_size: 512,
+            input_size,
         })
     }
 
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
+    }
+
     pub fn generate(&self, face_mat: &Mat) -> Result<Vec<f32>> {
         let processed_tensor = self.preprocess_image(face_mat)?;
         
@@ -48,33 +175,14 @@ impl EmbeddingGenerator {
         self.postprocess_output(&outputs)
     }
 
-    fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
-        let mut resized = Mat::default();
-        opencv::imgproc::resize(
-            face_mat,
-            &mut resized,
-            core::Size::new(112, 112),
-            0.0,
-            0.0,
-            opencv::imgproc::INTER_LINEAR,
-        )?;
-
-        let mut float_mat = Mat::default();
-        resized.convert_to(&mut float_mat, core::CV_32F, 1.0/255.0, 0.0)?;
-
-        let mut tensor_data = vec![0f32; 1 * 3 * 112 * 112];
-        for y in 0..112 {
-            for x in 0..112 {
-                let pixel = float_mat.at_2d::<core::Vec3f>(y, x)?;
-                for c in 0..3 {
-                    tensor_data[c * 112 * 112 + y * 112 + x] = pixel[c];
-                }
-            }
-        }
+    /// Run one dummy inference so the first real face is not slowed by
+    /// ONNX Runtime's lazy allocation.
+    pub fn warm_up(&self) -> Result<()> {
+        warm_up_session(&self.session, self.input_size)
+    }
 
-        Ok(ort::Tensor::from_array(
-            ndarray::Array4::from_shape_vec((1, 3, 112, 112), tensor_data)?
-        ))
+    fn preprocess_image(&self, face_mat: &Mat) -> Result<ort::Tensor<f32>> {
+        Ok(ort::Tensor::from_array(image_to_chw(face_mat, self.input_size)?))
     }
 
     fn postprocess_output(&self, outputs: &[Value]) -> Result<Vec<f32>> {
@@ -103,6 +211,81 @@ impl EmbeddingGenerator {
 
 pub struct EmbeddingComparator;
 
+/// How `find_matches` scores a pair of embeddings.
+///
+/// Pick the metric the embedding model was trained with: ArcFace/CosFace
+/// style models (including the bundled one) use `Cosine`; FaceNet style
+/// triplet-loss models use `Euclidean`, or `L2Normalized` when their
+/// outputs are not already unit length. `EmbeddingGenerator` normalizes its
+/// output, so for it all three rank matches identically and only the
+/// threshold scale differs (`L2Normalized` distance is `sqrt(2 - 2 * cosine)`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    #[default]
+    Cosine,        // Similarity in -1..=1, higher is better
+    Euclidean,     // Distance between the raw vectors, lower is better
+    L2Normalized,  // Distance between unit-length vectors in 0..=2, lower is better
+}
+
+impl Metric {
+    pub fn score(&self, emb1: &[f32], emb2: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => EmbeddingComparator::cosine_similarity(emb1, emb2),
+            Metric::Euclidean => EmbeddingComparator::euclidean_distance(emb1, emb2),
+            Metric::L2Normalized => EmbeddingComparator::euclidean_distance(&l2_normalize(emb1), &l2_normalize(emb2)),
+        }
+    }
+
+    pub fn higher_is_better(&self) -> bool {
+        matches!(self, Metric::Cosine)
+    }
+
+    /// Whether `score` is a match: above `threshold` for similarities,
+    /// below it for distances.
+    pub fn accepts(&self, score: f32, threshold: f32) -> bool {
+        if self.higher_is_better() {
+            score > threshold
+        } else {
+            score < threshold
+        }
+    }
+
+    /// Orders scores best first.
+    pub fn compare(&self, a: f32, b: f32) -> std::cmp::Ordering {
+        if self.higher_is_better() {
+            b.total_cmp(&a)
+        } else {
+            a.total_cmp(&b)
+        }
+    }
+}
+
+fn l2_normalize(embedding: &[f32]) -> Vec<f32> {
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm <= f32::EPSILON {
+        return embedding.to_vec();
+    }
+    embedding.iter().map(|x| x / norm).collect()
+}
+
+/// Verification accuracy at one similarity threshold, where a pair counts as
+/// a match when its similarity is above the threshold as in `find_matches`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThresholdStats {
+    pub threshold: f32,
+    pub true_positive_rate: f32,   // Same-person pairs accepted
+    pub false_positive_rate: f32,  // Different-person pairs accepted
+    pub precision: f32,            // Accepted pairs that are the same person; 1.0 when none are accepted
+    pub recall: f32,               // Equal to `true_positive_rate`
+}
+
+impl ThresholdStats {
+    pub fn false_negative_rate(&self) -> f32 {
+        1.0 - self.true_positive_rate
+    }
+}
+
 impl EmbeddingComparator {
     pub fn cosine_similarity(emb1: &[f32], emb2: &[f32]) -> f32 {
         let mut dot_product = 0.0;
@@ -127,58 +310,368 @@ impl EmbeddingComparator {
         sum_squares.sqrt()
     }
 
+    /// Faces scoring past `threshold` under `metric`, best match first.
+    /// The threshold is in the metric's own units.
     pub fn find_matches(
         query_embedding: &[f32],
         database_embeddings: &[FaceEmbedding],
         threshold: f32,
+        metric: Metric,
     ) -> Vec<(String, f32)> {
         let mut matches = Vec::new();
         
         for db_face in database_embeddings {
-            let similarity = Self::cosine_similarity(query_embedding, &db_face.embedding);
-            if similarity > threshold {
-                matches.push((db_face.face_id.clone(), similarity));
+            let score = metric.score(query_embedding, &db_face.embedding);
+            if metric.accepts(score, threshold) {
+                matches.push((db_face.face_id.clone(), score));
             }
         }
         
-        matches.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        matches.sort_by(|a, b| metric.compare(a.1, b.1));
         matches
     }
 
+    /// Sweep `thresholds` over labeled pairs `(a, b, same_person)` to help
+    /// choose the `find_matches` threshold. Stats follow the order of
+    /// `thresholds`; see `equal_error_point` for a balanced default.
+    pub fn evaluate_threshold(pairs: &[(Vec<f32>, Vec<f32>, bool)], thresholds: &[f32]) -> Vec<ThresholdStats> {
+        let scored: Vec<(f32, bool)> = pairs
+            .iter()
+            .map(|(a, b, same)| (Self::cosine_similarity(a, b), *same))
+            .collect();
+        let positives = scored.iter().filter(|(_, same)| *same).count();
+        let negatives = scored.len() - positives;
+        let rate = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f32 / total as f32 };
+
+        thresholds
+            .iter()
+            .map(|&threshold| {
+                let accepted = scored.iter().filter(|(similarity, _)| *similarity > threshold);
+                let (true_positives, false_positives) = accepted.fold((0, 0), |(tp, fp), (_, same)| {
+                    if *same { (tp + 1, fp) } else { (tp, fp + 1) }
+                });
+                let true_positive_rate = rate(true_positives, positives);
+                ThresholdStats {
+                    threshold,
+                    true_positive_rate,
+                    false_positive_rate: rate(false_positives, negatives),
+                    precision: if true_positives + false_positives == 0 {
+                        1.0
+                    } else {
+                        rate(true_positives, true_positives + false_positives)
+                    },
+                    recall: true_positive_rate,
+                }
+            })
+            .collect()
+    }
+
+    /// The evaluated threshold nearest the equal error rate, where false
+    /// accepts and false rejects are equally likely.
+    pub fn equal_error_point(stats: &[ThresholdStats]) -> Option<&ThresholdStats> {
+        stats.iter().min_by(|a, b| {
+            let gap = |s: &ThresholdStats| (s.false_positive_rate - s.false_negative_rate()).abs();
+            gap(a).total_cmp(&gap(b))
+        })
+    }
+
+    /// Group faces whose pairwise similarity exceeds `threshold`. Any two faces
+    /// connected by a chain of such pairs land in the same cluster, so the result
+    /// does not depend on input order. Clusters are sorted by size, largest first.
     pub fn cluster_embeddings(
         embeddings: &[FaceEmbedding],
         threshold: f32,
     ) -> Vec<Vec<String>> {
-        let mut clusters = Vec::new();
-        let mut assigned = vec![false; embeddings.len()];
-        
+        let mut sets = DisjointSet::new(embeddings.len());
+
         for i in 0..embeddings.len() {
-            if assigned[i] {
-                continue;
-            }
-            
-            let mut cluster = vec![embeddings[i].face_id.clone()];
-            assigned[i] = true;
-            
             for j in (i + 1)..embeddings.len() {
-                if assigned[j] {
-                    continue;
-                }
-                
                 let similarity = Self::cosine_similarity(
                     &embeddings[i].embedding,
                     &embeddings[j].embedding,
                 );
-                
+
                 if similarity > threshold {
-                    cluster.push(embeddings[j].face_id.clone());
-                    assigned[j] = true;
+                    sets.union(i, j);
                 }
             }
-            
-            clusters.push(cluster);
         }
-        
-        clusters
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of_root = std::collections::HashMap::new();
+        for i in 0..embeddings.len() {
+            let root = sets.find(i);
+            let group = *group_of_root.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(i);
+        }
+
+        Self::into_sorted_clusters(embeddings, groups)
+    }
+
+    /// Hierarchical clustering that repeatedly merges the two most similar
+    /// clusters until no pair is more similar than `threshold` under the
+    /// chosen linkage.
+    pub fn cluster_embeddings_agglomerative(
+        embeddings: &[FaceEmbedding],
+        threshold: f32,
+        linkage: Linkage,
+    ) -> Vec<Vec<String>> {
+        let n = embeddings.len();
+        let mut similarities = vec![vec![0.0f32; n]; n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let similarity = Self::cosine_similarity(
+                    &embeddings[i].embedding,
+                    &embeddings[j].embedding,
+                );
+                similarities[i][j] = similarity;
+                similarities[j][i] = similarity;
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+
+        loop {
+            let mut best: Option<(usize, usize, f32)> = None;
+
+            for a in 0..groups.len() {
+                for b in (a + 1)..groups.len() {
+                    let similarity = linkage.similarity(&groups[a], &groups[b], &similarities);
+                    if similarity > threshold
+                        && best.map(|(_, _, s)| similarity > s).unwrap_or(true)
+                    {
+                        best = Some((a, b, similarity));
+                    }
+                }
+            }
+
+            match best {
+                Some((a, b, _)) => {
+                    let merged = groups.remove(b);
+                    groups[a].extend(merged);
+                    groups[a].sort_unstable();
+                }
+                None => break,
+            }
+        }
+
+        Self::into_sorted_clusters(embeddings, groups)
+    }
+
+    /// The member with the highest total similarity to the rest of its cluster.
+    pub fn medoid<'a>(members: &[&'a FaceEmbedding]) -> Option<&'a FaceEmbedding> {
+        members
+            .iter()
+            .map(|candidate| {
+                let total: f32 = members
+                    .iter()
+                    .map(|other| Self::cosine_similarity(&candidate.embedding, &other.embedding))
+                    .sum();
+                (*candidate, total)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(candidate, _)| candidate)
+    }
+
+    fn into_sorted_clusters(
+        embeddings: &[FaceEmbedding],
+        mut groups: Vec<Vec<usize>>,
+    ) -> Vec<Vec<String>> {
+        // Stable sort keeps clusters of equal size in order of their first member
+        groups.sort_by_key(|group| group[0]);
+        groups.sort_by(|a, b| b.len().cmp(&a.len()));
+
+        groups
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|i| embeddings[i].face_id.clone())
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linkage {
+    Single,    // Most similar pair between clusters
+    Complete,  // Least similar pair between clusters
+    Average,   // Mean similarity over all cross-cluster pairs
+}
+
+impl Linkage {
+    fn similarity(&self, a: &[usize], b: &[usize], similarities: &[Vec<f32>]) -> f32 {
+        let pairs = a.iter().flat_map(|&i| b.iter().map(move |&j| similarities[i][j]));
+
+        match self {
+            Linkage::Single => pairs.fold(f32::MIN, f32::max),
+            Linkage::Complete => pairs.fold(f32::MAX, f32::min),
+            Linkage::Average => {
+                let count = (a.len() * b.len()) as f32;
+                pairs.sum::<f32>() / count
+            }
+        }
+    }
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            let root = self.find(self.parent[x]);
+            self.parent[x] = root;
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return;
+        }
+
+        if self.rank[root_a] < self.rank[root_b] {
+            self.parent[root_a] = root_b;
+        } else if self.rank[root_a] > self.rank[root_b] {
+            self.parent[root_b] = root_a;
+        } else {
+            self.parent[root_b] = root_a;
+            self.rank[root_a] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(face_id: &str, embedding: Vec<f32>) -> FaceEmbedding {
+        FaceEmbedding {
+            face_id: face_id.to_string(),
+            embedding,
+            metadata: FaceMetadata {
+                name: None,
+                tags: vec![],
+                timestamp: chrono::Utc::now(),
+                source_image: String::new(),
+                confidence: 1.0,
+                details: Default::default(),
+            },
+        }
     }
-} 
\ No newline at end of file
+
+    fn three_groups() -> Vec<FaceEmbedding> {
+        vec![
+            face("b1", vec![0.0, 1.0, 0.05]),
+            face("a1", vec![1.0, 0.0, 0.0]),
+            face("c1", vec![0.0, 0.0, 1.0]),
+            face("a2", vec![0.98, 0.05, 0.0]),
+            face("b2", vec![0.05, 0.97, 0.0]),
+            face("a3", vec![0.95, 0.0, 0.1]),
+        ]
+    }
+
+    #[test]
+    fn test_cluster_embeddings_three_groups() {
+        let mut faces = three_groups();
+        let clusters = EmbeddingComparator::cluster_embeddings(&faces, 0.9);
+        assert_eq!(clusters, vec![
+            vec!["a1".to_string(), "a2".to_string(), "a3".to_string()],
+            vec!["b1".to_string(), "b2".to_string()],
+            vec!["c1".to_string()],
+        ]);
+
+        // Reversing the input must not change group membership
+        faces.reverse();
+        let reversed = EmbeddingComparator::cluster_embeddings(&faces, 0.9);
+        let sizes: Vec<usize> = reversed.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 1]);
+        assert!(reversed[0].contains(&"a1".to_string()));
+        assert!(reversed[1].contains(&"b2".to_string()));
+    }
+
+    #[test]
+    fn test_cluster_embeddings_agglomerative() {
+        let faces = three_groups();
+        for linkage in [Linkage::Single, Linkage::Complete, Linkage::Average] {
+            let clusters = EmbeddingComparator::cluster_embeddings_agglomerative(&faces, 0.9, linkage);
+            let sizes: Vec<usize> = clusters.iter().map(|c| c.len()).collect();
+            assert_eq!(sizes, vec![3, 2, 1]);
+        }
+    }
+
+    #[test]
+    fn test_threshold_sweep_finds_equal_error_point() {
+        let pairs = vec![
+            (vec![1.0, 0.0], vec![1.0, 0.1], true),   // ~0.995
+            (vec![1.0, 0.0], vec![1.0, 0.5], true),   // ~0.894
+            (vec![1.0, 0.0], vec![1.0, 1.0], false),  // ~0.707
+            (vec![1.0, 0.0], vec![0.0, 1.0], false),  // 0.0
+        ];
+        let stats = EmbeddingComparator::evaluate_threshold(&pairs, &[0.5, 0.8, 0.95]);
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!((stats[0].true_positive_rate, stats[0].false_positive_rate), (1.0, 0.5));
+        assert_eq!(stats[0].precision, 2.0 / 3.0);
+        assert_eq!((stats[1].true_positive_rate, stats[1].false_positive_rate), (1.0, 0.0));
+        assert_eq!(stats[2].recall, 0.5);
+
+        let eer = EmbeddingComparator::equal_error_point(&stats).unwrap();
+        assert_eq!(eer.threshold, 0.8);
+    }
+
+    #[test]
+    fn test_find_matches_orders_distances_ascending() {
+        let faces = vec![
+            face("near", vec![1.0, 0.1]),
+            face("far", vec![0.0, 1.0]),
+            face("closest", vec![1.0, 0.0]),
+        ];
+        let query = [2.0, 0.0];
+
+        let ids = |matches: Vec<(String, f32)>| matches.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(EmbeddingComparator::find_matches(&query, &faces, 0.9, Metric::Cosine)),
+            vec!["closest", "near"],
+        );
+        assert_eq!(
+            ids(EmbeddingComparator::find_matches(&query, &faces, 0.5, Metric::L2Normalized)),
+            vec!["closest", "near"],
+        );
+        // Raw distance sees the query's length, so nothing is within 0.5
+        assert!(EmbeddingComparator::find_matches(&query, &faces, 0.5, Metric::Euclidean).is_empty());
+        assert_eq!(
+            ids(EmbeddingComparator::find_matches(&query, &faces, 1.1, Metric::Euclidean)),
+            vec!["closest", "near"],
+        );
+    }
+
+    #[test]
+    fn test_base64_embedding_round_trip_is_exact() {
+        let original = vec![0.1f32, -1.5e-7, 3.402_823_5e38, f32::MIN_POSITIVE, -0.0, 0.333_333_34];
+        let encoded = EncodedEmbedding::encode(&original, EmbeddingFormat::Base64);
+
+        let json = serde_json::to_string(&encoded).unwrap();
+        let decoded: EncodedEmbedding = serde_json::from_str(&json).unwrap();
+        let values = decoded.decode().unwrap();
+
+        let bits = |v: &[f32]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&values), bits(&original));
+        assert!(embedding_from_base64("AAA=").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use super::embeddings::{EmbeddingFormat, EncodedEmbedding, FaceEmbedding, FaceMetadata};
+
+const GALLERY_FORMAT: &str = "face-analyzer-gallery";
+const GALLERY_VERSION: u32 = 1;
+
+/// First line of a gallery archive. The archive is JSON lines: this header,
+/// then one `GalleryRecord` per face, so a whole gallery moves as one file.
+#[derive(Debug, Serialize, Deserialize)]
+struct GalleryHeader {
+    format: String,
+    version: u32,
+    dimension: Option<usize>,  // Embedding length shared by every record; None for an empty gallery
+    faces: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GalleryRecord {
+    face_id: String,
+    embedding: EncodedEmbedding,  // Always base64 so values survive exactly
+    metadata: FaceMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<String>,  // Base64 of the decrypted stored image
+}
+
+/// A face read from an archive, with its image bytes when they were exported.
+#[derive(Debug)]
+pub struct GalleryEntry {
+    pub face: FaceEmbedding,
+    pub image: Option<Vec<u8>>,
+}
+
+/// Serialize faces into an archive. `images` holds the image bytes of each
+/// face, in the same order, or `None` to leave the image out.
+pub fn write_gallery(faces: &[FaceEmbedding], images: &[Option<Vec<u8>>]) -> Result<String> {
+    let dimension = faces.first().map(|face| face.embedding.len());
+    if let Some(face) = faces.iter().find(|face| Some(face.embedding.len()) != dimension) {
+        anyhow::bail!(
+            "Face {} has a {}-value embedding, expected {}",
+            face.face_id,
+            face.embedding.len(),
+            dimension.unwrap_or_default()
+        );
+    }
+
+    let header = GalleryHeader {
+        format: GALLERY_FORMAT.to_string(),
+        version: GALLERY_VERSION,
+        dimension,
+        faces: faces.len(),
+    };
+    let mut archive = serde_json::to_string(&header)?;
+    archive.push('\n');
+
+    for (face, image) in faces.iter().zip(images) {
+        let record = GalleryRecord {
+            face_id: face.face_id.clone(),
+            embedding: EncodedEmbedding::encode(&face.embedding, EmbeddingFormat::Base64),
+            metadata: face.metadata.clone(),
+            image: image.as_ref().map(|data| BASE64.encode(data)),
+        };
+        archive.push_str(&serde_json::to_string(&record)?);
+        archive.push('\n');
+    }
+    Ok(archive)
+}
+
+/// Parse and validate a whole archive before anything is imported. Every
+/// embedding must have the header's dimension, and that must match
+/// `existing_dimension`, the length of embeddings already in the gallery.
+pub fn read_gallery(archive: &str, existing_dimension: Option<usize>) -> Result<Vec<GalleryEntry>> {
+    let mut lines = archive.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or_else(|| anyhow::anyhow!("Gallery archive is empty"))?;
+    let header: GalleryHeader = serde_json::from_str(header)
+        .map_err(|e| anyhow::anyhow!("Invalid gallery archive header: {}", e))?;
+    if header.format != GALLERY_FORMAT || header.version != GALLERY_VERSION {
+        anyhow::bail!("Unsupported gallery archive: {} version {}", header.format, header.version);
+    }
+    if let (Some(existing), Some(dimension)) = (existing_dimension, header.dimension) {
+        if existing != dimension {
+            anyhow::bail!(
+                "Gallery archive has {}-value embeddings but the database holds {}-value embeddings",
+                dimension,
+                existing
+            );
+        }
+    }
+
+    let mut entries = Vec::with_capacity(header.faces);
+    for (index, line) in lines {
+        let line_number = index + 1;
+        let record: GalleryRecord = serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("Invalid gallery record on line {}: {}", line_number, e))?;
+        let embedding = record.embedding.decode()?;
+        if Some(embedding.len()) != header.dimension {
+            anyhow::bail!(
+                "Face {} on line {} has a {}-value embedding, expected {}",
+                record.face_id,
+                line_number,
+                embedding.len(),
+                header.dimension.unwrap_or_default()
+            );
+        }
+        let image = record.image.map(|image| BASE64.decode(image)).transpose()?;
+
+        entries.push(GalleryEntry {
+            face: FaceEmbedding {
+                embedding,
+                face_id: record.face_id,
+                metadata: record.metadata,
+            },
+            image,
+        });
+    }
+
+    if entries.len() != header.faces {
+        anyhow::bail!("Gallery archive lists {} faces but holds {}", header.faces, entries.len());
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(face_id: &str, embedding: Vec<f32>) -> FaceEmbedding {
+        FaceEmbedding {
+            face_id: face_id.to_string(),
+            embedding,
+            metadata: FaceMetadata {
+                name: Some("Ada".to_string()),
+                tags: vec!["staff".to_string()],
+                timestamp: chrono::Utc::now(),
+                source_image: "data/faces/a.jpg".to_string(),
+                confidence: 0.9,
+                details: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_gallery_round_trip_and_dimension_checks() {
+        let faces = vec![face("a", vec![0.1, -0.2, 0.3]), face("b", vec![1.0, 0.0, 0.5])];
+        let images = vec![Some(vec![255u8, 216, 255]), None];
+        let archive = write_gallery(&faces, &images).unwrap();
+
+        let entries = read_gallery(&archive, Some(3)).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].face.embedding, faces[0].embedding);
+        assert_eq!(entries[0].face.metadata.name.as_deref(), Some("Ada"));
+        assert_eq!(entries[0].image, images[0]);
+        assert_eq!(entries[1].image, None);
+
+        assert!(read_gallery(&archive, Some(512)).is_err());
+        assert!(write_gallery(&[face("a", vec![0.1]), face("b", vec![0.1, 0.2])], &[None, None]).is_err());
+    }
+}
@@ -1,15 +1,27 @@
-use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+use sqlx::{Pool, Postgres, QueryBuilder, Row, postgres::PgPoolOptions};
 use anyhow::Result;
-use serde_json::Value as JsonValue;
 use uuid::Uuid;
-use super::embeddings::{FaceEmbedding, FaceMetadata};
-use std::path::Path;
+use super::embeddings::{FaceEmbedding, FaceMetadata, StoredMetadata};
+use super::gallery::{read_gallery, write_gallery};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
+use opencv::{core, imgcodecs, prelude::*};
+use sha2::{Digest, Sha256};
+use crate::attributes::emotion::Emotion;
+use crate::attributes::landmarks::FacialLandmarks;
+use crate::common::types::{crop_to_image, BoundingBox};
+use crate::processing::alignment::{align_face, AlignmentTemplate};
+use crate::security::encryption::SecureStorage;
 
 pub struct DatabaseConfig {
     pub connection_string: String,
     pub max_connections: u32,
     pub image_storage_path: String,
+    pub gallery_template: Option<AlignmentTemplate>,  // Store aligned chips instead of source images
+    pub keep_source_image: bool,  // With a gallery template, also keep the full source image
+    pub encryption_password: Option<String>,  // Encrypt stored images at rest as {face_id}.enc
+    pub dedupe_images: bool,  // Skip faces whose stored image bytes match a face already stored
 }
 
 impl Default for DatabaseConfig {
@@ -18,6 +30,10 @@ impl Default for DatabaseConfig {
             connection_string: "postgres://localhost/face_analyzer".to_string(),
             max_connections: 5,
             image_storage_path: "data/faces".to_string(),
+            gallery_template: None,
+            keep_source_image: false,
+            encryption_password: None,
+            dedupe_images: false,
         }
     }
 }
@@ -25,6 +41,7 @@ impl Default for DatabaseConfig {
 pub struct Database {
     pool: Pool<Postgres>,
     config: DatabaseConfig,
+    secure_storage: Option<Arc<SecureStorage>>,
 }
 
 impl Database {
@@ -38,7 +55,21 @@ impl Database {
 
         fs::create_dir_all(&config.image_storage_path).await?;
 
-        Ok(Self { pool, config })
+        let secure_storage = match &config.encryption_password {
+            Some(password) => Some(Arc::new(SecureStorage::new(
+                password,
+                config.image_storage_path.clone(),
+            )?)),
+            None => None,
+        };
+
+        Ok(Self { pool, config, secure_storage })
+    }
+
+    /// Storage used to encrypt face images, when encryption at rest is
+    /// enabled. Share it with anything that needs to read stored images.
+    pub fn secure_storage(&self) -> Option<Arc<SecureStorage>> {
+        self.secure_storage.clone()
     }
 
     async fn initialize_schema(pool: &Pool<Postgres>) -> Result<()> {
@@ -51,10 +82,25 @@ impl Database {
                 timestamp TIMESTAMPTZ NOT NULL,
                 source_image TEXT NOT NULL,
                 confidence FLOAT NOT NULL,
-                metadata JSONB
+                metadata JSONB,
+                content_hash TEXT
             );
 
+            ALTER TABLE faces ADD COLUMN IF NOT EXISTS content_hash TEXT;
+
             CREATE INDEX IF NOT EXISTS faces_name_idx ON faces(name);
+
+            -- Older schemas had a plain index and could hold repeated hashes;
+            -- keep the hash on the oldest row only so the unique index builds
+            DROP INDEX IF EXISTS faces_content_hash_idx;
+            UPDATE faces SET content_hash = NULL WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, row_number() OVER (PARTITION BY content_hash ORDER BY timestamp, id) AS n
+                    FROM faces WHERE content_hash IS NOT NULL
+                ) ranked WHERE n > 1
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS faces_content_hash_key ON faces(content_hash)
+                WHERE content_hash IS NOT NULL;
             CREATE INDEX IF NOT EXISTS faces_timestamp_idx ON faces(timestamp);
             CREATE INDEX IF NOT EXISTS faces_tags_idx ON faces USING GIN(tags);
         "#).execute(pool).await?;
@@ -62,21 +108,181 @@ impl Database {
         Ok(())
     }
 
-    pub async fn store_face(&self, face: FaceEmbedding) -> Result<()> {
-        let image_path = Path::new(&face.metadata.source_image);
-        let file_name = format!("{}.jpg", face.face_id);
-        let storage_path = Path::new(&self.config.image_storage_path).join(&file_name);
-        
-        fs::copy(image_path, &storage_path).await?;
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
 
-        sqlx::query!(
+    /// Wait for in-flight queries to finish and close all pooled connections.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    fn storage_path(&self, key: &str) -> PathBuf {
+        let extension = if self.secure_storage.is_some() { "enc" } else { "jpg" };
+        Path::new(&self.config.image_storage_path).join(format!("{}.{}", key, extension))
+    }
+
+    /// Where the full source image of `face_id` goes when it is kept
+    /// alongside the aligned chip.
+    fn source_copy_path(&self, face_id: &str) -> PathBuf {
+        self.storage_path(&source_copy_key(face_id))
+    }
+
+    /// Write image bytes under `key`, encrypted when encryption is enabled.
+    async fn write_image(&self, key: &str, data: &[u8]) -> Result<PathBuf> {
+        let path = self.storage_path(key);
+        match &self.secure_storage {
+            Some(secure_storage) => secure_storage.store(key, data).await?,
+            None => fs::write(&path, data).await?,
+        }
+        Ok(path)
+    }
+
+    /// Read a stored face image, decrypting it if it was stored encrypted.
+    pub async fn read_image(&self, source_image: &str) -> Result<Vec<u8>> {
+        read_stored_image(self.secure_storage.as_deref(), source_image).await
+    }
+
+    /// Store the face image and row. With `dedupe_images`, if an identical
+    /// image is already stored the insert is skipped and the existing face's
+    /// id is returned instead.
+    pub async fn store_face(&self, face: FaceEmbedding) -> Result<StoreOutcome> {
+        let data = fs::read(&face.metadata.source_image).await?;
+        self.store_face_bytes(&face, &data, &face.metadata.details).await
+    }
+
+    /// Store the face found at `bbox` in `image` as a chip aligned to the
+    /// configured gallery template, so every stored image has the eyes at
+    /// the same position. `landmarks` are in `image` coordinates and are
+    /// added to the face's stored metadata with the box. With
+    /// `keep_source_image` the full image is stored next to the chip.
+    /// Without a gallery template the plain crop is stored instead.
+    pub async fn store_face_normalized(
+        &self,
+        face: FaceEmbedding,
+        image: &Mat,
+        landmarks: &FacialLandmarks,
+        bbox: BoundingBox,
+    ) -> Result<StoreOutcome> {
+        let mut metadata = face.metadata.details.clone();
+        metadata.bbox = Some(bbox);
+        metadata.landmarks = Some(landmarks.clone());
+        let template = match &self.config.gallery_template {
+            Some(template) => template,
+            None => {
+                let crop = crop_to_image(image, bbox.rect())?
+                    .ok_or_else(|| anyhow::anyhow!("Face box lies outside the image"))?;
+                return self.store_face_bytes(&face, &encode_jpeg(&crop)?, &metadata).await;
+            }
+        };
+
+        let chip = encode_jpeg(&align_face(image, landmarks, template)?)?;
+        let content_hash = self.dedupe_hash(&chip);
+        if let Some(existing_id) = self.find_existing(content_hash.as_deref()).await? {
+            return Ok(StoreOutcome::Duplicate(existing_id));
+        }
+        let mut written = Vec::with_capacity(2);
+        if self.config.keep_source_image {
+            let path = self.write_image(&source_copy_key(&face.face_id), &encode_jpeg(image)?).await?;
+            metadata.source_copy = Some(path.to_string_lossy().into_owned());
+            written.push(path);
+        }
+
+        let storage_path = self.write_image(&face.face_id, &chip).await?;
+        written.push(storage_path.clone());
+        self.insert_unless_duplicate(&face, &storage_path, content_hash.as_deref(), &metadata, &written)
+            .await
+    }
+
+    /// Store a face cropped out of a larger upload, so several faces from
+    /// one image each get their own stored image.
+    pub async fn store_face_crop(&self, face: FaceEmbedding, crop: &Mat, bbox: BoundingBox) -> Result<StoreOutcome> {
+        let mut metadata = face.metadata.details.clone();
+        metadata.bbox = Some(bbox);
+        self.store_face_bytes(&face, &encode_jpeg(crop)?, &metadata).await
+    }
+
+    async fn store_face_bytes(&self, face: &FaceEmbedding, data: &[u8], metadata: &StoredMetadata) -> Result<StoreOutcome> {
+        let content_hash = self.dedupe_hash(data);
+        if let Some(existing_id) = self.find_existing(content_hash.as_deref()).await? {
+            return Ok(StoreOutcome::Duplicate(existing_id));
+        }
+
+        let storage_path = self.write_image(&face.face_id, data).await?;
+        self.insert_unless_duplicate(face, &storage_path, content_hash.as_deref(), metadata, &[storage_path.clone()])
+            .await
+    }
+
+    /// Hash stored with a new face, only when image deduplication is on.
+    fn dedupe_hash(&self, data: &[u8]) -> Option<String> {
+        self.config.dedupe_images.then(|| content_hash(data))
+    }
+
+    async fn find_existing(&self, content_hash: Option<&str>) -> Result<Option<String>> {
+        match content_hash {
+            Some(content_hash) => self.find_by_hash(content_hash).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Insert the row. When a face with the same content hash was stored
+    /// after `find_existing` ran, the unique index rejects this one: the
+    /// `written` files are removed and the other face is reported instead.
+    async fn insert_unless_duplicate(
+        &self,
+        face: &FaceEmbedding,
+        storage_path: &Path,
+        content_hash: Option<&str>,
+        metadata: &StoredMetadata,
+        written: &[PathBuf],
+    ) -> Result<StoreOutcome> {
+        if self.insert_face(face, storage_path, content_hash, metadata).await? {
+            return Ok(StoreOutcome::Inserted);
+        }
+
+        for path in written {
+            let _ = fs::remove_file(path).await;
+        }
+        let existing_id = self
+            .find_existing(content_hash)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Face {} conflicted with a face that no longer exists", face.face_id))?;
+        Ok(StoreOutcome::Duplicate(existing_id))
+    }
+
+    pub async fn find_by_hash(&self, content_hash: &str) -> Result<Option<String>> {
+        let record = sqlx::query!(
+            r#"
+            SELECT id FROM faces WHERE content_hash = $1 LIMIT 1
+            "#,
+            content_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.map(|r| r.id.to_string()))
+    }
+
+    /// Returns `false` when a face with the same `content_hash` is already
+    /// stored and nothing was inserted.
+    async fn insert_face(
+        &self,
+        face: &FaceEmbedding,
+        storage_path: &Path,
+        content_hash: Option<&str>,  // None when deduplication is off or the image bytes are not available
+        metadata: &StoredMetadata,
+    ) -> Result<bool> {
+        let inserted = sqlx::query!(
             r#"
             INSERT INTO faces (
                 id, embedding, name, tags, timestamp, source_image,
-                confidence, metadata
+                confidence, metadata, content_hash
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8
+                $1, $2, $3, $4, $5, $6, $7, $8, $9
             )
+            ON CONFLICT (content_hash) WHERE content_hash IS NOT NULL DO NOTHING
+            RETURNING id
             "#,
             Uuid::parse_str(&face.face_id)?,
             &face.embedding as &[f32],
@@ -85,12 +291,91 @@ impl Database {
             face.metadata.timestamp,
             storage_path.to_str().unwrap(),
             face.metadata.confidence,
-            JsonValue::Null,
+            serde_json::to_value(metadata)?,
+            content_hash,
         )
-        .execute(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(inserted.is_some())
+    }
+
+    /// Length of the embeddings already stored, if there are any.
+    async fn embedding_dimension(&self) -> Result<Option<usize>> {
+        let record = sqlx::query!(
+            r#"
+            SELECT array_length(embedding, 1) AS dimension FROM faces LIMIT 1
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record.and_then(|r| r.dimension).map(|dimension| dimension as usize))
+    }
+
+    /// Write every stored face to a single gallery archive at `path` (see
+    /// `database::gallery`), so a deployment can be backed up or migrated
+    /// without re-running inference. With `include_images` the decrypted
+    /// stored images go into the archive too. Returns the number of faces.
+    pub async fn export_gallery(&self, path: impl AsRef<Path>, include_images: bool) -> Result<usize> {
+        let faces = self.search_faces(&SearchQuery::default()).await?;
+
+        let mut images = Vec::with_capacity(faces.len());
+        for face in &faces {
+            let image = if include_images {
+                match self.read_image(&face.metadata.source_image).await {
+                    Ok(data) => Some(data),
+                    Err(e) => {
+                        log::warn!("Exporting face {} without its image: {}", face.face_id, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            images.push(image);
+        }
+
+        fs::write(path, write_gallery(&faces, &images)?).await?;
+        Ok(faces.len())
+    }
+
+    /// Import a gallery archive written by `export_gallery`. The archive is
+    /// validated in full, including the embedding dimension against faces
+    /// already stored, before anything is written. Faces whose id is already
+    /// stored are skipped, and, with `dedupe_images`, so are faces whose
+    /// image bytes match an already stored image. Images are stored under
+    /// this database's storage settings; faces exported without an image
+    /// keep their original `source_image` path. Returns the number imported.
+    pub async fn import_gallery(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let archive = fs::read_to_string(path).await?;
+        let entries = read_gallery(&archive, self.embedding_dimension().await?)?;
+
+        let mut imported = 0;
+        for entry in entries {
+            let face = entry.face;
+            if self.get_face(&face.face_id).await?.is_some() {
+                log::info!("Skipping face {}: already stored", face.face_id);
+                continue;
+            }
+
+            let outcome = match &entry.image {
+                Some(data) => self.store_face_bytes(&face, data, &face.metadata.details).await?,
+                None => {
+                    let source_image = Path::new(&face.metadata.source_image);
+                    self.insert_face(&face, source_image, None, &face.metadata.details).await?;
+                    StoreOutcome::Inserted
+                }
+            };
+            match outcome {
+                StoreOutcome::Inserted => imported += 1,
+                StoreOutcome::Duplicate(existing_id) => {
+                    log::info!("Skipping face {}: same image as {}", face.face_id, existing_id);
+                }
+            }
+        }
+
+        Ok(imported)
     }
 
     pub async fn get_face(&self, face_id: &str) -> Result<Option<FaceEmbedding>> {
@@ -103,7 +388,11 @@ impl Database {
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(record.map(|r| FaceEmbedding {
+        let r = match record {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        Ok(Some(FaceEmbedding {
             face_id: r.id.to_string(),
             embedding: r.embedding,
             metadata: FaceMetadata {
@@ -112,51 +401,59 @@ impl Database {
                 timestamp: r.timestamp,
                 source_image: r.source_image,
                 confidence: r.confidence,
+                details: StoredMetadata::from_json(r.metadata)?,
             },
         }))
     }
 
     pub async fn search_faces(&self, query: &SearchQuery) -> Result<Vec<FaceEmbedding>> {
-        let mut sql = String::from("SELECT * FROM faces WHERE 1=1");
-        let mut params = vec![];
+        let mut sql = QueryBuilder::<Postgres>::new("SELECT * FROM faces WHERE 1=1");
 
         if let Some(name) = &query.name {
-            sql.push_str(" AND name ILIKE $1");
-            params.push(format!("%{}%", name));
+            sql.push(" AND name ILIKE ").push_bind(format!("%{}%", name));
         }
 
         if let Some(tags) = &query.tags {
-            sql.push_str(" AND tags && $2");
-            params.push(tags.join(","));
+            sql.push(" AND tags && ").push_bind(tags.clone());
         }
 
         if let Some(start_date) = query.start_date {
-            sql.push_str(" AND timestamp >= $3");
-            params.push(start_date.to_string());
+            sql.push(" AND timestamp >= ").push_bind(start_date);
         }
 
         if let Some(end_date) = query.end_date {
-            sql.push_str(" AND timestamp <= $4");
-            params.push(end_date.to_string());
+            sql.push(" AND timestamp <= ").push_bind(end_date);
         }
 
         if let Some(min_confidence) = query.min_confidence {
-            sql.push_str(" AND confidence >= $5");
-            params.push(min_confidence.to_string());
+            sql.push(" AND confidence >= ").push_bind(min_confidence);
+        }
+
+        // Attribute filters match the `attributes` object persisted in
+        // `metadata`; faces stored without attributes never match them.
+        if let Some(emotion) = &query.emotion {
+            let emotion = serde_json::json!({ "attributes": { "emotion": { "emotion": emotion } } });
+            sql.push(" AND metadata @> ").push_bind(emotion);
         }
 
-        sql.push_str(" ORDER BY timestamp DESC");
+        if let Some(gender) = &query.gender {
+            let gender = serde_json::json!({ "attributes": { "gender": gender.to_lowercase() } });
+            sql.push(" AND metadata @> ").push_bind(gender);
+        }
 
-        let records = sqlx::query(&sql)
-            .bind(params.get(0).unwrap_or(&String::new()))
-            .bind(params.get(1).unwrap_or(&String::new()))
-            .bind(params.get(2).unwrap_or(&String::new()))
-            .bind(params.get(3).unwrap_or(&String::new()))
-            .bind(params.get(4).unwrap_or(&String::new()))
-            .fetch_all(&self.pool)
-            .await?;
+        if let Some(min_age) = query.min_age {
+            sql.push(" AND (metadata->'attributes'->>'age')::real >= ").push_bind(min_age);
+        }
 
-        let faces = records.into_iter().map(|r| FaceEmbedding {
+        if let Some(max_age) = query.max_age {
+            sql.push(" AND (metadata->'attributes'->>'age')::real <= ").push_bind(max_age);
+        }
+
+        sql.push(" ORDER BY timestamp DESC");
+
+        let records = sql.build().fetch_all(&self.pool).await?;
+
+        records.into_iter().map(|r| Ok(FaceEmbedding {
             face_id: r.get::<Uuid, _>("id").to_string(),
             embedding: r.get::<Vec<f32>, _>("embedding"),
             metadata: FaceMetadata {
@@ -165,10 +462,9 @@ impl Database {
                 timestamp: r.get("timestamp"),
                 source_image: r.get("source_image"),
                 confidence: r.get("confidence"),
+                details: StoredMetadata::from_json(r.get("metadata"))?,
             },
-        }).collect();
-
-        Ok(faces)
+        })).collect()
     }
 
     pub async fn update_face(&self, face_id: &str, updates: FaceUpdates) -> Result<()> {
@@ -216,8 +512,9 @@ impl Database {
 
         if let Some(record) = record {
             if let Err(e) = fs::remove_file(&record.source_image).await {
-                eprintln!("Failed to delete image file: {}", e);
+                log::error!("Failed to delete image file {}: {}", record.source_image, e);
             }
+            self.remove_source_copy(face_id).await;
         }
 
         sqlx::query!(
@@ -239,7 +536,7 @@ impl Database {
             r#"
             DELETE FROM faces 
             WHERE timestamp < $1
-            RETURNING source_image
+            RETURNING id, source_image
             "#,
             cutoff,
         )
@@ -248,24 +545,113 @@ impl Database {
 
         for record in &records {
             if let Err(e) = fs::remove_file(&record.source_image).await {
-                eprintln!("Failed to delete image file: {}", e);
+                log::error!("Failed to delete image file {}: {}", record.source_image, e);
             }
+            self.remove_source_copy(&record.id.to_string()).await;
         }
 
         Ok(records.len() as u64)
     }
+
+    /// Remove the kept source image of `face_id`, if there is one.
+    async fn remove_source_copy(&self, face_id: &str) {
+        let path = self.source_copy_path(face_id);
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::error!("Failed to delete image file {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn source_copy_key(face_id: &str) -> String {
+    format!("{}_source", face_id)
+}
+
+fn encode_jpeg(image: &Mat) -> Result<Vec<u8>> {
+    let mut encoded = core::Vector::<u8>::new();
+    imgcodecs::imencode(".jpg", image, &mut encoded, &core::Vector::<i32>::new())?;
+    Ok(encoded.to_vec())
+}
+
+/// Read an image written by `Database`. Files with an `.enc` extension are
+/// decrypted through `secure_storage`; anything else is read as is.
+pub async fn read_stored_image(secure_storage: Option<&SecureStorage>, path: &str) -> Result<Vec<u8>> {
+    let path = Path::new(path);
+    if path.extension().and_then(|ext| ext.to_str()) != Some("enc") {
+        return Ok(fs::read(path).await?);
+    }
+
+    let secure_storage = secure_storage
+        .ok_or_else(|| anyhow::anyhow!("{} is encrypted but no encryption key is configured", path.display()))?;
+    let key = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid encrypted image path: {}", path.display()))?;
+    secure_storage.retrieve(key).await
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreOutcome {
+    Inserted,
+    Duplicate(String),  // Id of the face already stored with identical image bytes
 }
 
+/// Hex-encoded SHA-256 of the stored image bytes.
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(Default)]
 pub struct SearchQuery {
     pub name: Option<String>,
     pub tags: Option<Vec<String>>,
     pub start_date: Option<chrono::DateTime<chrono::Utc>>,
     pub end_date: Option<chrono::DateTime<chrono::Utc>>,
     pub min_confidence: Option<f32>,
+    pub emotion: Option<Emotion>,
+    pub min_age: Option<f32>,
+    pub max_age: Option<f32>,
+    pub gender: Option<String>,  // "male" or "female", case-insensitive
 }
 
 pub struct FaceUpdates {
     pub name: Option<String>,
     pub tags: Option<Vec<String>>,
     pub confidence: Option<f32>,
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_crop_hashes_identically() {
+        let crop = vec![255u8, 216, 255, 224, 0, 16, 74, 70, 73, 70];
+        let mut other = crop.clone();
+        other[9] = 0;
+
+        assert_eq!(content_hash(&crop), content_hash(&crop.clone()));
+        assert_ne!(content_hash(&crop), content_hash(&other));
+        assert_eq!(content_hash(&crop).len(), 64);
+    }
+
+    #[test]
+    fn test_stored_metadata_omits_missing_fields() {
+        assert_eq!(serde_json::to_value(StoredMetadata::default()).unwrap(), serde_json::json!({}));
+
+        let metadata = StoredMetadata {
+            bbox: Some(BoundingBox::new(10, 20, 30, 40)),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(value["bbox"]["width"], 30);
+        assert!(value.get("landmarks").is_none());
+        let parsed = StoredMetadata::from_json(Some(value)).unwrap();
+        assert_eq!(parsed.bbox, metadata.bbox);
+        assert!(StoredMetadata::from_json(Some(serde_json::Value::Null)).unwrap().is_empty());
+    }
+}
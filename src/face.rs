@@ -1,14 +1,22 @@
-use opencv::{core, imgproc, prelude::*};
+use opencv::prelude::*;
 use ort::{Session, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::common::config::{Config, InputSize};
+use crate::common::error::{FaceAnalyzerError, Result};
+use crate::processing::preprocessing::image_to_chw;
 use crate::attributes::{
     emotion::{Emotion, EmotionPrediction},
-    landmarks::FacialLandmarks,
+    landmarks::{FacialLandmarks, LandmarkDetector},
     pose::PoseEstimation,
     ethnicity::EthnicityPrediction,
+    liveness::{LivenessDetector, LivenessResult},
+    mask::{MaskDetector, MaskPrediction},
+    accessories::{Accessories, AccessoryDetector},
+    eyes::EyeState,
+    smile::smile_intensity,
 };
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FaceAttributes {
     pub age: f32,
     pub gender: String,
@@ -16,70 +24,361 @@ pub struct FaceAttributes {
     pub landmarks: Option<FacialLandmarks>,
     pub pose: Option<PoseEstimation>,
     pub ethnicity: Option<EthnicityPrediction>,
+    pub liveness: Option<LivenessResult>,
+    pub mask: Option<MaskPrediction>,
+    pub accessories: Option<Accessories>,
+    pub eye_state: Option<EyeState>,  // Derived from `landmarks`
+    pub smile_intensity: Option<f32>,  // 0.0 to 1.0 from mouth geometry, see `attributes::smile`
+    #[serde(skip_serializing_if = "Vec::is_empty", skip_deserializing)]
+    pub unreliable: Vec<&'static str>,  // Attributes to distrust, e.g. age and emotion behind a mask; see `restore_unreliable`
 }
 
-pub fn analyze_face(face_roi: &Mat, session: &Session) -> Option<FaceAttributes> {
-    let mut resized = Mat::default();
-    imgproc::resize(
-        face_roi,
-        &mut resized,
-        core::Size { width: 62, height: 62 },
-        0.0,
-        0.0,
-        imgproc::INTER_LINEAR,
-    ).ok()?;
-    let mut bgr = Mat::default();
-    if resized.channels() == 1 {
-        imgproc::cvt_color(&resized, &mut bgr, imgproc::COLOR_GRAY2BGR, 0).ok()?;
-    } else {
-        bgr = resized;
-    }
-    let mut bgr_f32 = Mat::default();
-    bgr.convert_to(&mut bgr_f32, core::CV_32F, 1.0 / 255.0, 0.0).ok()?;
-    let mut chw = vec![0f32; 3 * 62 * 62];
-    for c in 0..3 {
-        for y in 0..62 {
-            for x in 0..62 {
-                let val = *bgr_f32.at_2d::<core::Vec3f>(y, x).ok()?;
-                chw[c * 62 * 62 + y * 62 + x] = val[c];
-            }
+/// Smallest ROI side, in pixels, the attribute models are run on. Smaller
+/// crops, e.g. from a box clamped at the image edge, carry no usable detail.
+pub const MIN_FACE_ROI_SIZE: i32 = 8;
+
+/// Attributes read from the lower face, which a mask hides.
+const MASK_OCCLUDED_ATTRIBUTES: [&str; 2] = ["age", "emotion"];
+
+/// A model run on the face ROI for one optional attribute. Implemented by
+/// the attribute detectors; tests substitute canned results.
+pub trait FaceModel<T>: Send + Sync {
+    fn run(&self, face_roi: &Mat) -> anyhow::Result<T>;
+}
+
+impl FaceModel<FacialLandmarks> for LandmarkDetector {
+    fn run(&self, face_roi: &Mat) -> anyhow::Result<FacialLandmarks> {
+        self.detect(face_roi)
+    }
+}
+
+impl FaceModel<LivenessResult> for LivenessDetector {
+    fn run(&self, face_roi: &Mat) -> anyhow::Result<LivenessResult> {
+        self.detect(face_roi)
+    }
+}
+
+impl FaceModel<MaskPrediction> for MaskDetector {
+    fn run(&self, face_roi: &Mat) -> anyhow::Result<MaskPrediction> {
+        self.detect(face_roi)
+    }
+}
+
+impl FaceModel<Accessories> for AccessoryDetector {
+    fn run(&self, face_roi: &Mat) -> anyhow::Result<Accessories> {
+        self.detect(face_roi)
+    }
+}
+
+/// Optional per-face models run alongside the attribute model. An attribute
+/// whose detector is not configured, or fails, is left as `None`.
+#[derive(Default)]
+pub struct AttributeDetectors {
+    pub landmarks: Option<Box<dyn FaceModel<FacialLandmarks>>>,
+    pub liveness: Option<Box<dyn FaceModel<LivenessResult>>>,
+    pub mask: Option<Box<dyn FaceModel<MaskPrediction>>>,
+    pub accessories: Option<Box<dyn FaceModel<Accessories>>>,
+}
+
+impl AttributeDetectors {
+    /// Load the detectors whose model paths are set in `config.models`.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut detectors = Self::default();
+        if let Some(path) = &config.models.landmarks {
+            detectors = detectors.with_landmarks(LandmarkDetector::from_config(path, config)?);
+        }
+        if let Some(path) = &config.models.liveness {
+            detectors = detectors.with_liveness(LivenessDetector::from_config(path, config)?);
+        }
+        if let Some(path) = &config.models.mask {
+            detectors = detectors.with_mask(MaskDetector::from_config(path, config)?);
+        }
+        if let Some(path) = &config.models.accessories {
+            detectors = detectors.with_accessories(AccessoryDetector::from_config(path, config)?);
         }
+        Ok(detectors)
     }
-    let input_tensor = ort::Tensor::from_array(
-        ndarray::Array4::from_shape_vec((1, 3, 62, 62), chw).ok()?
-    );
-    let outputs = session.run(vec![input_tensor]).ok()?;
+
+    pub fn with_landmarks(mut self, detector: impl FaceModel<FacialLandmarks> + 'static) -> Self {
+        self.landmarks = Some(Box::new(detector));
+        self
+    }
+
+    pub fn with_liveness(mut self, detector: impl FaceModel<LivenessResult> + 'static) -> Self {
+        self.liveness = Some(Box::new(detector));
+        self
+    }
+
+    pub fn with_mask(mut self, detector: impl FaceModel<MaskPrediction> + 'static) -> Self {
+        self.mask = Some(Box::new(detector));
+        self
+    }
+
+    pub fn with_accessories(mut self, detector: impl FaceModel<Accessories> + 'static) -> Self {
+        self.accessories = Some(Box::new(detector));
+        self
+    }
+
+    /// Fill the optional attributes of `attributes` from `face_roi`, along
+    /// with the ones derived from them.
+    pub fn apply(&self, face_roi: &Mat, attributes: &mut FaceAttributes) {
+        attributes.landmarks = run_optional(self.landmarks.as_deref(), "Landmark", face_roi);
+        attributes.liveness = run_optional(self.liveness.as_deref(), "Liveness", face_roi);
+        attributes.mask = run_optional(self.mask.as_deref(), "Mask", face_roi);
+        attributes.accessories = run_optional(self.accessories.as_deref(), "Accessory", face_roi);
+        attributes.eye_state = attributes.landmarks.as_ref().and_then(EyeState::from_landmarks);
+        attributes.smile_intensity = attributes.landmarks.as_ref().and_then(smile_intensity);
+        attributes.unreliable = unreliable_attributes(attributes.mask.as_ref());
+    }
+}
+
+/// Run an optional detector, logging and dropping its error so one failing
+/// model does not discard the rest of the face's attributes.
+fn run_optional<T>(detector: Option<&dyn FaceModel<T>>, name: &str, face_roi: &Mat) -> Option<T> {
+    detector.and_then(|detector| {
+        detector
+            .run(face_roi)
+            .map_err(|e| log::warn!("{} inference failed: {}", name, e))
+            .ok()
+    })
+}
+
+/// Numerically stable softmax over raw classifier logits.
+pub(crate) fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|&x| (x - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.into_iter().map(|x| x / sum).collect()
+}
+
+/// Positive-class probability from a binary classifier's output: taken as
+/// is for a single value, otherwise the softmax of the last class.
+pub(crate) fn binary_score(output: &[f32]) -> Option<f32> {
+    match output {
+        [] => None,
+        [score] => Some(score.clamp(0.0, 1.0)),
+        logits => softmax(logits).last().copied(),
+    }
+}
+
+/// Reject ROIs too small or empty to analyze, before any model sees them.
+fn check_face_roi(face_roi: &Mat) -> Result<()> {
+    if face_roi.empty() || face_roi.cols() < MIN_FACE_ROI_SIZE || face_roi.rows() < MIN_FACE_ROI_SIZE {
+        return Err(FaceAnalyzerError::decode(format!(
+            "Face ROI of {}x{} is below the {}px minimum",
+            face_roi.cols(),
+            face_roi.rows(),
+            MIN_FACE_ROI_SIZE
+        )));
+    }
+    Ok(())
+}
+
+pub fn analyze_face(face_roi: &Mat, session: &Session, input_size: InputSize) -> Result<FaceAttributes> {
+    analyze_face_with(face_roi, session, input_size, &AttributeDetectors::default())
+}
+
+pub fn analyze_face_with(
+    face_roi: &Mat,
+    session: &Session,
+    input_size: InputSize,
+    detectors: &AttributeDetectors,
+) -> Result<FaceAttributes> {
+    check_face_roi(face_roi)?;
+    let input_tensor = ort::Tensor::from_array(image_to_chw(face_roi, input_size)?);
+    let outputs = session.run(vec![input_tensor])?;
     if outputs.len() != 2 {
-        return None;
+        return Err(FaceAnalyzerError::decode(format!(
+            "Expected 2 attribute outputs, got {}",
+            outputs.len()
+        )));
     }
     let age = if let Value::Tensor(age_tensor) = &outputs[0] {
-        let age_val: f32 = *age_tensor.data::<f32>().ok()?.get(0)?;
+        let age_val: f32 = *age_tensor
+            .data::<f32>()?
+            .get(0)
+            .ok_or_else(|| FaceAnalyzerError::decode("Empty age output"))?;
         age_val * 100.0
     } else {
-        return None;
+        return Err(FaceAnalyzerError::decode("Age output is not a tensor"));
     };
     let gender = if let Value::Tensor(prob_tensor) = &outputs[1] {
-        let probs = prob_tensor.data::<f32>().ok()?;
+        let probs = prob_tensor.data::<f32>()?;
+        if probs.len() < 2 {
+            return Err(FaceAnalyzerError::decode("Gender output has fewer than 2 classes"));
+        }
         if probs[0] > probs[1] {
             "male"
         } else {
             "female"
         }.to_string()
     } else {
-        return None;
+        return Err(FaceAnalyzerError::decode("Gender output is not a tensor"));
     };
 
-    let emotion = None;
-    let landmarks = None;
-    let pose = None;
-    let ethnicity = None;
-
-    Some(FaceAttributes {
-        age,
-        gender,
-        emotion,
-        landmarks,
-        pose,
-        ethnicity,
-    })
+    let mut attributes = FaceAttributes::new(age, gender);
+    detectors.apply(face_roi, &mut attributes);
+    Ok(attributes)
+}
+
+impl FaceAttributes {
+    /// Age and gender from the attribute model, with every optional
+    /// attribute unset.
+    pub fn new(age: f32, gender: String) -> Self {
+        Self {
+            age,
+            gender,
+            emotion: None,
+            landmarks: None,
+            pose: None,
+            ethnicity: None,
+            liveness: None,
+            mask: None,
+            accessories: None,
+            eye_state: None,
+            smile_intensity: None,
+            unreliable: Vec::new(),
+        }
+    }
+
+    /// Recompute `unreliable`, which is not read back when deserializing.
+    pub fn restore_unreliable(&mut self) {
+        self.unreliable = unreliable_attributes(self.mask.as_ref());
+    }
+}
+
+fn unreliable_attributes(mask: Option<&MaskPrediction>) -> Vec<&'static str> {
+    match mask {
+        Some(mask) if mask.wearing_mask => MASK_OCCLUDED_ATTRIBUTES.to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::accessories::AccessoryFlag;
+
+    #[test]
+    fn test_degenerate_rois_are_rejected() {
+        let roi = |cols, rows| {
+            Mat::new_rows_cols_with_default(rows, cols, opencv::core::CV_8UC3, opencv::core::Scalar::all(0.0)).unwrap()
+        };
+        assert!(check_face_roi(&Mat::default()).is_err());
+        assert!(check_face_roi(&roi(3, 40)).is_err());
+        assert!(check_face_roi(&roi(MIN_FACE_ROI_SIZE, MIN_FACE_ROI_SIZE)).is_ok());
+    }
+
+    #[test]
+    fn test_softmax_sums_to_one() {
+        let probs = softmax(&[1.0, 2.0, 1000.0]);
+        assert!((probs.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(probs[2] > 0.99);
+    }
+
+    #[test]
+    fn test_mask_flags_lower_face_attributes() {
+        let masked = MaskPrediction { wearing_mask: true, confidence: 0.93 };
+        let bare = MaskPrediction { wearing_mask: false, confidence: 0.12 };
+        assert_eq!(unreliable_attributes(Some(&masked)), vec!["age", "emotion"]);
+        assert!(unreliable_attributes(Some(&bare)).is_empty());
+        assert!(unreliable_attributes(None).is_empty());
+    }
+
+    struct Canned<T>(T);
+
+    impl<T: Clone + Send + Sync> FaceModel<T> for Canned<T> {
+        fn run(&self, _face_roi: &Mat) -> anyhow::Result<T> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn face_roi() -> Mat {
+        Mat::new_rows_cols_with_default(64, 64, opencv::core::CV_8UC3, opencv::core::Scalar::all(0.0)).unwrap()
+    }
+
+    #[test]
+    fn test_configured_liveness_detector_fills_liveness() {
+        let mut attributes = FaceAttributes::new(31.0, "female".to_string());
+        AttributeDetectors::default().apply(&face_roi(), &mut attributes);
+        assert!(attributes.liveness.is_none());
+
+        let detectors = AttributeDetectors::default().with_liveness(Canned(LivenessResult { is_live: false, score: 0.07 }));
+        detectors.apply(&face_roi(), &mut attributes);
+        let liveness = attributes.liveness.unwrap();
+        assert!(!liveness.is_live);
+        assert_eq!(liveness.score, 0.07);
+        assert_eq!(attributes.age, 31.0);
+    }
+
+    #[test]
+    fn test_configured_mask_detector_fills_mask_and_flags_attributes() {
+        let detectors =
+            AttributeDetectors::default().with_mask(Canned(MaskPrediction { wearing_mask: true, confidence: 0.91 }));
+        let mut attributes = FaceAttributes::new(40.0, "male".to_string());
+        detectors.apply(&face_roi(), &mut attributes);
+
+        assert!(attributes.mask.as_ref().unwrap().wearing_mask);
+        assert_eq!(attributes.unreliable, vec!["age", "emotion"]);
+    }
+
+    #[test]
+    fn test_configured_accessory_detector_fills_accessories() {
+        let flag = |present| AccessoryFlag { present, confidence: if present { 0.9 } else { 0.1 } };
+        let detectors = AttributeDetectors::default().with_accessories(Canned(Accessories {
+            eyeglasses: flag(false),
+            sunglasses: flag(true),
+            headwear: flag(false),
+        }));
+        let mut attributes = FaceAttributes::new(25.0, "female".to_string());
+        detectors.apply(&face_roi(), &mut attributes);
+
+        assert_eq!(attributes.accessories.unwrap().present(), vec!["sunglasses"]);
+        assert!(attributes.mask.is_none());
+    }
+
+    #[test]
+    fn test_configured_landmark_detector_fills_smile_intensity() {
+        use crate::attributes::landmarks::FacialLandmark;
+
+        let point = |x: f32, y: f32| FacialLandmark { x, y, confidence: 1.0 };
+        let eye = |cx: f32| vec![point(cx - 6.0, 40.0), point(cx, 38.0), point(cx + 6.0, 40.0), point(cx, 42.0)];
+        // Corners 44px apart, 6px above the lip center: a broad smile
+        let outer_lips = (0..12)
+            .map(|i| {
+                let angle = std::f32::consts::PI * i as f32 / 6.0;
+                let y = if i % 6 == 0 { 94.0 } else { 100.0 + 6.0 * angle.sin() };
+                point(50.0 - 22.0 * angle.cos(), y)
+            })
+            .collect();
+        let landmarks = FacialLandmarks {
+            jaw_line: Vec::new(),
+            left_eye: eye(30.0),
+            right_eye: eye(70.0),
+            left_eyebrow: Vec::new(),
+            right_eyebrow: Vec::new(),
+            nose_bridge: Vec::new(),
+            nose_tip: point(50.0, 70.0),
+            outer_lips,
+            inner_lips: vec![point(45.0, 99.0), point(55.0, 99.0), point(55.0, 101.0), point(45.0, 101.0)],
+        };
+
+        let mut attributes = FaceAttributes::new(28.0, "male".to_string());
+        AttributeDetectors::default().apply(&face_roi(), &mut attributes);
+        assert!(attributes.smile_intensity.is_none());
+
+        AttributeDetectors::default()
+            .with_landmarks(Canned(landmarks))
+            .apply(&face_roi(), &mut attributes);
+        assert!(attributes.smile_intensity.unwrap() > 0.9);
+        assert!(attributes.eye_state.is_some());
+    }
+
+    #[test]
+    fn test_binary_score_handles_both_output_layouts() {
+        assert_eq!(binary_score(&[]), None);
+        assert_eq!(binary_score(&[0.83]), Some(0.83));
+        assert!(binary_score(&[3.0, -1.0]).unwrap() < 0.1);
+        assert!(binary_score(&[-1.0, 3.0]).unwrap() > 0.9);
+    }
 } 
\ No newline at end of file
@@ -6,22 +6,33 @@ pub mod attributes {
     pub mod landmarks;
     pub mod pose;
     pub mod ethnicity;
+    pub mod liveness;
+    pub mod mask;
+    pub mod accessories;
+    pub mod eyes;
+    pub mod smile;
 }
 
 pub mod realtime {
     pub mod webcam;
     pub mod video;
     pub mod visualization;
+    pub mod thumbnails;
+    pub mod pipeline;
+    pub mod tracking;
 }
 
 pub mod processing {
     pub mod preprocessing;
+    pub mod input;
     pub mod quality;
     pub mod detectors;
+    pub mod alignment;
 }
 
 pub mod database {
     pub mod embeddings;
+    pub mod gallery;
     pub mod similarity;
     pub mod storage;
 }
@@ -30,15 +41,21 @@ pub mod output {
     pub mod html;
     pub mod csv;
     pub mod progress;
+    pub mod report;
+    pub mod sink;
 }
 
 pub mod api {
+    pub mod error;
+    pub mod metrics;
     pub mod rest;
+    pub mod runtime;
     pub mod websocket;
     pub mod docker;
 }
 
 pub mod ui {
+    pub mod app;
     pub mod web;
     pub mod config;
     pub mod dashboard;
@@ -54,6 +71,7 @@ pub mod performance {
     pub mod gpu;
     pub mod threading;
     pub mod optimization;
+    pub mod timing;
 }
 
 pub mod common {
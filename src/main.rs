@@ -1,121 +1,516 @@
 use opencv::{core, imgcodecs, imgproc, objdetect, prelude::*, types};
 use serde::Serialize;
-use std::env;
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
 
 use ort::{Environment, SessionBuilder, Value};
 
-mod face;
-mod analysis;
-use crate::face::{analyze_face, FaceAttributes};
-use crate::analysis::{analyze_image, AnalysisResult, FaceResult};
+use face_analyzer::face::{analyze_face, FaceAttributes};
+use face_analyzer::analysis::{analyze_image_with_config, AnalysisResult, Analyzer, AnalyzerConfig, FaceResult};
+use face_analyzer::common::config::{Config, OutputConfig};
+use face_analyzer::common::logging;
+use face_analyzer::common::types::clamp_rect_to_image;
+use face_analyzer::processing::detectors::DetectorType;
+use face_analyzer::processing::input::{decode_image, is_gif, read_image};
+use face_analyzer::output::csv::CsvSink;
+use face_analyzer::output::report::ReportGenerator;
+use face_analyzer::output::progress::{BatchSummary, ProgressReporter};
+use face_analyzer::output::sink::{FileSink, NdjsonSink, ResultSink, StdoutSink};
+use face_analyzer::performance::gpu::ProviderKind;
+use face_analyzer::performance::threading::SessionPoolConfig;
 use std::io::Write;
 
-fn print_usage(program: &str) {
-    println!("Usage: {} <image_path> [output_image_path] [output_json_path]", program);
-    println!("\nArguments:");
-    println!("  <image_path>           Path to the input image (required)");
-    println!("  [output_image_path]    Path to save the annotated image (default: images/output.jpg)");
-    println!("  [output_json_path]     Path to save the JSON results (default: output.json)");
-    println!("\nOptions:");
-    println!("  -h, --help             Show this help message and exit");
+/// Detect faces in an image and estimate their attributes.
+#[derive(Parser, Debug)]
+#[command(name = "face-analyzer", version, about)]
+struct Cli {
+    /// Path to the input image
+    #[arg(required_unless_present = "batch")]
+    image_path: Option<String>,
+
+    /// Path to save the annotated image (default: output.image_path from the config)
+    output_image_path: Option<String>,
+
+    /// Path to save the JSON results (default: output.json_path from the config)
+    output_json_path: Option<String>,
+
+    /// Also write one CSV row per detected face to PATH
+    #[arg(long, value_name = "PATH")]
+    csv: Option<PathBuf>,
+
+    /// Also write a standalone HTML report of the analysis into DIR
+    #[arg(long, value_name = "DIR", conflicts_with = "batch")]
+    html_report: Option<String>,
+
+    /// Analyze every image in INPUT_DIR instead of a single image
+    #[arg(long, value_name = "INPUT_DIR", conflicts_with = "image_path")]
+    batch: Option<PathBuf>,
+
+    /// Attribute model (ONNX), overriding the config
+    #[arg(long, value_name = "PATH")]
+    model: Option<String>,
+
+    /// Haar cascade XML, overriding the config
+    #[arg(long, value_name = "PATH")]
+    cascade: Option<String>,
+
+    /// Face detector: haar, dnn, mtcnn or retinaface
+    #[arg(long, value_name = "TYPE")]
+    detector: Option<DetectorType>,
+
+    /// Haar pyramid step (> 1.0); closer to 1.0 finds more faces, slower
+    #[arg(long, value_name = "FACTOR")]
+    scale_factor: Option<f32>,
+
+    /// Haar neighbors needed per face; lower finds more faces and more false positives
+    #[arg(long, value_name = "N")]
+    min_neighbors: Option<i32>,
+
+    /// Smallest face side in pixels; lower finds small faces
+    #[arg(long, value_name = "PX")]
+    min_face_size: Option<i32>,
+
+    /// Execution providers to try in order, comma-separated: tensorrt, cuda, cpu
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    providers: Vec<ProviderKind>,
+
+    /// GPU to run inference on when several are installed
+    #[arg(long, value_name = "ID")]
+    device_id: Option<i32>,
+
+    /// TOML or JSON config file (default: $FACE_ANALYZER_CONFIG, then ./face_analyzer.toml)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// off, error, warn, info (default), debug or trace; overrides RUST_LOG
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Batch failure policy
+    #[arg(long, value_enum, default_value_t = OnError::Skip)]
+    on_error: OnError,
+
+    /// Also analyze images in subdirectories of INPUT_DIR
+    #[arg(long)]
+    recursive: bool,
+
+    /// Only analyze files whose path relative to INPUT_DIR matches; prefix
+    /// with ! to exclude instead. Repeatable. Without an include pattern the
+    /// usual image extensions are used.
+    #[arg(long = "glob", value_name = "PATTERN")]
+    globs: Vec<String>,
+
+    /// How the file sink stores batch results
+    #[arg(long, value_enum, default_value_t = BatchFormat::Files)]
+    batch_format: BatchFormat,
+
+    /// Where undecodable inputs are copied (default: <batch_dir>/failed)
+    #[arg(long, value_name = "DIR")]
+    failed_dir: Option<PathBuf>,
+
+    /// Batch result destination, repeatable: file (default), stdout, redis
+    #[arg(long = "sink", value_name = "NAME")]
+    sinks: Vec<String>,
+
+    /// Redis server for the redis sink (requires the redis-sink feature)
+    #[arg(long, default_value = "redis://127.0.0.1/")]
+    redis_url: String,
+
+    /// Channel the redis sink publishes to
+    #[arg(long, default_value = "face-analysis")]
+    topic: String,
 }
 
-fn main() -> opencv::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 || args[1] == "--help" || args[1] == "-h" {
-        print_usage(&args[0]);
-        std::process::exit(0);
-    }
-
-    if args[1] == "--batch" && args.len() >= 3 {
-        let input_dir = &args[2];
-        let annotated_dir = Path::new("batch_output/annotated");
-        let json_dir = Path::new("batch_output/json");
-        let faces_dir = Path::new("batch_output/faces");
-        fs::create_dir_all(annotated_dir).ok();
-        fs::create_dir_all(json_dir).ok();
-        fs::create_dir_all(faces_dir).ok();
-        let entries = match fs::read_dir(input_dir) {
-            Ok(e) => e,
+impl Cli {
+    /// Command-line paths and detector settings win over the config file and env.
+    fn apply_to(&self, config: &mut Config) {
+        if let Some(model) = &self.model {
+            config.models.attributes = model.clone();
+        }
+        if let Some(cascade) = &self.cascade {
+            config.models.cascade = cascade.clone();
+        }
+        if let Some(detector) = self.detector {
+            config.detector = detector;
+        }
+        if let Some(scale_factor) = self.scale_factor {
+            config.detection.scale_factor = scale_factor;
+        }
+        if let Some(min_neighbors) = self.min_neighbors {
+            config.detection.min_neighbors = min_neighbors;
+        }
+        if let Some(min_face_size) = self.min_face_size {
+            config.detection.min_face_size = min_face_size;
+        }
+        if !self.providers.is_empty() {
+            config.gpu.providers = self.providers.clone();
+        }
+        if let Some(device_id) = self.device_id {
+            config.gpu.device_id = device_id;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum BatchFormat {
+    Files,   // One <stem>.json per image under <batch_dir>/json (default)
+    Ndjson,  // One line per image appended to <batch_dir>/results.ndjson
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OnError {
+    Skip,     // Log the failure and carry on (default)
+    Abort,    // Stop at the first failure
+    Summary,  // Carry on, list failures at the end and exit non-zero
+}
+
+#[derive(Debug, Default)]
+struct BatchOutcome {
+    summary: BatchSummary,
+    failed: Vec<(PathBuf, String)>,
+    aborted: bool,
+}
+
+impl BatchOutcome {
+    fn exit_code(&self, policy: OnError) -> i32 {
+        match policy {
+            OnError::Skip => 0,
+            OnError::Abort | OnError::Summary => if self.failed.is_empty() { 0 } else { 1 },
+        }
+    }
+}
+
+/// Run `process` over `files`, which returns the number of faces found.
+fn process_batch<F>(files: &[PathBuf], policy: OnError, mut progress: ProgressReporter, mut process: F) -> BatchOutcome
+where
+    F: FnMut(usize, &Path) -> Result<usize, String>,
+{
+    let mut outcome = BatchOutcome::default();
+    for (i, path) in files.iter().enumerate() {
+        progress.start_item(&path.display().to_string());
+        match process(i, path) {
+            Ok(faces) => progress.succeeded(faces),
             Err(e) => {
-                eprintln!("Failed to read input directory: {}", e);
-                std::process::exit(1);
-            }
-        };
-        let mut image_files = vec![];
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if let Some(ext) = path.extension() {
-                    let ext = ext.to_string_lossy().to_lowercase();
-                    if ext == "jpg" || ext == "jpeg" || ext == "png" || ext == "bmp" {
-                        image_files.push(path);
-                    }
+                progress.failed();
+                progress.println(format!("  Failed to process {}: {}", path.display(), e));
+                outcome.failed.push((path.clone(), e));
+                if policy == OnError::Abort {
+                    outcome.aborted = true;
+                    progress.println("Aborting batch after first failure (--on-error abort)");
+                    break;
                 }
             }
         }
-        let total = image_files.len();
-        for (i, path) in image_files.iter().enumerate() {
-            let fname = path.file_stem().unwrap().to_string_lossy();
-            let annotated_path = annotated_dir.join(format!("{}_annotated.jpg", fname));
-            let json_path = json_dir.join(format!("{}.json", fname));
-            println!("Processing {}/{}: {}", i + 1, total, path.display());
-            let (img, analysis) = match analyze_image(path.to_str().unwrap()) {
-                Ok(res) => res,
-                Err(e) => {
-                    eprintln!("  Failed to analyze {}: {}", path.display(), e);
-                    continue;
+    }
+    outcome.summary = progress.finish();
+    if policy == OnError::Summary && !outcome.failed.is_empty() {
+        eprintln!("{} of {} images failed:", outcome.failed.len(), files.len());
+        for (path, reason) in &outcome.failed {
+            eprintln!("  {}: {}", path.display(), reason);
+        }
+    }
+    outcome
+}
+
+/// Reject files that cannot be decoded. A missing end-of-image marker is
+/// only logged: many cameras pad or trim the tail of otherwise valid files,
+/// and OpenCV decodes what is there.
+fn check_image_integrity(path: &Path) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if bytes.is_empty() {
+        return Err("File is empty".to_string());
+    }
+
+    decode_image(&bytes).map_err(|e| format!("Could not decode image: {}", e))?;
+
+    let is_jpeg = bytes.starts_with(&[0xFF, 0xD8]);
+    let is_png = bytes.starts_with(b"\x89PNG\r\n\x1a\n");
+    if is_jpeg && !bytes.ends_with(&[0xFF, 0xD9]) {
+        log::warn!("{}: JPEG has no end-of-image marker and may be truncated", path.display());
+    }
+    if is_png && !bytes.windows(4).rev().take(16).any(|w| w == b"IEND") {
+        log::warn!("{}: PNG has no IEND chunk and may be truncated", path.display());
+    }
+    Ok(())
+}
+
+/// Copy a bad input into `failed_dir` under its path `relative` to the batch
+/// input directory, and append the reason to `failed_dir/errors.log`. The
+/// original is left where it was.
+fn quarantine_file(path: &Path, relative: &Path, failed_dir: &Path, reason: &str) -> std::io::Result<PathBuf> {
+    // An absolute `relative` would make `join` escape `failed_dir`
+    let relative = if relative.is_absolute() {
+        Path::new(path.file_name().unwrap_or_default())
+    } else {
+        relative
+    };
+    let target = failed_dir.join(relative);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(path, &target)?;
+
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(failed_dir.join("errors.log"))?;
+    writeln!(log, "{}\t{}", path.display(), reason)?;
+    Ok(target)
+}
+
+fn validate_or_quarantine(path: &Path, relative: &Path, failed_dir: &Path) -> Result<(), String> {
+    check_image_integrity(path).map_err(|reason| {
+        match quarantine_file(path, relative, failed_dir, &reason) {
+            Ok(target) => log::warn!("Copied {} to {}", path.display(), target.display()),
+            Err(e) => log::error!("Failed to copy {} to {}: {}", path.display(), failed_dir.display(), e),
+        }
+        reason
+    })
+}
+
+/// Which files under the batch input directory get analyzed.
+struct ImageFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl ImageFilter {
+    const DEFAULT_EXTENSIONS: [&'static str; 7] = ["jpg", "jpeg", "png", "bmp", "webp", "heic", "heif"];
+
+    fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+        let mut has_include = false;
+        for pattern in patterns {
+            let (builder, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (&mut exclude, pattern),
+                None => {
+                    has_include = true;
+                    (&mut include, pattern.as_str())
                 }
             };
-            if let Err(e) = imgcodecs::imwrite(annotated_path.to_str().unwrap(), &img, &types::VectorOfint::new()) {
-                eprintln!("  Failed to write annotated image: {}", e);
-                continue;
+            builder.add(Glob::new(pattern).map_err(|e| format!("Invalid --glob pattern: {}", e))?);
+        }
+        let build = |builder: GlobSetBuilder| builder.build().map_err(|e| format!("Invalid --glob pattern: {}", e));
+        Ok(Self {
+            include: if has_include { Some(build(include)?) } else { None },
+            exclude: build(exclude)?,
+        })
+    }
+
+    /// `relative` is the path below the input directory.
+    fn matches(&self, relative: &Path) -> bool {
+        let included = match &self.include {
+            Some(include) => include.is_match(relative),
+            None => relative
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .map_or(false, |ext| Self::DEFAULT_EXTENSIONS.contains(&ext.as_str())),
+        };
+        included && !self.exclude.is_match(relative)
+    }
+}
+
+/// Sorted list of the images to analyze. Only the top level of `input_dir`
+/// is read unless `recursive` is set.
+fn collect_images(input_dir: &Path, recursive: bool, filter: &ImageFilter) -> Result<Vec<PathBuf>, String> {
+    let max_depth = if recursive { usize::MAX } else { 1 };
+    let mut image_files = Vec::new();
+    for entry in WalkDir::new(input_dir).min_depth(1).max_depth(max_depth).sort_by_file_name() {
+        let entry = entry.map_err(|e| format!("Failed to read input directory: {}", e))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(input_dir).unwrap_or(entry.path());
+        if filter.matches(relative) {
+            image_files.push(entry.into_path());
+        }
+    }
+    Ok(image_files)
+}
+
+/// `relative` is the image's path below the batch input directory; its
+/// parent directories are recreated under each output directory.
+fn process_batch_image(
+    analyzer: &Analyzer,
+    path: &Path,
+    relative: &Path,
+    annotated_dir: &Path,
+    faces_dir: &Path,
+    sink: &mut dyn ResultSink,
+) -> Result<usize, String> {
+    let fname = path.file_stem().unwrap().to_string_lossy();
+    let subdir = relative.parent().unwrap_or(Path::new(""));
+    let (annotated_dir, faces_dir) = (annotated_dir.join(subdir), faces_dir.join(subdir));
+    for dir in [&annotated_dir, &faces_dir] {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let key = subdir.join(&*fname).to_string_lossy().replace('\\', "/");
+    let annotated_path = annotated_dir.join(format!("{}_annotated.jpg", fname));
+    let (img, analysis) = analyzer.analyze(path.to_str().unwrap())
+        .map_err(|e| format!("Failed to analyze: {}", e))?;
+    imgcodecs::imwrite(annotated_path.to_str().unwrap(), &img, &types::VectorOfint::new())
+        .map_err(|e| format!("Failed to write annotated image: {}", e))?;
+    sink.publish_image(path, &key, &analysis)
+        .map_err(|e| format!("Failed to publish result: {}", e))?;
+    let orig_img = read_image(path.to_str().unwrap()).unwrap_or_default();
+    for (face_idx, face) in analysis.faces.iter().enumerate() {
+        let rect = clamp_rect_to_image(face.bbox.rect(), orig_img.cols(), orig_img.rows());
+        if rect.area() == 0 {
+            continue;
+        }
+        if let Ok(face_roi) = Mat::roi(&orig_img, rect) {
+            let face_path = faces_dir.join(format!("{}_face{}.jpg", fname, face_idx + 1));
+            if let Err(e) = imgcodecs::imwrite(face_path.to_str().unwrap(), &face_roi, &types::VectorOfint::new()) {
+                log::warn!("Failed to write face image {}: {}", face_path.display(), e);
             }
-            let json = match serde_json::to_string_pretty(&analysis) {
-                Ok(j) => j,
-                Err(e) => {
-                    eprintln!("  Failed to serialize JSON: {}", e);
-                    continue;
+        }
+    }
+    log::debug!("Saved: {} ({} faces)", annotated_path.display(), analysis.faces.len());
+    Ok(analysis.faces.len())
+}
+
+/// Build the sinks named by `--sink` (repeatable). Without any `--sink`
+/// results go to the file sink, which writes JSON files to
+/// `<batch_dir>/json` or, with `--batch-format ndjson`, lines to
+/// `<batch_dir>/results.ndjson`. `--csv` adds a per-face CSV alongside.
+fn build_sinks(cli: &Cli, output: &OutputConfig) -> Result<Vec<Box<dyn ResultSink>>, String> {
+    let default = ["file".to_string()];
+    let names = if cli.sinks.is_empty() { &default[..] } else { &cli.sinks[..] };
+
+    let mut sinks: Vec<Box<dyn ResultSink>> = Vec::new();
+    for name in names {
+        match name.as_str() {
+            "file" => match cli.batch_format {
+                BatchFormat::Files => {
+                    let sink = FileSink::new(output.batch_subdir("json")).map_err(|e| e.to_string())?;
+                    sinks.push(Box::new(sink));
                 }
-            };
-            if let Err(e) = File::create(&json_path).and_then(|mut file| file.write_all(json.as_bytes())) {
-                eprintln!("  Failed to write JSON: {}", e);
-                continue;
-            }
-            let orig_img = imgcodecs::imread(path.to_str().unwrap(), imgcodecs::IMREAD_COLOR).unwrap_or_default();
-            for (face_idx, face) in analysis.faces.iter().enumerate() {
-                let (x, y, w, h) = face.bbox;
-                let rect = core::Rect { x, y, width: w, height: h };
-                if x >= 0 && y >= 0 && w > 0 && h > 0 && x + w <= orig_img.cols() && y + h <= orig_img.rows() {
-                    if let Ok(face_roi) = Mat::roi(&orig_img, rect) {
-                        let face_path = faces_dir.join(format!("{}_face{}.jpg", fname, face_idx + 1));
-                        if let Err(e) = imgcodecs::imwrite(face_path.to_str().unwrap(), &face_roi, &types::VectorOfint::new()) {
-                            eprintln!("  Failed to write face image: {}", e);
-                        }
-                    }
+                BatchFormat::Ndjson => {
+                    let sink = NdjsonSink::new(output.batch_subdir("results.ndjson")).map_err(|e| e.to_string())?;
+                    sinks.push(Box::new(sink));
                 }
+            },
+            "stdout" => sinks.push(Box::new(StdoutSink)),
+            #[cfg(feature = "redis-sink")]
+            "redis" => {
+                let sink = face_analyzer::output::sink::RedisSink::connect(&cli.redis_url, &cli.topic)
+                    .map_err(|e| format!("Failed to connect to Redis: {}", e))?;
+                sinks.push(Box::new(sink));
             }
-            println!("  Saved: {} and {} ({} faces)", annotated_path.display(), json_path.display(), analysis.faces.len());
+            other => return Err(format!("Unknown sink: {}", other)),
         }
-        println!("Batch processing complete. Results in batch_output/.");
-        return Ok(());
     }
+    if let Some(path) = &cli.csv {
+        sinks.push(Box::new(CsvSink::create(path).map_err(|e| format!("Failed to create CSV: {}", e))?));
+    }
+    Ok(sinks)
+}
 
-    let image_path = &args[1];
-    let output_image_path = args.get(2).map(|s| s.as_str()).unwrap_or("images/output.jpg");
-    let output_json_path = args.get(3).map(|s| s.as_str()).unwrap_or("output.json");
-
-    let model_path = "models/face_attributes.onnx";
-    let cascade_path = "haarcascades/haarcascade_frontalface_default.xml";
-    if !Path::new(model_path).exists() {
-        eprintln!("Required model file not found: {}", model_path);
+/// Single-image mode for GIFs: write the per-frame results to
+/// `output_json_path`. There is no annotated image for an animation.
+fn analyze_animation(bytes: &[u8], config: &Config, output_json_path: &str) {
+    let analyzer_config = AnalyzerConfig::from_config(config.clone()).with_pool(SessionPoolConfig::single());
+    let result = Analyzer::with_config(&analyzer_config)
+        .and_then(|analyzer| analyzer.analyze_animated(bytes));
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Failed to analyze animation: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let written = serde_json::to_string_pretty(&result)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(output_json_path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Failed to write output JSON: {}", e);
         std::process::exit(1);
     }
-    if !Path::new(cascade_path).exists() {
-        eprintln!("Required cascade file not found: {}", cascade_path);
+    if result.truncated {
+        println!("Only the first {} frames were analyzed", config.animation.max_frames);
+    }
+    println!("Analyzed {} frames. Results saved to {}", result.frames.len(), output_json_path);
+}
+
+fn main() -> opencv::Result<()> {
+    let cli = Cli::parse();
+    if let Err(e) = logging::init(cli.log_level.as_deref()) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+
+    let mut config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {:#}", e);
+            std::process::exit(2);
+        }
+    };
+    cli.apply_to(&mut config);
+
+    if let Some(input_dir) = &cli.batch {
+        let policy = cli.on_error;
+        let failed_dir = cli.failed_dir.clone().unwrap_or_else(|| config.output.batch_subdir("failed"));
+        let annotated_dir = config.output.batch_subdir("annotated");
+        let faces_dir = config.output.batch_subdir("faces");
+        fs::create_dir_all(&annotated_dir).ok();
+        fs::create_dir_all(&faces_dir).ok();
+        let mut sinks = match build_sinks(&cli, &config.output) {
+            Ok(sinks) => sinks,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        };
+        let analyzer = match Analyzer::with_config(&AnalyzerConfig::from_config(config.clone())) {
+            Ok(analyzer) => analyzer,
+            Err(e) => {
+                eprintln!("Failed to load models: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let image_files = match ImageFilter::new(&cli.globs)
+            .and_then(|filter| collect_images(input_dir, cli.recursive, &filter))
+        {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let progress = ProgressReporter::new(image_files.len());
+        let outcome = process_batch(&image_files, policy, progress, |_, path| {
+            let relative = path.strip_prefix(input_dir).unwrap_or(path);
+            validate_or_quarantine(path, relative, &failed_dir)?;
+            let face_count = process_batch_image(&analyzer, path, relative, &annotated_dir, &faces_dir, &mut sinks)?;
+            Ok(face_count)
+        });
+        // Keep stdout to the sink's JSON lines when it is in use
+        let report = format!(
+            "Batch processing complete. Results in {}/.\n{}",
+            config.output.batch_dir, outcome.summary
+        );
+        if cli.sinks.iter().any(|sink| sink == "stdout") {
+            eprintln!("{}", report);
+        } else {
+            println!("{}", report);
+        }
+        std::process::exit(outcome.exit_code(policy));
+    }
+
+    // clap guarantees an image path when --batch is absent
+    let image_path = cli.image_path.as_deref().unwrap_or_default();
+    let output_image_path = cli.output_image_path.as_deref().unwrap_or(&config.output.image_path);
+    let output_json_path = cli.output_json_path.as_deref().unwrap_or(&config.output.json_path);
+
+    let model_path = &config.models.attributes;
+    if !Path::new(model_path).exists() {
+        eprintln!("Required model file not found: {} (use --model to point at it)", model_path);
         std::process::exit(1);
     }
 
@@ -128,7 +523,14 @@ fn main() -> opencv::Result<()> {
         }
     }
 
-    let (img, analysis) = match analyze_image(image_path) {
+    if let Ok(bytes) = fs::read(image_path) {
+        if is_gif(&bytes) {
+            analyze_animation(&bytes, &config, output_json_path);
+            return Ok(());
+        }
+    }
+
+    let (img, analysis) = match analyze_image_with_config(image_path, &config) {
         Ok(res) => res,
         Err(e) => {
             eprintln!("Failed to analyze image: {}", e);
@@ -150,6 +552,183 @@ fn main() -> opencv::Result<()> {
         eprintln!("Failed to write output JSON: {}", e);
         std::process::exit(1);
     }
+    if let Some(csv_path) = &cli.csv {
+        let written = CsvSink::create(csv_path)
+            .and_then(|mut sink| sink.publish_image(Path::new(image_path), "", &analysis));
+        if let Err(e) = written {
+            eprintln!("Failed to write CSV: {}", e);
+            std::process::exit(1);
+        }
+        println!("Per-face CSV saved to {}", csv_path.display());
+    }
+    if let Some(report_dir) = &cli.html_report {
+        match ReportGenerator::new(report_dir.clone()).generate_analysis_report(output_image_path, &analysis) {
+            Ok(path) => println!("HTML report saved to {}", path),
+            Err(e) => {
+                eprintln!("Failed to write HTML report: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     println!("Analysis complete. Results saved to {} and {}", output_image_path, output_json_path);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_with_one_bad_input() -> (tempfile::TempDir, Vec<PathBuf>) {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<PathBuf> = ["a.jpg", "bad.jpg", "c.jpg"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, b"").unwrap();
+                path
+            })
+            .collect();
+        (dir, files)
+    }
+
+    fn fake_process(_: usize, path: &Path) -> Result<usize, String> {
+        if path.ends_with("bad.jpg") {
+            Err("Could not decode image".to_string())
+        } else {
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_undecodable_image_is_copied_to_failed_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let failed_dir = dir.path().join("failed");
+
+        let img = Mat::new_rows_cols_with_default(32, 32, core::CV_8UC3, core::Scalar::all(128.0)).unwrap();
+        let mut encoded = core::Vector::<u8>::new();
+        imgcodecs::imencode(".jpg", &img, &mut encoded, &core::Vector::new()).unwrap();
+        let mut bytes = encoded.to_vec();
+
+        let good = dir.path().join("good.jpg");
+        fs::write(&good, &bytes).unwrap();
+        // Decodable despite the missing end-of-image marker
+        bytes.truncate(bytes.len() - 2);
+        let unterminated = dir.path().join("unterminated.jpg");
+        fs::write(&unterminated, &bytes).unwrap();
+        // Two files with the same name in different subdirectories
+        for sub in ["a", "b"] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+            fs::write(dir.path().join(sub).join("bad.jpg"), b"not an image").unwrap();
+        }
+
+        assert!(validate_or_quarantine(&good, Path::new("good.jpg"), &failed_dir).is_ok());
+        assert!(validate_or_quarantine(&unterminated, Path::new("unterminated.jpg"), &failed_dir).is_ok());
+        for sub in ["a", "b"] {
+            let relative = Path::new(sub).join("bad.jpg");
+            let reason = validate_or_quarantine(&dir.path().join(&relative), &relative, &failed_dir).unwrap_err();
+            assert!(reason.contains("Could not decode"));
+        }
+
+        assert!(dir.path().join("a/bad.jpg").exists());
+        assert!(failed_dir.join("a/bad.jpg").exists());
+        assert!(failed_dir.join("b/bad.jpg").exists());
+        assert!(!failed_dir.join("unterminated.jpg").exists());
+        let log = fs::read_to_string(failed_dir.join("errors.log")).unwrap();
+        assert_eq!(log.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_path_flags_override_config() {
+        let cli = Cli::try_parse_from([
+            "face-analyzer",
+            "photo.jpg",
+            "--model",
+            "/opt/models/attrs.onnx",
+            "--cascade",
+            "/opt/cascades/frontal.xml",
+            "--detector",
+            "dnn",
+            "--min-neighbors",
+            "2",
+            "--min-face-size",
+            "20",
+            "--providers",
+            "tensorrt,cpu",
+            "--device-id",
+            "1",
+        ])
+        .unwrap();
+        let mut config = Config::default();
+        cli.apply_to(&mut config);
+
+        assert_eq!(cli.image_path.as_deref(), Some("photo.jpg"));
+        assert_eq!(config.models.attributes, "/opt/models/attrs.onnx");
+        assert_eq!(config.models.cascade, "/opt/cascades/frontal.xml");
+        assert_eq!(config.detector, DetectorType::DNN);
+        assert_eq!(config.detection.min_neighbors, 2);
+        assert_eq!(config.detection.min_face_size, 20);
+        assert_eq!(config.detection.scale_factor, 1.1);
+        assert_eq!(config.gpu.providers, vec![ProviderKind::TensorRT, ProviderKind::Cpu]);
+        assert_eq!(config.gpu.device_id, 1);
+
+        assert!(Cli::try_parse_from(["face-analyzer", "--detector", "yolo", "photo.jpg"]).is_err());
+        assert!(Cli::try_parse_from(["face-analyzer", "--providers", "opencl", "photo.jpg"]).is_err());
+        assert!(Cli::try_parse_from(["face-analyzer", "--on-error", "abort"]).is_err());
+    }
+
+    #[test]
+    fn test_collect_images_recursive_with_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.jpg", "notes.txt", "2021/b.JPG", "2021/raw/c.png", "2021/thumbs/d.jpg"] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"").unwrap();
+        }
+        let relative = |files: Vec<PathBuf>| -> Vec<String> {
+            files
+                .iter()
+                .map(|p| p.strip_prefix(dir.path()).unwrap().to_string_lossy().replace('\\', "/"))
+                .collect()
+        };
+
+        let default = ImageFilter::new(&[]).unwrap();
+        assert_eq!(relative(collect_images(dir.path(), false, &default).unwrap()), vec!["a.jpg"]);
+        assert_eq!(
+            relative(collect_images(dir.path(), true, &default).unwrap()),
+            vec!["2021/b.JPG", "2021/raw/c.png", "2021/thumbs/d.jpg", "a.jpg"]
+        );
+
+        let filter = ImageFilter::new(&["*.jpg".to_string(), "!**/thumbs/**".to_string()]).unwrap();
+        assert_eq!(relative(collect_images(dir.path(), true, &filter).unwrap()), vec!["a.jpg"]);
+    }
+
+    #[test]
+    fn test_skip_policy_continues_and_exits_zero() {
+        let (_dir, files) = batch_with_one_bad_input();
+        let outcome = process_batch(&files, OnError::Skip, ProgressReporter::hidden(), fake_process);
+        assert_eq!(outcome.summary.processed, 2);
+        assert_eq!(outcome.summary.faces, 2);
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!(outcome.exit_code(OnError::Skip), 0);
+    }
+
+    #[test]
+    fn test_abort_policy_stops_at_first_failure() {
+        let (_dir, files) = batch_with_one_bad_input();
+        let outcome = process_batch(&files, OnError::Abort, ProgressReporter::hidden(), fake_process);
+        assert!(outcome.aborted);
+        assert_eq!(outcome.summary.processed, 1);
+        assert_eq!(outcome.exit_code(OnError::Abort), 1);
+    }
+
+    #[test]
+    fn test_summary_policy_processes_all_and_exits_non_zero() {
+        let (_dir, files) = batch_with_one_bad_input();
+        let outcome = process_batch(&files, OnError::Summary, ProgressReporter::hidden(), fake_process);
+        assert!(!outcome.aborted);
+        assert_eq!(outcome.summary.processed, 2);
+        assert_eq!(outcome.summary.failed, 1);
+        assert_eq!(outcome.failed[0].0, files[1]);
+        assert_eq!(outcome.exit_code(OnError::Summary), 1);
+    }
+}
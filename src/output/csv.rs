@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Result};
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::analysis::{AnalysisResult, FaceResult};
+use crate::database::embeddings::{embedding_to_base64, EmbeddingFormat, FaceEmbedding};
+use crate::output::sink::ResultSink;
+
+/// Gallery column that `ReportGenerator::export_csv` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    FaceId,
+    Name,
+    Tags,
+    Timestamp,
+    Confidence,
+    SourceImage,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::FaceId,
+        Field::Name,
+        Field::Tags,
+        Field::Timestamp,
+        Field::Confidence,
+        Field::SourceImage,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::FaceId => "face_id",
+            Field::Name => "name",
+            Field::Tags => "tags",
+            Field::Timestamp => "timestamp",
+            Field::Confidence => "confidence",
+            Field::SourceImage => "source_image",
+        }
+    }
+
+    pub fn value(&self, face: &FaceEmbedding) -> String {
+        match self {
+            Field::FaceId => face.face_id.clone(),
+            Field::Name => face.metadata.name.clone().unwrap_or_default(),
+            Field::Tags => face.metadata.tags.join(","),
+            Field::Timestamp => face.metadata.timestamp.to_rfc3339(),
+            Field::Confidence => face.metadata.confidence.to_string(),
+            Field::SourceImage => face.metadata.source_image.clone(),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.name() == s.trim())
+            .ok_or_else(|| {
+                let known: Vec<&str> = Field::ALL.iter().map(Field::name).collect();
+                anyhow!("Unknown CSV column: {} (expected one of: {})", s, known.join(", "))
+            })
+    }
+}
+
+/// Shape of a gallery CSV export.
+#[derive(Debug, Clone)]
+pub struct CsvExportOptions {
+    pub columns: Vec<Field>,
+    pub delimiter: u8,
+    pub include_embeddings: bool,  // Appends an `embedding` column after `columns`
+    pub embedding_format: EmbeddingFormat,
+}
+
+impl Default for CsvExportOptions {
+    fn default() -> Self {
+        Self {
+            columns: Field::ALL.to_vec(),
+            delimiter: b',',
+            include_embeddings: false,
+            embedding_format: EmbeddingFormat::default(),
+        }
+    }
+}
+
+impl CsvExportOptions {
+    /// Parse a comma-separated column list such as `face_id,name`.
+    pub fn parse_columns(list: &str) -> Result<Vec<Field>> {
+        let columns = list
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(Field::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        if columns.is_empty() {
+            return Err(anyhow!("No CSV columns requested"));
+        }
+        Ok(columns)
+    }
+
+    /// Accepts a single ASCII character or one of `comma`, `tab`,
+    /// `semicolon` and `pipe`.
+    pub fn parse_delimiter(value: &str) -> Result<u8> {
+        match value {
+            "comma" => Ok(b','),
+            "tab" | "\\t" => Ok(b'\t'),
+            "semicolon" => Ok(b';'),
+            "pipe" => Ok(b'|'),
+            _ if value.len() == 1 && value.is_ascii() && value != "\"" => Ok(value.as_bytes()[0]),
+            _ => Err(anyhow!("Unsupported CSV delimiter: {:?}", value)),
+        }
+    }
+
+    /// `tsv` for tab-separated exports, `csv` otherwise.
+    pub fn file_extension(&self) -> &'static str {
+        if self.delimiter == b'\t' {
+            "tsv"
+        } else {
+            "csv"
+        }
+    }
+
+    pub fn header(&self) -> Vec<&'static str> {
+        let mut header: Vec<&str> = self.columns.iter().map(Field::name).collect();
+        if self.include_embeddings {
+            header.push("embedding");
+        }
+        header
+    }
+
+    pub fn record(&self, face: &FaceEmbedding) -> Vec<String> {
+        let mut record: Vec<String> = self.columns.iter().map(|field| field.value(face)).collect();
+        if self.include_embeddings {
+            record.push(match self.embedding_format {
+                EmbeddingFormat::Floats => face.embedding
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join("|"),
+                EmbeddingFormat::Base64 => embedding_to_base64(&face.embedding),
+            });
+        }
+        record
+    }
+}
+
+pub const FACE_COLUMNS: [&str; 11] = [
+    "image",
+    "face",
+    "x",
+    "y",
+    "width",
+    "height",
+    "age",
+    "gender",
+    "emotion",
+    "emotion_confidence",
+    "quality",
+];
+
+/// One CSV row per detected face, for loading CLI results into a
+/// spreadsheet. Missing attributes are left as empty cells.
+pub struct CsvSink<W: std::io::Write = File> {
+    writer: Writer<W>,
+}
+
+impl CsvSink<File> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::from_writer(File::create(path)?)
+    }
+}
+
+impl<W: std::io::Write> CsvSink<W> {
+    pub fn from_writer(inner: W) -> Result<Self> {
+        let mut writer = Writer::from_writer(inner);
+        writer.write_record(FACE_COLUMNS)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write_result(&mut self, image: &str, result: &AnalysisResult) -> Result<()> {
+        for (index, face) in result.faces.iter().enumerate() {
+            self.writer.write_record(face_row(image, index, face))?;
+        }
+        // Flush per image so the file is usable while a batch is still running
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Result<W> {
+        self.writer.into_inner().map_err(|e| anyhow::anyhow!("Failed to flush CSV: {}", e.error()))
+    }
+}
+
+fn face_row(image: &str, index: usize, face: &FaceResult) -> Vec<String> {
+    let attributes = face.attributes.as_ref();
+    let emotion = attributes.and_then(|a| a.emotion.as_ref());
+    vec![
+        image.to_string(),
+        (index + 1).to_string(),
+        face.bbox.x.to_string(),
+        face.bbox.y.to_string(),
+        face.bbox.width.to_string(),
+        face.bbox.height.to_string(),
+        attributes.map(|a| format!("{:.1}", a.age)).unwrap_or_default(),
+        attributes.map(|a| a.gender.clone()).unwrap_or_default(),
+        emotion.map(|e| format!("{:?}", e.emotion).to_lowercase()).unwrap_or_default(),
+        emotion.map(|e| format!("{:.3}", e.confidence)).unwrap_or_default(),
+        face.quality.as_ref().map(|q| format!("{:.3}", q.overall_score)).unwrap_or_default(),
+    ]
+}
+
+impl<W: std::io::Write + Send> ResultSink for CsvSink<W> {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        self.write_result(key, result)
+    }
+
+    fn publish_image(&mut self, source: &Path, _key: &str, result: &AnalysisResult) -> Result<()> {
+        self.write_result(&source.to_string_lossy(), result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::BoundingBox;
+    use crate::database::embeddings::FaceMetadata;
+    use crate::face::FaceAttributes;
+
+    #[test]
+    fn test_export_options_select_columns_and_reject_unknown() {
+        let options = CsvExportOptions {
+            columns: CsvExportOptions::parse_columns("face_id, confidence").unwrap(),
+            delimiter: CsvExportOptions::parse_delimiter("tab").unwrap(),
+            ..Default::default()
+        };
+        let face = FaceEmbedding {
+            face_id: "f1".to_string(),
+            embedding: vec![0.5, 1.0],
+            metadata: FaceMetadata {
+                name: Some("Ada".to_string()),
+                tags: vec![],
+                timestamp: chrono::Utc::now(),
+                source_image: "f1.jpg".to_string(),
+                confidence: 0.75,
+                details: Default::default(),
+            },
+        };
+
+        assert_eq!(options.header(), vec!["face_id", "confidence"]);
+        assert_eq!(options.record(&face), vec!["f1", "0.75"]);
+        assert_eq!(options.file_extension(), "tsv");
+
+        let err = CsvExportOptions::parse_columns("face_id,nmae").unwrap_err();
+        assert!(err.to_string().contains("Unknown CSV column: nmae"));
+        assert!(CsvExportOptions::parse_columns(" , ").is_err());
+        assert!(CsvExportOptions::parse_delimiter("\"").is_err());
+        assert_eq!(CsvExportOptions::parse_delimiter(";").unwrap(), b';');
+    }
+
+    #[test]
+    fn test_one_row_per_face_with_empty_missing_cells() {
+        let result = AnalysisResult {
+            faces: vec![
+                FaceResult {
+                    bbox: BoundingBox::new(10, 20, 40, 40),
+                    attributes: Some(FaceAttributes {
+                        age: 31.5,
+                        gender: "female".to_string(),
+                        emotion: None,
+                        landmarks: None,
+                        pose: None,
+                        ethnicity: None,
+                        liveness: None,
+                        mask: None,
+                        accessories: None,
+                        eye_state: None,
+                        smile_intensity: None,
+                        unreliable: Vec::new(),
+                    }),
+                    quality: None,
+                    tags: Vec::new(),
+                },
+                FaceResult {
+                    bbox: BoundingBox::new(90, 20, 30, 30),
+                    attributes: None,
+                    quality: None,
+                    tags: Vec::new(),
+                },
+            ],
+        };
+
+        let mut sink = CsvSink::from_writer(Vec::new()).unwrap();
+        sink.publish_image(Path::new("photos/group.jpg"), "group", &result).unwrap();
+        let csv = String::from_utf8(sink.into_inner().unwrap()).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], FACE_COLUMNS.join(","));
+        assert_eq!(lines[1], "photos/group.jpg,1,10,20,40,40,31.5,female,,,");
+        assert_eq!(lines[2], "photos/group.jpg,2,90,20,30,30,,,,,");
+    }
+}
@@ -0,0 +1,121 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Totals for a finished batch run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchSummary {
+    pub processed: usize,
+    pub failed: usize,
+    pub faces: usize,
+    pub elapsed: Duration,
+}
+
+impl BatchSummary {
+    /// Mean faces per successfully processed image.
+    pub fn average_faces(&self) -> f64 {
+        if self.processed == 0 {
+            0.0
+        } else {
+            self.faces as f64 / self.processed as f64
+        }
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Images processed: {}", self.processed)?;
+        writeln!(f, "Images failed:    {}", self.failed)?;
+        writeln!(f, "Total faces:      {}", self.faces)?;
+        writeln!(f, "Faces per image:  {:.2}", self.average_faces())?;
+        write!(f, "Elapsed:          {:.1}s", self.elapsed.as_secs_f64())
+    }
+}
+
+/// Overall progress bar for a batch of items, collecting the totals
+/// reported by `finish`. Messages printed through `println` are drawn above
+/// the bar instead of tearing it.
+pub struct ProgressReporter {
+    bar: ProgressBar,
+    summary: BatchSummary,
+    started: Instant,
+}
+
+impl ProgressReporter {
+    pub fn new(total: usize) -> Self {
+        let bar = ProgressBar::new(total as u64);
+        bar.set_style(ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} images ({eta} left) {wide_msg}")
+            .unwrap()
+            .progress_chars("##-"));
+        Self::with_bar(bar)
+    }
+
+    /// Track totals without drawing anything, e.g. in tests or when stdout
+    /// is piped.
+    pub fn hidden() -> Self {
+        Self::with_bar(ProgressBar::hidden())
+    }
+
+    fn with_bar(bar: ProgressBar) -> Self {
+        Self {
+            bar,
+            summary: BatchSummary::default(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Show `label` (typically the file name) as the item being worked on.
+    pub fn start_item(&self, label: &str) {
+        self.bar.set_message(label.to_string());
+    }
+
+    pub fn succeeded(&mut self, faces: usize) {
+        self.summary.processed += 1;
+        self.summary.faces += faces;
+        self.bar.inc(1);
+    }
+
+    pub fn failed(&mut self) {
+        self.summary.failed += 1;
+        self.bar.inc(1);
+    }
+
+    pub fn println(&self, message: impl AsRef<str>) {
+        if self.bar.is_hidden() {
+            eprintln!("{}", message.as_ref());
+        } else {
+            self.bar.println(message);
+        }
+    }
+
+    pub fn summary(&self) -> &BatchSummary {
+        &self.summary
+    }
+
+    pub fn finish(mut self) -> BatchSummary {
+        self.bar.finish_and_clear();
+        self.summary.elapsed = self.started.elapsed();
+        self.summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reporter_totals() {
+        let mut reporter = ProgressReporter::hidden();
+        reporter.succeeded(3);
+        reporter.failed();
+        reporter.succeeded(0);
+        reporter.succeeded(2);
+
+        let summary = reporter.finish();
+        assert_eq!((summary.processed, summary.failed, summary.faces), (3, 1, 5));
+        assert!((summary.average_faces() - 5.0 / 3.0).abs() < 1e-9);
+        assert!(summary.to_string().contains("Faces per image:  1.67"));
+        assert_eq!(BatchSummary::default().average_faces(), 0.0);
+    }
+}
@@ -1,8 +1,15 @@
-use crate::database::embeddings::{FaceEmbedding, FaceMetadata};
+use crate::analysis::{AnalysisResult, FaceResult};
+use crate::database::embeddings::FaceEmbedding;
+use crate::output::csv::CsvExportOptions;
+use crate::database::storage::read_stored_image;
+use crate::security::encryption::SecureStorage;
 use anyhow::Result;
 use askama::Template;
-use csv::Writer;
+use csv::WriterBuilder;
+use printpdf::{BuiltinFont, Image, ImageTransform, IndirectFontRef, Mm, PdfDocument, PdfLayerReference};
+use std::io::BufWriter;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
 use base64;
 use image;
@@ -12,25 +19,100 @@ use image;
 struct FaceReportTemplate<'a> {
     title: &'a str,
     faces: &'a [FaceReportEntry],
-    generated_at: chrono::DateTime<chrono::Utc>,
+    generated_at: String,
 }
 
 struct FaceReportEntry {
     face_id: String,
     name: Option<String>,
     tags: Vec<String>,
-    timestamp: chrono::DateTime<chrono::Utc>,
-    confidence: f32,
+    timestamp: String,   // Human-readable, see `format_timestamp`
+    confidence: String,  // Percentage, see `format_confidence`
     image_data: String,
 }
 
+/// Render a 0..1 detection confidence as a whole percentage, e.g. "97%".
+fn format_confidence(confidence: f32) -> String {
+    format!("{:.0}%", (confidence * 100.0).clamp(0.0, 100.0))
+}
+
+fn format_timestamp(timestamp: &chrono::DateTime<chrono::Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[derive(Template)]
+#[template(path = "analysis_report.html")]
+struct AnalysisReportTemplate<'a> {
+    title: &'a str,
+    source: &'a str,
+    image_data: &'a str,
+    faces: &'a [AnalysisReportRow],
+    generated_at: String,
+}
+
+/// One table row of the analysis report, pre-formatted for display.
+struct AnalysisReportRow {
+    index: usize,
+    bbox: String,
+    age: String,
+    gender: String,
+    emotion: String,
+    quality: String,
+    tags: Vec<String>,
+}
+
+impl AnalysisReportRow {
+    fn new(index: usize, face: &FaceResult) -> Self {
+        let attributes = face.attributes.as_ref();
+        let unknown = || "-".to_string();
+        Self {
+            index: index + 1,
+            bbox: format!("{}x{} at ({}, {})", face.bbox.width, face.bbox.height, face.bbox.x, face.bbox.y),
+            age: attributes.map(|a| format!("{:.0}", a.age)).unwrap_or_else(unknown),
+            gender: attributes.map(|a| a.gender.clone()).unwrap_or_else(unknown),
+            emotion: attributes
+                .and_then(|a| a.emotion.as_ref())
+                .map(|e| format!("{:?} ({:.0}%)", e.emotion, e.confidence * 100.0))
+                .unwrap_or_else(unknown),
+            quality: face
+                .quality
+                .as_ref()
+                .map(|q| q.get_quality_description())
+                .unwrap_or_else(unknown),
+            tags: face.tags.clone(),
+        }
+    }
+}
+
+/// Default bound on the longest side of gallery images embedded in reports.
+pub const DEFAULT_THUMBNAIL_MAX_DIM: u32 = 256;
+
 pub struct ReportGenerator {
     output_dir: String,
+    secure_storage: Option<Arc<SecureStorage>>,
+    thumbnail_max_dim: Option<u32>,  // None embeds images at full resolution
 }
 
 impl ReportGenerator {
     pub fn new(output_dir: String) -> Self {
-        Self { output_dir }
+        Self {
+            output_dir,
+            secure_storage: None,
+            thumbnail_max_dim: Some(DEFAULT_THUMBNAIL_MAX_DIM),
+        }
+    }
+
+    /// Downscale embedded gallery images so their longest side is at most
+    /// `max_dim` pixels, or embed them untouched with `None`.
+    pub fn with_thumbnail_max_dim(mut self, max_dim: Option<u32>) -> Self {
+        self.thumbnail_max_dim = max_dim;
+        self
+    }
+
+    /// Decrypt face images stored encrypted at rest, see `Database::secure_storage`.
+    pub fn with_secure_storage(mut self, secure_storage: Arc<SecureStorage>) -> Self {
+        self.secure_storage = Some(secure_storage);
+        self
     }
 
     pub async fn generate_html_report(
@@ -42,13 +124,13 @@ impl ReportGenerator {
 
         let mut report_entries = Vec::new();
         for face in faces {
-            let image_data = Self::load_image_as_base64(&face.metadata.source_image)?;
+            let image_data = self.load_image_as_base64(&face.metadata.source_image).await?;
             report_entries.push(FaceReportEntry {
                 face_id: face.face_id.clone(),
                 name: face.metadata.name.clone(),
                 tags: face.metadata.tags.clone(),
-                timestamp: face.metadata.timestamp,
-                confidence: face.metadata.confidence,
+                timestamp: format_timestamp(&face.metadata.timestamp),
+                confidence: format_confidence(face.metadata.confidence),
                 image_data,
             });
         }
@@ -56,7 +138,7 @@ impl ReportGenerator {
         let template = FaceReportTemplate {
             title,
             faces: &report_entries,
-            generated_at: chrono::Utc::now(),
+            generated_at: format_timestamp(&chrono::Utc::now()),
         };
 
         let html = template.render()?;
@@ -71,172 +153,313 @@ impl ReportGenerator {
         Ok(file_path.to_string_lossy().into_owned())
     }
 
-    pub async fn export_csv(
-        &self,
-        faces: &[FaceEmbedding],
-        include_embeddings: bool,
-    ) -> Result<String> {
+    /// Render a standalone HTML report for one CLI analysis run: the image at
+    /// `image_path` (normally the annotated output) followed by a table of
+    /// every face's attributes and quality. Synchronous, so the CLI can call
+    /// it without a runtime. Returns the path of the written report.
+    pub fn generate_analysis_report(&self, image_path: &str, result: &AnalysisResult) -> Result<String> {
+        std::fs::create_dir_all(&self.output_dir)?;
+
+        let image_data = jpeg_data_url(&image::open(image_path)?)?;
+        let rows: Vec<AnalysisReportRow> = result
+            .faces
+            .iter()
+            .enumerate()
+            .map(|(index, face)| AnalysisReportRow::new(index, face))
+            .collect();
+        let source = Path::new(image_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| image_path.to_string());
+
+        let html = AnalysisReportTemplate {
+            title: "Face Analysis Report",
+            source: &source,
+            image_data: &image_data,
+            faces: &rows,
+            generated_at: format_timestamp(&chrono::Utc::now()),
+        }
+        .render()?;
+
+        let stem = Path::new(image_path).file_stem().unwrap_or_default().to_string_lossy();
+        let file_path = Path::new(&self.output_dir).join(format!("{}_report.html", stem));
+        std::fs::write(&file_path, html)?;
+        Ok(file_path.to_string_lossy().into_owned())
+    }
+
+    /// Render the same face cards as `generate_html_report` to a PDF, one
+    /// card per A4 page under a header with the title and generation time.
+    /// Returns the path of the written file.
+    pub async fn generate_pdf_report(&self, faces: &[FaceEmbedding], title: &str) -> Result<String> {
         fs::create_dir_all(&self.output_dir).await?;
 
+        let mut cards = Vec::with_capacity(faces.len());
+        for face in faces {
+            let image = self.load_report_image(&face.metadata.source_image).await?;
+            cards.push((pdf_card_lines(face), image));
+        }
+
+        let generated_at = format_timestamp(&chrono::Utc::now());
+        let pdf = render_pdf(title, &generated_at, &cards)?;
+
         let file_name = format!(
-            "face_export_{}.csv",
+            "face_report_{}.pdf",
             chrono::Utc::now().format("%Y%m%d_%H%M%S")
         );
         let file_path = Path::new(&self.output_dir).join(&file_name);
-        
-        let mut writer = Writer::from_path(&file_path)?;
-
-        let mut headers = vec![
-            "face_id",
-            "name",
-            "tags",
-            "timestamp",
-            "confidence",
-            "source_image",
-        ];
-        if include_embeddings {
-            headers.push("embedding");
-        }
-        writer.write_record(headers)?;
+        fs::write(&file_path, pdf).await?;
+
+        Ok(file_path.to_string_lossy().into_owned())
+    }
 
+    /// Write `faces` as CSV (or TSV, depending on `options.delimiter`) and
+    /// return the path of the written file.
+    pub async fn export_csv(&self, faces: &[FaceEmbedding], options: &CsvExportOptions) -> Result<String> {
+        fs::create_dir_all(&self.output_dir).await?;
+
+        let file_name = format!(
+            "face_export_{}.{}",
+            chrono::Utc::now().format("%Y%m%d_%H%M%S"),
+            options.file_extension()
+        );
+        let file_path = Path::new(&self.output_dir).join(&file_name);
+
+        let mut writer = WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .from_path(&file_path)?;
+        writer.write_record(options.header())?;
         for face in faces {
-            let mut record = vec![
-                face.face_id.clone(),
-                face.metadata.name.clone().unwrap_or_default(),
-                face.metadata.tags.join(","),
-                face.metadata.timestamp.to_rfc3339(),
-                face.metadata.confidence.to_string(),
-                face.metadata.source_image.clone(),
-            ];
-
-            if include_embeddings {
-                record.push(
-                    face.embedding
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<_>>()
-                        .join("|"),
-                );
-            }
-
-            writer.write_record(record)?;
+            writer.write_record(options.record(face))?;
         }
 
         writer.flush()?;
         Ok(file_path.to_string_lossy().into_owned())
     }
 
-    fn load_image_as_base64(image_path: &str) -> Result<String> {
-        let img = image::open(image_path)?;
-        let mut buffer = Vec::new();
-        img.write_to(&mut buffer, image::ImageFormat::Jpeg)?;
-        Ok(format!(
-            "data:image/jpeg;base64,{}",
-            base64::encode(&buffer)
-        ))
-    }
-}
-
-const REPORT_TEMPLATE: &str = r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{{ title }}</title>
-    <style>
-        body {
-            font-family: Arial, sans-serif;
-            line-height: 1.6;
-            margin: 0;
-            padding: 20px;
-            background-color: #f5f5f5;
-        }
-        .container {
-            max-width: 1200px;
-            margin: 0 auto;
-            background-color: white;
-            padding: 20px;
-            border-radius: 8px;
-            box-shadow: 0 2px 4px rgba(0,0,0,0.1);
-        }
-        h1 {
-            color: #333;
-            margin-bottom: 20px;
-        }
-        .face-grid {
-            display: grid;
-            grid-template-columns: repeat(auto-fill, minmax(250px, 1fr));
-            gap: 20px;
-            margin-top: 20px;
-        }
-        .face-card {
-            border: 1px solid #ddd;
-            border-radius: 8px;
-            padding: 15px;
-            background-color: white;
-        }
-        .face-image {
-            width: 100%;
-            height: 200px;
-            object-fit: cover;
-            border-radius: 4px;
-            margin-bottom: 10px;
-        }
-        .face-info {
-            font-size: 14px;
-        }
-        .tag {
-            display: inline-block;
-            background-color: #e9ecef;
-            padding: 2px 8px;
-            border-radius: 12px;
-            margin: 2px;
-            font-size: 12px;
-        }
-        .confidence {
-            color: #28a745;
-            font-weight: bold;
-        }
-        .timestamp {
-            color: #666;
-            font-size: 12px;
-        }
-        .footer {
-            margin-top: 20px;
-            text-align: center;
-            color: #666;
-            font-size: 12px;
-        }
-    </style>
-</head>
-<body>
-    <div class="container">
-        <h1>{{ title }}</h1>
-        <div class="face-grid">
-            {% for face in faces %}
-            <div class="face-card">
-                <img src="{{ face.image_data }}" alt="Face {{ face.face_id }}" class="face-image">
-                <div class="face-info">
-                    <div>ID: {{ face.face_id }}</div>
-                    {% if face.name %}
-                    <div>Name: {{ face.name }}</div>
-                    {% endif %}
-                    <div>
-                        {% for tag in face.tags %}
-                        <span class="tag">{{ tag }}</span>
-                        {% endfor %}
-                    </div>
-                    <div class="confidence">Confidence: {{ face.confidence }}%</div>
-                    <div class="timestamp">{{ face.timestamp }}</div>
-                </div>
-            </div>
-            {% endfor %}
-        </div>
-        <div class="footer">
-            Generated at {{ generated_at }}
-        </div>
-    </div>
-</body>
-</html>
-"#; 
\ No newline at end of file
+    async fn load_image_as_base64(&self, image_path: &str) -> Result<String> {
+        jpeg_data_url(&self.load_report_image(image_path).await?)
+    }
+
+    /// Decrypt and decode a gallery image, downscaled per `thumbnail_max_dim`.
+    async fn load_report_image(&self, image_path: &str) -> Result<image::DynamicImage> {
+        let data = read_stored_image(self.secure_storage.as_deref(), image_path).await?;
+        let img = image::load_from_memory(&data)?;
+        Ok(match self.thumbnail_max_dim {
+            Some(max_dim) => downscale(img, max_dim),
+            None => img,
+        })
+    }
+}
+
+const PAGE_WIDTH: Mm = Mm(210.0);
+const PAGE_HEIGHT: Mm = Mm(297.0);
+const MARGIN_MM: f64 = 20.0;
+const CARD_IMAGE_MM: f64 = 80.0;  // Longest side of the face image on the page
+
+fn pdf_card_lines(face: &FaceEmbedding) -> Vec<String> {
+    let mut lines = vec![format!("ID: {}", face.face_id)];
+    if let Some(name) = &face.metadata.name {
+        lines.push(format!("Name: {}", name));
+    }
+    if !face.metadata.tags.is_empty() {
+        lines.push(format!("Tags: {}", face.metadata.tags.join(", ")));
+    }
+    lines.push(format!("Confidence: {}", format_confidence(face.metadata.confidence)));
+    lines.push(format!("Captured: {}", format_timestamp(&face.metadata.timestamp)));
+    lines
+}
+
+/// Lay out one card per page. Pages without cards still get the header, so
+/// an empty gallery yields a one-page PDF saying so.
+fn render_pdf(title: &str, generated_at: &str, cards: &[(Vec<String>, image::DynamicImage)]) -> Result<Vec<u8>> {
+    let (doc, first_page, first_layer) = PdfDocument::new(title, PAGE_WIDTH, PAGE_HEIGHT, "Layer 1");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let header = |layer: &PdfLayerReference| {
+        let top = PAGE_HEIGHT.0 - MARGIN_MM;
+        layer.use_text(title, 18.0, Mm(MARGIN_MM), Mm(top), &bold);
+        layer.use_text(format!("Generated at {}", generated_at), 10.0, Mm(MARGIN_MM), Mm(top - 8.0), &font);
+        top - 20.0
+    };
+
+    if cards.is_empty() {
+        let layer = doc.get_page(first_page).get_layer(first_layer);
+        let y = header(&layer);
+        layer.use_text("No faces in the gallery.", 12.0, Mm(MARGIN_MM), Mm(y), &font);
+    }
+
+    for (index, (lines, img)) in cards.iter().enumerate() {
+        let layer = if index == 0 {
+            doc.get_page(first_page).get_layer(first_layer)
+        } else {
+            let (page, layer) = doc.add_page(PAGE_WIDTH, PAGE_HEIGHT, format!("Layer {}", index + 1));
+            doc.get_page(page).get_layer(layer)
+        };
+        let top = header(&layer);
+        let image_bottom = add_card_image(&layer, img, top);
+        draw_lines(&layer, lines, &font, image_bottom - 10.0);
+    }
+
+    let mut buffer = BufWriter::new(Vec::new());
+    doc.save(&mut buffer)?;
+    Ok(buffer.into_inner()?)
+}
+
+/// Place `img` with its top edge at `top` mm, scaled so its longest side is
+/// `CARD_IMAGE_MM`. Returns the y coordinate of its bottom edge.
+fn add_card_image(layer: &PdfLayerReference, img: &image::DynamicImage, top: f64) -> f64 {
+    let (width, height) = (img.width().max(1) as f64, img.height().max(1) as f64);
+    // printpdf sizes images by DPI: pick the one that maps the longest side to CARD_IMAGE_MM
+    let dpi = width.max(height) * 25.4 / CARD_IMAGE_MM;
+    let height_mm = height * 25.4 / dpi;
+
+    // printpdf cannot embed alpha channels
+    let rgb = image::DynamicImage::ImageRgb8(img.to_rgb8());
+    Image::from_dynamic_image(&rgb).add_to_layer(
+        layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm(MARGIN_MM)),
+            translate_y: Some(Mm(top - height_mm)),
+            dpi: Some(dpi),
+            ..Default::default()
+        },
+    );
+    top - height_mm
+}
+
+fn draw_lines(layer: &PdfLayerReference, lines: &[String], font: &IndirectFontRef, top: f64) {
+    for (i, line) in lines.iter().enumerate() {
+        layer.use_text(line.as_str(), 12.0, Mm(MARGIN_MM), Mm(top - i as f64 * 7.0), font);
+    }
+}
+
+/// Shrink `img` to fit in a `max_dim` square, keeping the aspect ratio.
+/// Images that already fit are returned unchanged.
+fn downscale(img: image::DynamicImage, max_dim: u32) -> image::DynamicImage {
+    if img.width().max(img.height()) <= max_dim {
+        return img;
+    }
+    img.resize(max_dim, max_dim, image::imageops::FilterType::Triangle)
+}
+
+fn jpeg_data_url(img: &image::DynamicImage) -> Result<String> {
+    let mut buffer = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut buffer), image::ImageFormat::Jpeg)?;
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        base64::encode(&buffer)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_report_images_are_decrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Arc::new(
+            SecureStorage::new("report_password", dir.path().to_string_lossy().into_owned()).unwrap(),
+        );
+
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(8, 8)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        storage.store("face-1", &jpeg).await.unwrap();
+
+        let generator = ReportGenerator::new(dir.path().to_string_lossy().into_owned())
+            .with_secure_storage(storage);
+        let path = dir.path().join("face-1.enc");
+        let data_url = generator.load_image_as_base64(path.to_str().unwrap()).await.unwrap();
+        assert!(data_url.starts_with("data:image/jpeg;base64,"));
+
+        let plain = ReportGenerator::new(dir.path().to_string_lossy().into_owned());
+        assert!(plain.load_image_as_base64(path.to_str().unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pdf_report_is_written() {
+        use crate::database::embeddings::FaceMetadata;
+
+        let dir = tempfile::tempdir().unwrap();
+        let faces: Vec<FaceEmbedding> = ["alice", "bob"]
+            .iter()
+            .map(|id| {
+                let source_image = dir.path().join(format!("{}.png", id));
+                image::DynamicImage::new_rgba8(40, 60).save(&source_image).unwrap();
+                FaceEmbedding {
+                    face_id: id.to_string(),
+                    embedding: vec![0.0; 4],
+                    metadata: FaceMetadata {
+                        name: Some(id.to_string()),
+                        tags: vec!["staff".to_string()],
+                        timestamp: chrono::Utc::now(),
+                        source_image: source_image.to_string_lossy().into_owned(),
+                        confidence: 0.9,
+                        details: Default::default(),
+                    },
+                }
+            })
+            .collect();
+
+        let generator = ReportGenerator::new(dir.path().join("reports").to_string_lossy().into_owned());
+        let path = generator.generate_pdf_report(&faces, "Gallery").await.unwrap();
+
+        assert!(path.ends_with(".pdf"));
+        assert!(std::fs::read(&path).unwrap().starts_with(b"%PDF"));
+        assert!(render_pdf("Empty", "now", &[]).unwrap().starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_downscale_keeps_aspect_ratio() {
+        let large = downscale(image::DynamicImage::new_rgb8(1024, 512), 256);
+        assert_eq!((large.width(), large.height()), (256, 128));
+
+        let small = downscale(image::DynamicImage::new_rgb8(100, 40), 256);
+        assert_eq!((small.width(), small.height()), (100, 40));
+    }
+
+    #[test]
+    fn test_confidence_and_timestamp_formatting() {
+        assert_eq!(format_confidence(0.97), "97%");
+        assert_eq!(format_confidence(0.5049), "50%");
+        assert_eq!(format_confidence(1.0), "100%");
+
+        let timestamp = chrono::DateTime::parse_from_rfc3339("2024-03-05T14:07:09.123Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(format_timestamp(&timestamp), "2024-03-05 14:07:09 UTC");
+    }
+
+    #[test]
+    fn test_analysis_report_lists_every_face() {
+        use crate::common::types::BoundingBox;
+
+        let dir = tempfile::tempdir().unwrap();
+        let image_path = dir.path().join("group_annotated.jpg");
+        image::DynamicImage::new_rgb8(64, 32).save(&image_path).unwrap();
+        let result = AnalysisResult {
+            faces: (0..2)
+                .map(|i| FaceResult {
+                    bbox: BoundingBox::new(i * 32, 0, 24, 24),
+                    attributes: None,
+                    quality: None,
+                    tags: vec![format!("tag-{}", i)],
+                })
+                .collect(),
+        };
+
+        let generator = ReportGenerator::new(dir.path().join("reports").to_string_lossy().into_owned());
+        let report_path = generator
+            .generate_analysis_report(image_path.to_str().unwrap(), &result)
+            .unwrap();
+
+        assert!(report_path.ends_with("group_annotated_report.html"));
+        let html = std::fs::read_to_string(report_path).unwrap();
+        assert!(html.contains("data:image/jpeg;base64,"));
+        assert!(html.contains("24x24 at (32, 0)"));
+        assert!(html.contains("tag-1"));
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::analysis::AnalysisResult;
+
+/// Destination for analysis results. `key` identifies the input, e.g. the
+/// source image's file stem.
+pub trait ResultSink: Send {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()>;
+
+    /// Publish the result for the image at `source`. Sinks that only need a
+    /// key get `key`.
+    fn publish_image(&mut self, source: &Path, key: &str, result: &AnalysisResult) -> Result<()> {
+        self.publish(key, result)
+    }
+}
+
+/// Envelope used by sinks that mix results from many inputs in one stream.
+#[derive(Serialize)]
+struct SinkRecord<'a> {
+    source: &'a str,
+    result: &'a AnalysisResult,
+}
+
+/// Writes each result to `<dir>/<key>.json`. Keys may contain `/` to
+/// mirror the input directory layout.
+pub struct FileSink {
+    dir: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl ResultSink for FileSink {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        let json = serde_json::to_string_pretty(result)?;
+        let path = self.dir.join(format!("{}.json", key));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Line written by `NdjsonSink`: the source image next to its faces.
+#[derive(Serialize)]
+struct NdjsonRecord<'a> {
+    image: &'a str,
+    #[serde(flatten)]
+    result: &'a AnalysisResult,
+}
+
+/// Appends one `{"image": ..., "faces": [...]}` line per result to a single
+/// newline-delimited JSON file.
+pub struct NdjsonSink {
+    writer: BufWriter<File>,
+}
+
+impl NdjsonSink {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+
+    fn write_record(&mut self, image: &str, result: &AnalysisResult) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &NdjsonRecord { image, result })?;
+        self.writer.write_all(b"\n")?;
+        // Flush per line so an interrupted batch leaves only whole records
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl ResultSink for NdjsonSink {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        self.write_record(key, result)
+    }
+
+    fn publish_image(&mut self, source: &Path, _key: &str, result: &AnalysisResult) -> Result<()> {
+        self.write_record(&source.to_string_lossy(), result)
+    }
+}
+
+/// Writes one JSON object per line to stdout.
+pub struct StdoutSink;
+
+impl ResultSink for StdoutSink {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        let json = serde_json::to_string(&SinkRecord { source: key, result })?;
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", json)?;
+        Ok(())
+    }
+}
+
+/// Publishes each result as JSON to a Redis pub/sub channel.
+#[cfg(feature = "redis-sink")]
+pub struct RedisSink {
+    connection: redis::Connection,
+    topic: String,
+}
+
+#[cfg(feature = "redis-sink")]
+impl RedisSink {
+    pub fn connect(url: &str, topic: impl Into<String>) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            connection: client.get_connection()?,
+            topic: topic.into(),
+        })
+    }
+}
+
+#[cfg(feature = "redis-sink")]
+impl ResultSink for RedisSink {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        let json = serde_json::to_string(&SinkRecord { source: key, result })?;
+        redis::cmd("PUBLISH")
+            .arg(&self.topic)
+            .arg(json)
+            .query::<i64>(&mut self.connection)?;
+        Ok(())
+    }
+}
+
+/// Fan a result out to several sinks, e.g. files plus a message queue.
+impl ResultSink for Vec<Box<dyn ResultSink>> {
+    fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+        for sink in self.iter_mut() {
+            sink.publish(key, result)?;
+        }
+        Ok(())
+    }
+
+    fn publish_image(&mut self, source: &Path, key: &str, result: &AnalysisResult) -> Result<()> {
+        for sink in self.iter_mut() {
+            sink.publish_image(source, key, result)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::FaceResult;
+    use crate::common::types::BoundingBox;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        published: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl ResultSink for MemorySink {
+        fn publish(&mut self, key: &str, result: &AnalysisResult) -> Result<()> {
+            let json = serde_json::to_string(result)?;
+            self.published.lock().unwrap().push((key.to_string(), json));
+            Ok(())
+        }
+    }
+
+    fn result_with_faces(count: usize) -> AnalysisResult {
+        AnalysisResult {
+            faces: (0..count)
+                .map(|i| FaceResult {
+                    bbox: BoundingBox::new(i as i32 * 50, 0, 40, 40),
+                    attributes: None,
+                    quality: None,
+                    tags: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_every_result_is_published() {
+        let memory = MemorySink::default();
+        let dir = tempfile::tempdir().unwrap();
+        let mut sinks: Vec<Box<dyn ResultSink>> = vec![
+            Box::new(memory.clone()),
+            Box::new(FileSink::new(dir.path()).unwrap()),
+        ];
+
+        for (key, faces) in [("a", 1), ("b", 0), ("c", 3)] {
+            sinks.publish(key, &result_with_faces(faces)).unwrap();
+        }
+
+        let published = memory.published.lock().unwrap();
+        let keys: Vec<&str> = published.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert!(published[2].1.contains("\"bbox\":{\"x\":100,\"y\":0,\"width\":40,\"height\":40}"));
+        assert!(dir.path().join("c.json").exists());
+    }
+
+    #[test]
+    fn test_ndjson_sink_appends_one_line_per_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.ndjson");
+
+        let mut sink = NdjsonSink::new(&path).unwrap();
+        sink.publish_image(Path::new("photos/a.jpg"), "a", &result_with_faces(2)).unwrap();
+        drop(sink);
+        // Reopening appends rather than truncating
+        let mut sink = NdjsonSink::new(&path).unwrap();
+        sink.publish_image(Path::new("photos/b.jpg"), "b", &result_with_faces(0)).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["image"], "photos/a.jpg");
+        assert_eq!(lines[0]["faces"].as_array().unwrap().len(), 2);
+        assert_eq!(lines[1]["image"], "photos/b.jpg");
+    }
+}
@@ -0,0 +1,367 @@
+use anyhow::Result;
+use ndarray::Array4;
+use ort::{Environment, ExecutionProvider, Session, SessionBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::common::config::InputSize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    TensorRT,
+    Cuda,
+    Cpu,
+}
+
+impl ProviderKind {
+    fn execution_provider(&self, device_id: i32) -> ExecutionProvider {
+        match self {
+            ProviderKind::TensorRT => ExecutionProvider::tensorrt().with_device_id(device_id),
+            ProviderKind::Cuda => ExecutionProvider::cuda().with_device_id(device_id),
+            ProviderKind::Cpu => ExecutionProvider::cpu(),
+        }
+    }
+}
+
+impl std::str::FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tensorrt" => Ok(ProviderKind::TensorRT),
+            "cuda" => Ok(ProviderKind::Cuda),
+            "cpu" => Ok(ProviderKind::Cpu),
+            _ => Err(anyhow::anyhow!("Unknown execution provider: {} (expected tensorrt, cuda or cpu)", s)),
+        }
+    }
+}
+
+/// Which execution providers to try when building inference sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuConfig {
+    pub providers: Vec<ProviderKind>,  // Tried in order; CPU is always the final fallback
+    pub device_id: i32,                // GPU to run on when several are installed
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            providers: default_providers(),
+            device_id: 0,
+        }
+    }
+}
+
+impl GpuConfig {
+    pub fn cpu_only() -> Self {
+        Self {
+            providers: vec![ProviderKind::Cpu],
+            device_id: 0,
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderKind::TensorRT => write!(f, "TensorRT"),
+            ProviderKind::Cuda => write!(f, "CUDA"),
+            ProviderKind::Cpu => write!(f, "CPU"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadFailure {
+    Opset,      // Model needs a newer opset than this runtime supports
+    Provider,   // Execution provider missing or failed to initialize
+    Other,
+}
+
+impl LoadFailure {
+    pub fn classify(error: &anyhow::Error) -> Self {
+        let message = error.to_string().to_lowercase();
+        if message.contains("opset") {
+            LoadFailure::Opset
+        } else if message.contains("provider")
+            || message.contains("cuda")
+            || message.contains("tensorrt")
+        {
+            LoadFailure::Provider
+        } else {
+            LoadFailure::Other
+        }
+    }
+}
+
+/// Providers tried in order when no explicit list is given. CPU is always
+/// appended as the final fallback by `load_with_fallback`.
+pub fn default_providers() -> Vec<ProviderKind> {
+    vec![ProviderKind::Cuda, ProviderKind::Cpu]
+}
+
+/// Try each provider in turn, logging why a provider was skipped, and return
+/// the first value that builds along with the provider that produced it.
+pub fn load_with_fallback<T, F>(
+    providers: &[ProviderKind],
+    mut build: F,
+) -> Result<(T, ProviderKind)>
+where
+    F: FnMut(ProviderKind) -> Result<T>,
+{
+    let mut attempts = providers.to_vec();
+    if !attempts.contains(&ProviderKind::Cpu) {
+        attempts.push(ProviderKind::Cpu);
+    }
+
+    let mut last_error = None;
+    for provider in attempts {
+        match build(provider) {
+            Ok(value) => return Ok((value, provider)),
+            Err(e) => {
+                match LoadFailure::classify(&e) {
+                    LoadFailure::Opset => {
+                        // A different provider will not fix an opset mismatch
+                        return Err(anyhow::anyhow!(
+                            "Model requires an ONNX opset not supported by this runtime; \
+                             re-export the model with a lower opset or upgrade ONNX Runtime: {}",
+                            e
+                        ));
+                    }
+                    LoadFailure::Provider => {
+                        log::warn!("{} execution provider unavailable, falling back: {}", provider, e);
+                    }
+                    LoadFailure::Other => {
+                        log::error!("Failed to build session with {} provider: {}", provider, e);
+                    }
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Failed to load model with any execution provider: {}",
+        last_error.map(|e| e.to_string()).unwrap_or_default()
+    ))
+}
+
+pub fn build_session(environment: &Arc<Environment>, model_path: &str) -> Result<Session> {
+    build_session_with(environment, model_path, &GpuConfig::default())
+}
+
+/// Build a session on the first provider in `gpu` that is available and
+/// loads the model, warning when it has to fall back to the CPU.
+pub fn build_session_with(
+    environment: &Arc<Environment>,
+    model_path: &str,
+    gpu: &GpuConfig,
+) -> Result<Session> {
+    if !Path::new(model_path).exists() {
+        return Err(anyhow::anyhow!("Model file not found: {}", model_path));
+    }
+
+    let (session, provider) = load_with_fallback(&gpu.providers, |provider| {
+        let execution_provider = provider.execution_provider(gpu.device_id);
+        if !execution_provider.is_available() {
+            return Err(anyhow::anyhow!("{} execution provider is not available in this build", provider));
+        }
+        let session = SessionBuilder::new(environment)?
+            .with_execution_providers([execution_provider])?
+            .with_model_from_file(model_path)?;
+        Ok(session)
+    })?;
+
+    if provider == ProviderKind::Cpu && gpu.providers.iter().any(|p| *p != ProviderKind::Cpu) {
+        log::warn!("No GPU provider available, running {} on CPU", model_path);
+    }
+
+    Ok(session)
+}
+
+/// Run one inference on an all-zero `1x3xHxW` input. ONNX Runtime allocates
+/// buffers and picks kernels on the first `run`, so doing this at startup
+/// keeps that cost off the first real request.
+pub fn warm_up_session(session: &Session, size: InputSize) -> Result<()> {
+    let input = Array4::<f32>::zeros((1, 3, size.height.max(1) as usize, size.width.max(1) as usize));
+    session.run(vec![ort::Tensor::from_array(input)])?;
+    Ok(())
+}
+
+/// A CPU session for a model, built the first time it is needed. Lets GPU
+/// inference that runs out of memory finish on the CPU without loading a
+/// second copy of the model up front.
+pub struct CpuFallback {
+    environment: Arc<Environment>,
+    model_path: String,
+    session: OnceLock<std::result::Result<Session, String>>,  // Build error kept so it is not retried per face
+}
+
+impl CpuFallback {
+    pub fn new(environment: Arc<Environment>, model_path: impl Into<String>) -> Self {
+        Self {
+            environment,
+            model_path: model_path.into(),
+            session: OnceLock::new(),
+        }
+    }
+
+    /// Run `f` on the CPU session, building it on the first call.
+    pub fn with_session<R>(&self, f: impl FnOnce(&Session) -> R) -> Result<R> {
+        let session = self.session.get_or_init(|| {
+            log::warn!("Loading {} on CPU after a GPU out-of-memory error", self.model_path);
+            build_session_with(&self.environment, &self.model_path, &GpuConfig::cpu_only()).map_err(|e| e.to_string())
+        });
+        match session {
+            Ok(session) => Ok(f(session)),
+            Err(e) => Err(anyhow::anyhow!("CPU fallback session unavailable: {}", e)),
+        }
+    }
+}
+
+pub fn is_out_of_memory(error: &dyn fmt::Display) -> bool {
+    let message = error.to_string().to_lowercase();
+    message.contains("out of memory")
+        || message.contains("cudaerrormemoryallocation")
+        || message.contains("failed to allocate memory")
+}
+
+/// Run `inputs` through `run_batch` in chunks of `batch_size`. When a chunk
+/// fails with a GPU out-of-memory error the chunk size is halved (and kept
+/// smaller for the rest of the run); once a single item still runs out of
+/// memory it is processed with `run_cpu` instead.
+pub fn run_with_oom_fallback<I, O, G, C>(
+    inputs: &[I],
+    batch_size: usize,
+    mut run_batch: G,
+    mut run_cpu: C,
+) -> Result<Vec<O>>
+where
+    G: FnMut(&[I]) -> Result<Vec<O>>,
+    C: FnMut(&I) -> Result<O>,
+{
+    let mut outputs = Vec::with_capacity(inputs.len());
+    let mut size = batch_size.max(1);
+    let mut index = 0;
+
+    while index < inputs.len() {
+        let end = (index + size).min(inputs.len());
+        match run_batch(&inputs[index..end]) {
+            Ok(batch_outputs) => {
+                outputs.extend(batch_outputs);
+                index = end;
+            }
+            Err(e) if is_out_of_memory(&e) && size > 1 => {
+                size /= 2;
+                log::warn!("GPU out of memory, reducing batch size to {}", size);
+            }
+            Err(e) if is_out_of_memory(&e) => {
+                log::warn!("GPU out of memory at batch size 1, running item {} on CPU", index);
+                outputs.push(run_cpu(&inputs[index])?);
+                index += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unavailable_provider_falls_back_to_cpu() {
+        let mut tried = Vec::new();
+        let (value, provider) = load_with_fallback(&[ProviderKind::Cuda], |provider| {
+            tried.push(provider);
+            match provider {
+                ProviderKind::Cpu => Ok("session"),
+                _ => Err(anyhow::anyhow!("CUDA execution provider is not available")),
+            }
+        })
+        .unwrap();
+
+        assert_eq!(value, "session");
+        assert_eq!(provider, ProviderKind::Cpu);
+        assert_eq!(tried, vec![ProviderKind::Cuda, ProviderKind::Cpu]);
+    }
+
+    #[test]
+    fn test_gpu_config_deserializes_provider_names() {
+        let config: GpuConfig =
+            serde_json::from_value(serde_json::json!({ "providers": ["tensorrt", "cuda"] })).unwrap();
+        assert_eq!(config.providers, vec![ProviderKind::TensorRT, ProviderKind::Cuda]);
+        assert_eq!(config.device_id, 0);
+    }
+
+    #[test]
+    fn test_opset_failure_is_reported() {
+        let result: Result<((), ProviderKind)> = load_with_fallback(&[ProviderKind::Cpu], |_| {
+            Err(anyhow::anyhow!("Unsupported model IR version, opset 19 required"))
+        });
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("opset"));
+    }
+
+    #[test]
+    fn test_oom_halves_batch_until_it_fits() {
+        let inputs: Vec<u32> = (0..10).collect();
+        let mut attempted = Vec::new();
+
+        let outputs = run_with_oom_fallback(
+            &inputs,
+            8,
+            |batch: &[u32]| {
+                attempted.push(batch.len());
+                if batch.len() > 2 {
+                    Err(anyhow::anyhow!("CUDA failure 2: out of memory"))
+                } else {
+                    Ok(batch.iter().map(|x| x * 2).collect())
+                }
+            },
+            |_| panic!("CPU fallback should not be needed"),
+        )
+        .unwrap();
+
+        assert_eq!(outputs, inputs.iter().map(|x| x * 2).collect::<Vec<_>>());
+        assert_eq!(&attempted[..3], &[8, 4, 2]);
+    }
+
+    #[test]
+    fn test_oom_at_single_item_falls_back_to_cpu() {
+        let inputs = vec![1u32, 2, 3];
+        let mut cpu_items = Vec::new();
+
+        let outputs = run_with_oom_fallback(
+            &inputs,
+            4,
+            |_: &[u32]| -> Result<Vec<u32>> { Err(anyhow::anyhow!("out of memory")) },
+            |item| {
+                cpu_items.push(*item);
+                Ok(*item)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(outputs, inputs);
+        assert_eq!(cpu_items, inputs);
+    }
+
+    #[test]
+    fn test_non_oom_errors_are_returned() {
+        let result = run_with_oom_fallback(
+            &[1u32],
+            1,
+            |_: &[u32]| -> Result<Vec<u32>> { Err(anyhow::anyhow!("invalid input shape")) },
+            |item| Ok(*item),
+        );
+        assert!(result.is_err());
+    }
+}
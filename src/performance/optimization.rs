@@ -4,10 +4,13 @@ use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 
+use crate::api::websocket::{self, SharedWsManager};
+
 pub struct BatchProcessor {
     batch_size: usize,
     num_threads: usize,
     use_gpu: bool,
+    progress: Option<(String, SharedWsManager)>,  // Job id and websocket clients to notify
 }
 
 impl BatchProcessor {
@@ -16,14 +19,24 @@ impl BatchProcessor {
             batch_size,
             num_threads,
             use_gpu,
+            progress: None,
         }
     }
 
+    /// Broadcast a `Progress` message for `job_id` as each batch completes.
+    pub fn with_progress(mut self, job_id: impl Into<String>, ws_manager: SharedWsManager) -> Self {
+        self.progress = Some((job_id.into(), ws_manager));
+        self
+    }
+
+    /// Run `processor` over every image. The returned results line up with
+    /// `images`, so a failed image keeps its position and its error; only a
+    /// batch that could not run at all fails the whole call.
     pub async fn process_images<F, T>(
         &self,
         images: Vec<Mat>,
         processor: F,
-    ) -> Result<Vec<T>>
+    ) -> Result<Vec<Result<T>>>
     where
         F: Fn(&Mat) -> Result<T> + Send + Sync + 'static,
         T: Send + 'static,
@@ -38,7 +51,8 @@ impl BatchProcessor {
             .collect();
 
         let processor = Arc::new(processor);
-        let results = Arc::new(Mutex::new(vec![None; total_images]));
+        let results: Vec<Option<Result<T>>> = (0..total_images).map(|_| None).collect();
+        let results = Arc::new(Mutex::new(results));
 
         for (batch_idx, batch) in batches.into_iter().enumerate() {
             let tx = tx.clone();
@@ -59,25 +73,35 @@ impl BatchProcessor {
 
                 let mut results = results.lock().unwrap();
                 for (idx, result) in batch_results {
-                    if let Ok(result) = result {
-                        results[idx] = Some(result);
-                    }
+                    results[idx] = Some(result);
                 }
 
                 tx.blocking_send(batch_idx).unwrap();
             });
         }
+        // Only the batch tasks hold senders now, so a panicked batch ends
+        // the loop below instead of hanging it
+        drop(tx);
 
+        let mut done = 0;
         for _ in 0..num_batches {
-            rx.recv().await.ok_or_else(|| anyhow::anyhow!("Batch processing failed"))?;
+            let batch_idx = rx.recv().await.ok_or_else(|| anyhow::anyhow!("Batch processing failed"))?;
+            done += self.batch_size.min(total_images - batch_idx * self.batch_size);
+
+            if let Some((job_id, ws_manager)) = &self.progress {
+                websocket::notify_progress(ws_manager, job_id.clone(), done as u64, total_images as u64).await;
+            }
         }
 
         let results = Arc::try_unwrap(results)
-            .unwrap()
+            .map_err(|_| anyhow::anyhow!("Batch results are still in use"))?
             .into_inner()
-            .unwrap()
+            .map_err(|_| anyhow::anyhow!("Batch results lock is poisoned"))?
             .into_iter()
-            .filter_map(|r| r)
+            .enumerate()
+            .map(|(idx, result)| {
+                result.unwrap_or_else(|| Err(anyhow::anyhow!("Image {} was not processed", idx)))
+            })
             .collect();
 
         Ok(results)
@@ -99,10 +123,30 @@ impl BatchProcessor {
     }
 }
 
+/// Dynamic INT8 quantization with onnxruntime's quantization tooling.
+const QUANTIZE_SCRIPT: &str = r#"
+import sys
+from onnxruntime.quantization import QuantType, quantize_dynamic
+quantize_dynamic(sys.argv[1], sys.argv[2], weight_type=QuantType.QInt8)
+"#;
+
+/// FP16 conversion of weights and activations, keeping float32 graph I/O.
+const FP16_SCRIPT: &str = r#"
+import sys
+import onnx
+from onnxconverter_common import float16
+model = float16.convert_float_to_float16(onnx.load(sys.argv[1]), keep_io_types=True)
+onnx.save(model, sys.argv[2])
+"#;
+
+/// Offline model conversions. These run through onnxruntime's Python
+/// tooling (`onnxruntime` and, for FP16, `onnx` plus `onnxconverter-common`),
+/// which must be installed for the configured interpreter.
 pub struct ModelOptimizer {
     quantize: bool,
     use_tensorrt: bool,
     use_fp16: bool,
+    python: String,
 }
 
 impl ModelOptimizer {
@@ -111,6 +155,7 @@ impl ModelOptimizer {
             quantize: false,
             use_tensorrt: false,
             use_fp16: false,
+            python: "python3".to_string(),
         }
     }
 
@@ -126,56 +171,114 @@ impl ModelOptimizer {
         self.use_fp16 = true;
     }
 
-    pub fn optimize_model(&self, model_path: &str, output_path: &str) -> Result<()> {
-        let mut model = ort::SessionBuilder::new()?
-            .with_model_from_file(model_path)?;
+    /// Interpreter used to run the conversion scripts.
+    pub fn set_python(&mut self, python: impl Into<String>) {
+        self.python = python.into();
+    }
 
-        if self.quantize {
+    fn conversion_script(&self) -> Result<&'static str> {
+        match (self.quantize, self.use_fp16) {
+            (true, true) => Err(anyhow::anyhow!(
+                "INT8 quantization and FP16 conversion are mutually exclusive; enable only one"
+            )),
+            (true, false) => Ok(QUANTIZE_SCRIPT),
+            (false, true) => Ok(FP16_SCRIPT),
+            (false, false) if self.use_tensorrt => Err(anyhow::anyhow!(
+                "TensorRT has no offline conversion step; select it at load time with \
+                 GpuConfig providers instead"
+            )),
+            (false, false) => Err(anyhow::anyhow!(
+                "No optimization enabled; call enable_quantization or enable_fp16 first"
+            )),
         }
+    }
 
+    /// Convert `model_path` and write the optimized model to `output_path`.
+    pub fn optimize_model(&self, model_path: &str, output_path: &str) -> Result<()> {
+        let script = self.conversion_script()?;
+        if !std::path::Path::new(model_path).exists() {
+            return Err(anyhow::anyhow!("Model file not found: {}", model_path));
+        }
         if self.use_tensorrt {
+            log::warn!("TensorRT is applied at load time; run the optimized model with the TensorRT provider");
         }
 
-        if self.use_fp16 {
+        let output = std::process::Command::new(&self.python)
+            .arg("-c")
+            .arg(script)
+            .arg(model_path)
+            .arg(output_path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to run {}: {}", self.python, e))?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Model optimization failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        if !std::path::Path::new(output_path).exists() {
+            return Err(anyhow::anyhow!("Optimizer did not write {}", output_path));
         }
 
         Ok(())
     }
 }
 
+/// LRU cache of processed images. Every method takes `&self`, so one cache
+/// can sit in `web::Data` and be shared by all REST workers. Lookups also
+/// update recency, so both reads and writes take the lock briefly.
 pub struct CacheManager {
-    cache_size: usize,
-    cache: lru::LruCache<String, Arc<Mat>>,
+    cache: Mutex<lru::LruCache<String, Arc<Mat>>>,
 }
 
 impl CacheManager {
+    /// A size of zero is treated as one.
     pub fn new(cache_size: usize) -> Self {
         Self {
-            cache_size,
-            cache: lru::LruCache::new(cache_size),
+            cache: Mutex::new(lru::LruCache::new(cache_capacity(cache_size))),
         }
     }
 
-    pub fn cache_result(&mut self, key: String, result: Mat) {
-        self.cache.put(key, Arc::new(result));
+    pub fn cache_result(&self, key: String, result: Mat) {
+        self.lock().put(key, Arc::new(result));
     }
 
-    pub fn get_cached_result(&mut self, key: &str) -> Option<Arc<Mat>> {
-        self.cache.get(key).cloned()
+    pub fn get_cached_result(&self, key: &str) -> Option<Arc<Mat>> {
+        self.lock().get(key).cloned()
     }
 
-    pub fn clear_cache(&mut self) {
-        self.cache.clear();
+    pub fn clear_cache(&self) {
+        self.lock().clear();
     }
 
-    pub fn resize_cache(&mut self, new_size: usize) {
-        let mut new_cache = lru::LruCache::new(new_size);
-        for (key, value) in self.cache.iter() {
-            new_cache.put(key.clone(), value.clone());
-        }
-        self.cache = new_cache;
-        self.cache_size = new_size;
+    /// Change the capacity, evicting the least recently used entries when
+    /// shrinking.
+    pub fn resize_cache(&self, new_size: usize) {
+        self.lock().resize(cache_capacity(new_size));
+    }
+
+    pub fn cache_size(&self) -> usize {
+        self.lock().cap().get()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
     }
+
+    /// A panic while holding the lock cannot leave the cache half-updated,
+    /// so a poisoned lock is still safe to use.
+    fn lock(&self) -> std::sync::MutexGuard<'_, lru::LruCache<String, Arc<Mat>>> {
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn cache_capacity(size: usize) -> std::num::NonZeroUsize {
+    std::num::NonZeroUsize::new(size).unwrap_or(std::num::NonZeroUsize::MIN)
 }
 
 #[cfg(test)]
@@ -203,11 +306,73 @@ mod tests {
             .unwrap();
 
         assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_batch_errors_keep_their_index() {
+        let processor = BatchProcessor::new(2, 1, false);
+        let images = (0..5)
+            .map(|i| Mat::new_rows_cols_with_default(1, i + 1, core::CV_8UC1, core::Scalar::all(0.0)).unwrap())
+            .collect();
+        let results = processor
+            .process_images(images, |image| {
+                if image.cols() % 2 == 0 {
+                    Err(anyhow::anyhow!("{} columns", image.cols()))
+                } else {
+                    Ok(image.cols())
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap(), &1);
+        assert_eq!(results[1].as_ref().unwrap_err().to_string(), "2 columns");
+        assert_eq!(results[2].as_ref().unwrap(), &3);
+        assert_eq!(results[3].as_ref().unwrap_err().to_string(), "4 columns");
+        assert_eq!(results[4].as_ref().unwrap(), &5);
+    }
+
+    #[tokio::test]
+    async fn test_batch_progress_is_broadcast() {
+        let ws_manager = Arc::new(tokio::sync::Mutex::new(websocket::WsManager::new()));
+        let (_, tx) = ws_manager.lock().await.create_connection();
+        let mut rx = tx.subscribe();
+
+        let processor = BatchProcessor::new(2, 1, false).with_progress("job-1", ws_manager);
+        let images = vec![Mat::default(); 5];
+        let results = processor.process_images(images, |_| Ok(1u8)).await.unwrap();
+        assert_eq!(results.len(), 5);
+
+        let mut progress = Vec::new();
+        while let Ok(websocket::WsMessage::Progress { job_id, done, total }) = rx.try_recv() {
+            assert_eq!(job_id, "job-1");
+            assert_eq!(total, 5);
+            progress.push(done);
+        }
+        assert_eq!(progress.len(), 3);
+        assert_eq!(progress.last(), Some(&5));
+    }
+
+    #[test]
+    fn test_optimizer_requires_a_conversion() {
+        let mut optimizer = ModelOptimizer::new();
+        assert!(optimizer.optimize_model("model.onnx", "out.onnx").is_err());
+
+        optimizer.enable_tensorrt();
+        assert!(optimizer.conversion_script().is_err());
+
+        optimizer.enable_quantization();
+        assert_eq!(optimizer.conversion_script().unwrap(), QUANTIZE_SCRIPT);
+
+        optimizer.enable_fp16();
+        assert!(optimizer.conversion_script().is_err());
     }
 
     #[test]
     fn test_cache_manager() {
-        let mut cache = CacheManager::new(2);
+        let cache = CacheManager::new(2);
         let mat = Mat::default();
 
         cache.cache_result("key1".to_string(), mat.clone());
@@ -224,4 +389,34 @@ mod tests {
         assert!(cache.get_cached_result("key3").is_some());
         assert!(cache.get_cached_result("key4").is_some());
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_cache_manager_concurrent_access() {
+        let cache = Arc::new(CacheManager::new(64));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    for i in 0..500 {
+                        let key = format!("key{}", (task * 7 + i) % 100);
+                        if i % 3 == 0 {
+                            cache.cache_result(key, Mat::default());
+                        } else if let Some(mat) = cache.get_cached_result(&key) {
+                            assert!(mat.empty());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert!(cache.len() <= 64);
+        assert!(!cache.is_empty());
+        cache.resize_cache(10);
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.cache_size(), 10);
+    }
 } 
\ No newline at end of file
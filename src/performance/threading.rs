@@ -0,0 +1,231 @@
+use anyhow::Result;
+use ort::{Environment, Session};
+use serde::Deserialize;
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::sync::Semaphore;
+
+use crate::performance::gpu::{build_session_with, GpuConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SessionPoolConfig {
+    pub sessions: usize,        // Sessions loaded, and so jobs that run at once
+    pub queue_capacity: usize,  // Jobs `run` lets wait for a session before holding submitters back
+}
+
+impl Default for SessionPoolConfig {
+    fn default() -> Self {
+        let sessions = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).clamp(1, 4);
+        Self {
+            sessions,
+            queue_capacity: sessions * 4,
+        }
+    }
+}
+
+impl SessionPoolConfig {
+    /// One session, for callers that never run inference concurrently,
+    /// such as the single-image CLI.
+    pub fn single() -> Self {
+        Self {
+            sessions: 1,
+            queue_capacity: 4,
+        }
+    }
+}
+
+/// A fixed set of sessions for one model, each used by one job at a time,
+/// so models are loaded once and at most `size()` inferences run at once.
+/// Synchronous callers, such as rayon workers analyzing faces in parallel,
+/// check a session out with `with_session`. Async callers queue jobs with
+/// `run`, which bounds how many may wait so a burst of requests does not
+/// pile up blocking threads.
+pub struct SessionPool<S = Session> {
+    slots: Arc<Slots<S>>,
+    queue: Arc<Semaphore>,
+    size: usize,
+}
+
+struct Slots<S> {
+    sessions: Mutex<Vec<S>>,
+    available: Condvar,
+}
+
+impl<S> Slots<S> {
+    fn with_session<R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            loop {
+                match sessions.pop() {
+                    Some(session) => break session,
+                    None => sessions = self.available.wait(sessions).unwrap(),
+                }
+            }
+        };
+
+        let mut checkout = Checkout { slots: self, session: Some(session) };
+        f(checkout.session.as_mut().expect("session is checked out until drop"))
+    }
+}
+
+/// Puts the session back when dropped, so a panicking job does not shrink
+/// the pool.
+struct Checkout<'a, S> {
+    slots: &'a Slots<S>,
+    session: Option<S>,
+}
+
+impl<S> Drop for Checkout<'_, S> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            if let Ok(mut sessions) = self.slots.sessions.lock() {
+                sessions.push(session);
+            }
+            self.slots.available.notify_one();
+        }
+    }
+}
+
+impl SessionPool<Session> {
+    pub fn build(
+        environment: &Arc<Environment>,
+        model_path: &str,
+        gpu: &GpuConfig,
+        config: &SessionPoolConfig,
+    ) -> Result<Self> {
+        let sessions = (0..config.sessions.max(1))
+            .map(|_| build_session_with(environment, model_path, gpu))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(sessions, config.queue_capacity)
+    }
+}
+
+impl<S> SessionPool<S> {
+    pub fn new(sessions: Vec<S>, queue_capacity: usize) -> Result<Self> {
+        if sessions.is_empty() {
+            return Err(anyhow::anyhow!("Session pool needs at least one session"));
+        }
+        Ok(Self {
+            size: sessions.len(),
+            queue: Arc::new(Semaphore::new(sessions.len() + queue_capacity)),
+            slots: Arc::new(Slots {
+                sessions: Mutex::new(sessions),
+                available: Condvar::new(),
+            }),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Run `f` with a session checked out of the pool, blocking until one is
+    /// free. The session is returned to the pool afterwards, even if `f`
+    /// panics. Must not be called from an async task; use `run` there.
+    pub fn with_session<R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        self.slots.with_session(f)
+    }
+
+    /// Run `f` on each session currently in the pool, skipping checked-out
+    /// ones. Meant for startup work such as warm-up, before the pool is
+    /// shared.
+    pub fn for_each_idle<R>(&self, f: impl FnMut(&S) -> R) -> Vec<R> {
+        self.slots.sessions.lock().unwrap().iter().map(f).collect()
+    }
+}
+
+impl<S: Send + 'static> SessionPool<S> {
+    /// Queue `job` for the next free session and wait for its result. The
+    /// job runs on tokio's blocking threads; while the queue is full this
+    /// waits for room without blocking the executor.
+    pub async fn run<R, F>(&self, job: F) -> Result<R>
+    where
+        F: FnOnce(&mut S) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let _permit = self
+            .queue
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| anyhow::anyhow!("Session pool is shut down"))?;
+        let slots = self.slots.clone();
+        tokio::task::spawn_blocking(move || slots.with_session(job))
+            .await
+            .map_err(|e| {
+                log::error!("Inference job panicked: {}", e);
+                anyhow::anyhow!("Inference job failed")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_session_pool_limits_concurrent_checkouts() {
+        use rayon::prelude::*;
+
+        let pool = SessionPool::new(vec![0u8, 1], 0).unwrap();
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let results: Vec<usize> = (0..32usize)
+            .into_par_iter()
+            .map(|i| {
+                pool.with_session(|_| {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    i
+                })
+            })
+            .collect();
+
+        assert_eq!(results, (0..32).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= pool.size());
+
+        let mut idle = pool.for_each_idle(|session| *session);
+        idle.sort();
+        assert_eq!(idle, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounds_concurrency() {
+        let pool = Arc::new(SessionPool::new(vec![0usize, 0], 2).unwrap());
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let jobs: Vec<_> = (0..16usize)
+            .map(|i| {
+                let (pool, active, peak) = (pool.clone(), active.clone(), peak.clone());
+                tokio::spawn(async move {
+                    pool.run(move |handled: &mut usize| {
+                        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(2));
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        *handled += 1;
+                        i * 2
+                    })
+                    .await
+                    .unwrap()
+                })
+            })
+            .collect();
+
+        for (i, job) in jobs.into_iter().enumerate() {
+            assert_eq!(job.await.unwrap(), i * 2);
+        }
+        assert!(peak.load(Ordering::SeqCst) <= pool.size());
+        assert_eq!(pool.for_each_idle(|handled| *handled).iter().sum::<usize>(), 16);
+
+        // A panicking job is reported and its session goes back to the pool
+        assert!(pool.run(|_: &mut usize| panic!("bad input")).await.is_err());
+        assert_eq!(pool.for_each_idle(|_| ()).len(), 2);
+        assert_eq!(pool.run(|_: &mut usize| 7).await.unwrap(), 7);
+    }
+}
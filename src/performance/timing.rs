@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Pipeline stages timed by `PerfStats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Detection,   // `FaceDetector::detect` or the Haar cascade
+    Attributes,  // `analyze_face`, once per face
+    Embedding,   // `EmbeddingGenerator::generate`, once per face
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StageTiming {
+    pub calls: u32,
+    pub total_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Accumulates how long each stage took. Safe to share between the workers
+/// that analyze faces in parallel, so a stage's total is the sum over calls
+/// and can exceed the wall-clock time of the request.
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    stages: Mutex<BTreeMap<Stage, StageTiming>>,
+}
+
+impl PerfStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f`, adding the time it took to `stage`.
+    pub fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(stage, start.elapsed());
+        result
+    }
+
+    pub fn record(&self, stage: Stage, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        if let Ok(mut stages) = self.stages.lock() {
+            let timing = stages.entry(stage).or_default();
+            timing.calls += 1;
+            timing.total_ms += ms;
+            timing.max_ms = timing.max_ms.max(ms);
+        }
+    }
+
+    /// Timings recorded so far, keyed by stage. Stages never run are absent.
+    pub fn snapshot(&self) -> BTreeMap<Stage, StageTiming> {
+        self.stages.lock().map(|stages| stages.clone()).unwrap_or_default()
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut stages) = self.stages.lock() {
+            stages.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perf_stats_accumulate_per_stage() {
+        let stats = PerfStats::new();
+        stats.record(Stage::Attributes, Duration::from_millis(4));
+        stats.record(Stage::Attributes, Duration::from_millis(10));
+        assert_eq!(stats.time(Stage::Detection, || 7), 7);
+
+        let snapshot = stats.snapshot();
+        let attributes = &snapshot[&Stage::Attributes];
+        assert_eq!(attributes.calls, 2);
+        assert!((attributes.total_ms - 14.0).abs() < 1e-6);
+        assert!((attributes.max_ms - 10.0).abs() < 1e-6);
+        assert_eq!(snapshot[&Stage::Detection].calls, 1);
+        assert!(!snapshot.contains_key(&Stage::Embedding));
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["attributes"]["calls"], 2);
+
+        stats.reset();
+        assert!(stats.snapshot().is_empty());
+    }
+}
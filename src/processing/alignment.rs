@@ -0,0 +1,148 @@
+use opencv::{
+    core,
+    imgproc,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use anyhow::Result;
+use crate::attributes::landmarks::{FacialLandmark, FacialLandmarks};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlignmentTemplate {
+    pub width: i32,
+    pub height: i32,
+    pub left_eye: (f32, f32),   // Relative position (0.0 to 1.0) in the output chip
+    pub right_eye: (f32, f32),  // Relative position (0.0 to 1.0) in the output chip
+}
+
+impl Default for AlignmentTemplate {
+    fn default() -> Self {
+        Self {
+            width: 112,
+            height: 112,
+            left_eye: (0.35, 0.40),
+            right_eye: (0.65, 0.40),
+        }
+    }
+}
+
+impl AlignmentTemplate {
+    pub fn left_eye_px(&self) -> core::Point2f {
+        core::Point2f::new(self.left_eye.0 * self.width as f32, self.left_eye.1 * self.height as f32)
+    }
+
+    pub fn right_eye_px(&self) -> core::Point2f {
+        core::Point2f::new(self.right_eye.0 * self.width as f32, self.right_eye.1 * self.height as f32)
+    }
+}
+
+/// Centers of the two eyes, ordered left-to-right in image coordinates.
+pub fn eye_centers(landmarks: &FacialLandmarks) -> Option<(core::Point2f, core::Point2f)> {
+    let a = centroid(&landmarks.left_eye)?;
+    let b = centroid(&landmarks.right_eye)?;
+    if a.x <= b.x {
+        Some((a, b))
+    } else {
+        Some((b, a))
+    }
+}
+
+fn centroid(points: &[FacialLandmark]) -> Option<core::Point2f> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f32;
+    let x = points.iter().map(|p| p.x).sum::<f32>() / n;
+    let y = points.iter().map(|p| p.y).sum::<f32>() / n;
+    Some(core::Point2f::new(x, y))
+}
+
+/// Similarity transform (rotation, uniform scale, translation) that maps the
+/// detected eye centers onto the template eye positions, as a 2x3 affine matrix.
+pub fn alignment_transform(
+    left_eye: core::Point2f,
+    right_eye: core::Point2f,
+    template: &AlignmentTemplate,
+) -> Result<[[f64; 3]; 2]> {
+    let dst_left = template.left_eye_px();
+    let dst_right = template.right_eye_px();
+
+    let (sx, sy) = ((right_eye.x - left_eye.x) as f64, (right_eye.y - left_eye.y) as f64);
+    let (dx, dy) = ((dst_right.x - dst_left.x) as f64, (dst_right.y - dst_left.y) as f64);
+
+    let src_dist = (sx * sx + sy * sy).sqrt();
+    if src_dist < 1e-6 {
+        return Err(anyhow::anyhow!("Eye positions are degenerate"));
+    }
+
+    let scale = (dx * dx + dy * dy).sqrt() / src_dist;
+    let angle = dy.atan2(dx) - sy.atan2(sx);
+    let a = scale * angle.cos();
+    let b = scale * angle.sin();
+
+    let tx = dst_left.x as f64 - (a * left_eye.x as f64 - b * left_eye.y as f64);
+    let ty = dst_left.y as f64 - (b * left_eye.x as f64 + a * left_eye.y as f64);
+
+    Ok([[a, -b, tx], [b, a, ty]])
+}
+
+pub fn apply_transform(transform: &[[f64; 3]; 2], point: core::Point2f) -> core::Point2f {
+    let (x, y) = (point.x as f64, point.y as f64);
+    core::Point2f::new(
+        (transform[0][0] * x + transform[0][1] * y + transform[0][2]) as f32,
+        (transform[1][0] * x + transform[1][1] * y + transform[1][2]) as f32,
+    )
+}
+
+/// Warp `image` so the eyes land on the template positions and crop to the
+/// template size.
+pub fn align_face(
+    image: &Mat,
+    landmarks: &FacialLandmarks,
+    template: &AlignmentTemplate,
+) -> Result<Mat> {
+    let (left_eye, right_eye) = eye_centers(landmarks)
+        .ok_or_else(|| anyhow::anyhow!("Landmarks are missing eye points"))?;
+    let transform = alignment_transform(left_eye, right_eye, template)?;
+    let matrix = Mat::from_slice_2d(&transform)?;
+
+    let mut aligned = Mat::default();
+    imgproc::warp_affine(
+        image,
+        &mut aligned,
+        &matrix,
+        core::Size::new(template.width, template.height),
+        imgproc::INTER_LINEAR,
+        core::BORDER_REPLICATE,
+        core::Scalar::default(),
+    )?;
+
+    Ok(aligned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: core::Point2f, expected: core::Point2f) {
+        assert!((actual.x - expected.x).abs() < 0.5, "{:?} vs {:?}", actual, expected);
+        assert!((actual.y - expected.y).abs() < 0.5, "{:?} vs {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_differently_posed_faces_align_to_template() {
+        let template = AlignmentTemplate::default();
+        let faces = [
+            // Upright face, eyes 60px apart
+            (core::Point2f::new(200.0, 150.0), core::Point2f::new(260.0, 150.0)),
+            // Small face rolled by roughly 25 degrees
+            (core::Point2f::new(40.0, 80.0), core::Point2f::new(67.0, 93.0)),
+        ];
+
+        for (left, right) in faces {
+            let transform = alignment_transform(left, right, &template).unwrap();
+            assert_near(apply_transform(&transform, left), template.left_eye_px());
+            assert_near(apply_transform(&transform, right), template.right_eye_px());
+        }
+    }
+}
@@ -4,23 +4,45 @@ use opencv::{
     prelude::*,
     types::VectorOfMat,
 };
-use serde::Serialize;
-use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+use crate::common::config::{Config, DetectorThresholds, ModelPaths};
+use crate::common::error::{FaceAnalyzerError, Result};
+use crate::common::types::{BoundingBox, Landmark};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub enum DetectorType {
+    #[default]
+    #[serde(alias = "haar")]
     Haar,
+    #[serde(alias = "dnn")]
     DNN,
+    #[serde(alias = "mtcnn")]
     MTCNN,
+    #[serde(alias = "retinaface")]
     RetinaFace,
 }
 
+impl std::str::FromStr for DetectorType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "haar" => Ok(DetectorType::Haar),
+            "dnn" => Ok(DetectorType::DNN),
+            "mtcnn" => Ok(DetectorType::MTCNN),
+            "retinaface" => Ok(DetectorType::RetinaFace),
+            _ => Err(anyhow::anyhow!("Unknown detector type: {} (expected haar, dnn, mtcnn or retinaface)", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct DetectionResult {
-    pub bbox: core::Rect,
+    pub bbox: BoundingBox,
     pub confidence: f32,
-    pub landmarks: Option<Vec<core::Point2f>>,
+    pub landmarks: Option<Vec<Landmark>>,
 }
 
 pub struct FaceDetector {
@@ -28,6 +50,8 @@ pub struct FaceDetector {
     confidence_threshold: f32,
     min_face_size: core::Size,
     scale_factor: f32,
+    min_neighbors: i32,
+    model_paths: ModelPaths,
 }
 
 impl FaceDetector {
@@ -42,9 +66,56 @@ impl FaceDetector {
             confidence_threshold,
             min_face_size,
             scale_factor,
+            min_neighbors: 3,
+            model_paths: ModelPaths::default(),
         }
     }
 
+    /// Overlapping Haar candidates needed to keep a detection. Lower finds
+    /// more faces and more false positives.
+    pub fn with_min_neighbors(mut self, min_neighbors: i32) -> Self {
+        self.min_neighbors = min_neighbors;
+        self
+    }
+
+    /// Load the cascade and DNN weights from `model_paths` instead of the
+    /// default locations.
+    pub fn with_model_paths(mut self, model_paths: ModelPaths) -> Self {
+        self.model_paths = model_paths;
+        self
+    }
+
+    /// Switch to another detector type, picking up that type's configured
+    /// confidence threshold.
+    pub fn set_detector_type(&mut self, detector_type: DetectorType, thresholds: &DetectorThresholds) {
+        self.detector_type = detector_type;
+        self.confidence_threshold = thresholds.for_type(detector_type);
+    }
+
+    pub fn detector_type(&self) -> DetectorType {
+        self.detector_type
+    }
+
+    pub fn confidence_threshold(&self) -> f32 {
+        self.confidence_threshold
+    }
+
+    pub fn min_face_size(&self) -> core::Size {
+        self.min_face_size
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    pub fn min_neighbors(&self) -> i32 {
+        self.min_neighbors
+    }
+
+    pub fn model_paths(&self) -> &ModelPaths {
+        &self.model_paths
+    }
+
     pub fn detect(&self, image: &Mat) -> Result<Vec<DetectionResult>> {
         match self.detector_type {
             DetectorType::Haar => self.detect_haar(image),
@@ -55,9 +126,7 @@ impl FaceDetector {
     }
 
     fn detect_haar(&self, image: &Mat) -> Result<Vec<DetectionResult>> {
-        let cascade = opencv::objdetect::CascadeClassifier::new(
-            "haarcascades/haarcascade_frontalface_default.xml"
-        )?;
+        let cascade = opencv::objdetect::CascadeClassifier::new(&self.model_paths.cascade)?;
 
         let mut gray = Mat::default();
         opencv::imgproc::cvt_color(image, &mut gray, opencv::imgproc::COLOR_BGR2GRAY, 0)?;
@@ -67,25 +136,24 @@ impl FaceDetector {
             &gray,
             &mut faces,
             self.scale_factor,
-            3,
+            self.min_neighbors,
             0,
             self.min_face_size,
             core::Size::new(0, 0),
         )?;
 
         Ok(faces.iter().map(|rect| DetectionResult {
-            bbox: rect,
+            bbox: rect.into(),
             confidence: 1.0, // Haar cascade doesn't provide confidence scores
             landmarks: None,
-        }).collect())
+        })
+        .filter(|detection| detection.confidence >= self.confidence_threshold)
+        .collect())
     }
 
     fn detect_dnn(&self, image: &Mat) -> Result<Vec<DetectionResult>> {
         // Load DNN model (e.g., ResNet SSD)
-        let model_path = "models/res10_300x300_ssd_iter_140000.caffemodel";
-        let config_path = "models/deploy.prototxt";
-
-        let net = dnn::read_net_from_caffe(config_path, model_path)?;
+        let net = dnn::read_net_from_caffe(&self.model_paths.dnn_config, &self.model_paths.dnn_model)?;
         
         // Prepare input blob
         let blob = dnn::blob_from_image(
@@ -122,7 +190,7 @@ impl FaceDetector {
                 );
 
                 results.push(DetectionResult {
-                    bbox: rect,
+                    bbox: rect.into(),
                     confidence,
                     landmarks: None,
                 });
@@ -135,13 +203,13 @@ impl FaceDetector {
     fn detect_mtcnn(&self, _image: &Mat) -> Result<Vec<DetectionResult>> {
         // TODO: Implement MTCNN detection
         // This requires implementing or integrating the MTCNN model
-        unimplemented!("MTCNN detection not yet implemented")
+        Err(FaceAnalyzerError::detection("MTCNN detection not yet implemented"))
     }
 
     fn detect_retinaface(&self, _image: &Mat) -> Result<Vec<DetectionResult>> {
         // TODO: Implement RetinaFace detection
         // This requires implementing or integrating the RetinaFace model
-        unimplemented!("RetinaFace detection not yet implemented")
+        Err(FaceAnalyzerError::detection("RetinaFace detection not yet implemented"))
     }
 }
 
@@ -154,34 +222,102 @@ impl DetectorFactory {
         min_face_size: Option<core::Size>,
         scale_factor: Option<f32>,
     ) -> Result<FaceDetector> {
-        // Check if required model files exist
-        match detector_type {
-            DetectorType::Haar => {
-                let cascade_path = Path::new("haarcascades/haarcascade_frontalface_default.xml");
-                if !cascade_path.exists() {
-                    return Err(anyhow::anyhow!("Haar cascade file not found"));
-                }
-            }
-            DetectorType::DNN => {
-                let model_path = Path::new("models/res10_300x300_ssd_iter_140000.caffemodel");
-                let config_path = Path::new("models/deploy.prototxt");
-                if !model_path.exists() || !config_path.exists() {
-                    return Err(anyhow::anyhow!("DNN model files not found"));
-                }
-            }
-            DetectorType::MTCNN => {
-                // TODO: Add MTCNN model file checks
-            }
-            DetectorType::RetinaFace => {
-                // TODO: Add RetinaFace model file checks
-            }
-        }
+        Self::create_detector_with_thresholds(
+            detector_type,
+            confidence_threshold,
+            min_face_size,
+            scale_factor,
+            &DetectorThresholds::default(),
+        )
+    }
+
+    /// Like `create_detector`, but falls back to the per-type threshold from
+    /// `thresholds` when no explicit threshold is given.
+    pub fn create_detector_with_thresholds(
+        detector_type: DetectorType,
+        confidence_threshold: Option<f32>,
+        min_face_size: Option<core::Size>,
+        scale_factor: Option<f32>,
+        thresholds: &DetectorThresholds,
+    ) -> Result<FaceDetector> {
+        check_model_files(detector_type, &ModelPaths::default())?;
 
         Ok(FaceDetector::new(
             detector_type,
-            confidence_threshold.unwrap_or(0.5),
+            confidence_threshold.unwrap_or_else(|| thresholds.for_type(detector_type)),
             min_face_size.unwrap_or(core::Size::new(30, 30)),
             scale_factor.unwrap_or(1.1),
         ))
     }
-} 
\ No newline at end of file
+
+    /// Build the detector selected in `config`, with its model paths and
+    /// confidence threshold.
+    pub fn from_config(config: &Config) -> Result<FaceDetector> {
+        check_model_files(config.detector, &config.models)?;
+        let params = &config.detection;
+        Ok(FaceDetector::new(
+            config.detector,
+            config.confidence_threshold(),
+            core::Size::new(params.min_face_size, params.min_face_size),
+            params.scale_factor,
+        )
+        .with_min_neighbors(params.min_neighbors)
+        .with_model_paths(config.models.clone()))
+    }
+}
+
+fn check_model_files(detector_type: DetectorType, model_paths: &ModelPaths) -> Result<()> {
+    match detector_type {
+        DetectorType::Haar => {
+            if !Path::new(&model_paths.cascade).exists() {
+                return Err(FaceAnalyzerError::detection(format!(
+                    "Haar cascade file not found: {}",
+                    model_paths.cascade
+                )));
+            }
+        }
+        DetectorType::DNN => {
+            if !Path::new(&model_paths.dnn_model).exists() || !Path::new(&model_paths.dnn_config).exists() {
+                return Err(FaceAnalyzerError::detection(format!(
+                    "DNN model files not found: {}, {}",
+                    model_paths.dnn_model, model_paths.dnn_config
+                )));
+            }
+        }
+        DetectorType::MTCNN => {
+            // TODO: Add MTCNN model file checks
+        }
+        DetectorType::RetinaFace => {
+            // TODO: Add RetinaFace model file checks
+        }
+    }
+    Ok(())
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switching_detector_applies_type_threshold() {
+        let thresholds = DetectorThresholds {
+            haar: 0.0,
+            dnn: 0.6,
+            mtcnn: 0.95,
+            retinaface: 0.85,
+        };
+        let mut detector = FaceDetector::new(
+            DetectorType::Haar,
+            thresholds.haar,
+            core::Size::new(30, 30),
+            1.1,
+        );
+
+        detector.set_detector_type(DetectorType::RetinaFace, &thresholds);
+        assert_eq!(detector.detector_type(), DetectorType::RetinaFace);
+        assert_eq!(detector.confidence_threshold(), 0.85);
+
+        detector.set_detector_type(DetectorType::DNN, &thresholds);
+        assert_eq!(detector.confidence_threshold(), 0.6);
+    }
+}
@@ -0,0 +1,278 @@
+use opencv::{core, imgcodecs, imgproc, prelude::*};
+use std::io::Cursor;
+use crate::common::error::{FaceAnalyzerError, Result};
+
+/// Keep the stored bit depth and channel count, which `to_bgr8` normalizes,
+/// and leave orientation to `apply_orientation` so it is applied exactly once.
+const READ_FLAGS: i32 =
+    imgcodecs::IMREAD_ANYDEPTH | imgcodecs::IMREAD_ANYCOLOR | imgcodecs::IMREAD_IGNORE_ORIENTATION;
+
+/// Read an image file as upright 8-bit BGR; see `decode_image`.
+pub fn read_image(path: &str) -> Result<Mat> {
+    let bytes = std::fs::read(path)?;
+    decode_image(&bytes).map_err(|e| match e {
+        FaceAnalyzerError::Decode(message) => {
+            FaceAnalyzerError::decode(format!("Could not load image {}: {}", path, message))
+        }
+        other => other,
+    })
+}
+
+/// Decode an encoded image (JPEG, PNG, TIFF, WebP, HEIC, ...) into 8-bit
+/// BGR, rotated upright according to its EXIF orientation. Phone cameras
+/// store photos sideways and only tag the rotation, which detectors do not
+/// expect.
+///
+/// OpenCV decodes most formats. WebP falls back to the `image` crate when
+/// OpenCV was built without it, and HEIC goes through libheif, which needs
+/// the `heic` feature.
+pub fn decode_image(bytes: &[u8]) -> Result<Mat> {
+    if is_heif(bytes) {
+        // libheif applies the container's rotation itself
+        return decode_heif(bytes);
+    }
+
+    let buffer = core::Vector::<u8>::from_slice(bytes);
+    let image = match imgcodecs::imdecode(&buffer, READ_FLAGS) {
+        Ok(image) if !image.empty() => to_bgr8(&image)?,
+        _ if is_webp(bytes) => decode_webp(bytes)?,
+        _ if is_gif(bytes) => decode_gif_frames(bytes, 1)?.0.remove(0),
+        _ => return Err(FaceAnalyzerError::decode("Could not decode image")),
+    };
+    match exif_orientation(bytes) {
+        Some(orientation) => apply_orientation(&image, orientation),
+        None => Ok(image),
+    }
+}
+
+pub fn is_gif(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+}
+
+/// Decode up to `max_frames` (at least one) frames of a GIF as BGR images,
+/// each the full canvas with earlier frames composited in. Also returns
+/// whether further frames were left out.
+pub fn decode_gif_frames(bytes: &[u8], max_frames: usize) -> Result<(Vec<Mat>, bool)> {
+    use image::AnimationDecoder;
+
+    let gif_error = |e: image::ImageError| FaceAnalyzerError::decode(format!("Could not decode GIF: {}", e));
+    let max_frames = max_frames.max(1);
+    let decoder = image::codecs::gif::GifDecoder::new(Cursor::new(bytes)).map_err(gif_error)?;
+
+    let mut frames = Vec::new();
+    for frame in decoder.into_frames().take(max_frames + 1) {
+        if frames.len() == max_frames {
+            return Ok((frames, true));
+        }
+        let rgb = image::DynamicImage::ImageRgba8(frame.map_err(gif_error)?.into_buffer()).to_rgb8();
+        let (width, height) = rgb.dimensions();
+        frames.push(rgb8_to_bgr_mat(width as usize, height as usize, width as usize * 3, rgb.as_raw())?);
+    }
+    if frames.is_empty() {
+        return Err(FaceAnalyzerError::decode("GIF has no frames"));
+    }
+    Ok((frames, false))
+}
+
+fn is_webp(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP"
+}
+
+/// HEIF container (`ftyp` box) with an image brand, as written for `.heic`
+/// and `.heif` files.
+fn is_heif(bytes: &[u8]) -> bool {
+    const BRANDS: [&[u8]; 8] = [b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1"];
+    bytes.len() >= 12 && &bytes[4..8] == b"ftyp" && BRANDS.contains(&&bytes[8..12])
+}
+
+fn decode_webp(bytes: &[u8]) -> Result<Mat> {
+    let image = image::load_from_memory_with_format(bytes, image::ImageFormat::WebP)
+        .map_err(|e| FaceAnalyzerError::decode(format!("Could not decode WebP image: {}", e)))?
+        .to_rgb8();
+    let (width, height) = image.dimensions();
+    rgb8_to_bgr_mat(width as usize, height as usize, width as usize * 3, image.as_raw())
+}
+
+#[cfg(feature = "heic")]
+fn decode_heif(bytes: &[u8]) -> Result<Mat> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let heif_error = |e: libheif_rs::HeifError| FaceAnalyzerError::decode(format!("Could not decode HEIC image: {}", e));
+    let context = HeifContext::read_from_bytes(bytes).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(heif_error)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| FaceAnalyzerError::decode("HEIC image has no interleaved RGB plane"))?;
+    rgb8_to_bgr_mat(plane.width as usize, plane.height as usize, plane.stride, plane.data)
+}
+
+#[cfg(not(feature = "heic"))]
+fn decode_heif(_bytes: &[u8]) -> Result<Mat> {
+    Err(FaceAnalyzerError::decode("HEIC images need face_analyzer built with the `heic` feature"))
+}
+
+/// Copy packed RGB rows, `stride` bytes apart, into a BGR `Mat`.
+fn rgb8_to_bgr_mat(width: usize, height: usize, stride: usize, data: &[u8]) -> Result<Mat> {
+    if width == 0 || height == 0 || stride < width * 3 || data.len() < stride * (height - 1) + width * 3 {
+        return Err(FaceAnalyzerError::decode("Decoded image buffer is smaller than its dimensions"));
+    }
+
+    let mut rgb = Mat::new_rows_cols_with_default(height as i32, width as i32, core::CV_8UC3, core::Scalar::all(0.0))?;
+    let row_bytes = width * 3;
+    let pixels = rgb.data_bytes_mut()?;
+    for y in 0..height {
+        pixels[y * row_bytes..(y + 1) * row_bytes].copy_from_slice(&data[y * stride..y * stride + row_bytes]);
+    }
+
+    let mut bgr = Mat::default();
+    imgproc::cvt_color(&rgb, &mut bgr, imgproc::COLOR_RGB2BGR, 0)?;
+    Ok(bgr)
+}
+
+/// The EXIF orientation tag (1-8) of an encoded image, if it has one.
+pub fn exif_orientation(bytes: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(bytes)).ok()?;
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+/// Turn an image stored with EXIF `orientation` upright. Values outside
+/// 2-8, including the normal 1, leave the image unchanged.
+pub fn apply_orientation(image: &Mat, orientation: u32) -> Result<Mat> {
+    let mut upright = Mat::default();
+    match orientation {
+        2 => core::flip(image, &mut upright, 1)?,
+        3 => core::rotate(image, &mut upright, core::ROTATE_180)?,
+        4 => core::flip(image, &mut upright, 0)?,
+        5 => core::transpose(image, &mut upright)?,
+        6 => core::rotate(image, &mut upright, core::ROTATE_90_CLOCKWISE)?,
+        7 => {
+            let mut transposed = Mat::default();
+            core::transpose(image, &mut transposed)?;
+            core::flip(&transposed, &mut upright, -1)?;
+        }
+        8 => core::rotate(image, &mut upright, core::ROTATE_90_COUNTERCLOCKWISE)?,
+        _ => upright = image.try_clone()?,
+    }
+    Ok(upright)
+}
+
+/// Convert an image to the 8-bit, 3-channel BGR layout the detectors and
+/// models expect. 16-bit images are scaled so their full range maps onto
+/// 0..=255, and float images are taken to be in 0..=1 as elsewhere in
+/// OpenCV. Grayscale is replicated across channels and alpha is dropped.
+pub fn to_bgr8(image: &Mat) -> Result<Mat> {
+    let mut eight_bit = Mat::default();
+    match image.depth() {
+        core::CV_8U => eight_bit = image.try_clone()?,
+        core::CV_16U => image.convert_to(&mut eight_bit, core::CV_8U, 1.0 / 257.0, 0.0)?,
+        core::CV_32F | core::CV_64F => image.convert_to(&mut eight_bit, core::CV_8U, 255.0, 0.0)?,
+        depth => {
+            let name = match depth {
+                core::CV_8S => "8-bit signed",
+                core::CV_16S => "16-bit signed",
+                core::CV_32S => "32-bit signed",
+                _ => "unknown",
+            };
+            return Err(FaceAnalyzerError::decode(format!(
+                "Unsupported {} image; expected 8- or 16-bit unsigned or floating point pixels",
+                name
+            )));
+        }
+    }
+
+    let mut bgr = Mat::default();
+    match eight_bit.channels() {
+        1 => imgproc::cvt_color(&eight_bit, &mut bgr, imgproc::COLOR_GRAY2BGR, 0)?,
+        3 => bgr = eight_bit,
+        4 => imgproc::cvt_color(&eight_bit, &mut bgr, imgproc::COLOR_BGRA2BGR, 0)?,
+        channels => {
+            return Err(FaceAnalyzerError::decode(format!(
+                "Unsupported image with {} channels; expected 1, 3 or 4",
+                channels
+            )));
+        }
+    }
+    Ok(bgr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_bgr8_scales_depth_and_expands_gray() {
+        let gray16 = Mat::new_rows_cols_with_default(4, 6, core::CV_16UC1, core::Scalar::all(65535.0)).unwrap();
+        let bgr = to_bgr8(&gray16).unwrap();
+        assert_eq!(bgr.typ(), core::CV_8UC3);
+        assert_eq!((bgr.cols(), bgr.rows()), (6, 4));
+        assert_eq!(*bgr.at_2d::<core::Vec3b>(0, 0).unwrap(), core::Vec3b::from([255, 255, 255]));
+
+        let float = Mat::new_rows_cols_with_default(2, 2, core::CV_32FC3, core::Scalar::all(0.5)).unwrap();
+        assert_eq!(to_bgr8(&float).unwrap().at_2d::<core::Vec3b>(1, 1).unwrap()[0], 128);
+
+        let signed = Mat::new_rows_cols_with_default(2, 2, core::CV_8SC1, core::Scalar::all(1.0)).unwrap();
+        assert!(to_bgr8(&signed).is_err());
+    }
+
+    #[test]
+    fn test_webp_and_heif_are_recognized() {
+        assert!(is_webp(b"RIFF\x24\x00\x00\x00WEBPVP8 "));
+        assert!(!is_webp(b"RIFF\x24\x00\x00\x00WAVEfmt "));
+        assert!(is_heif(b"\x00\x00\x00\x18ftypheic\x00\x00\x00\x00"));
+        assert!(!is_heif(b"\x00\x00\x00\x18ftypisom\x00\x00\x00\x00"));
+
+        // Two RGB pixels per row, padded to a 8-byte stride
+        let data = [255, 0, 0, 0, 255, 0, 9, 9, 0, 0, 255, 1, 2, 3, 9, 9];
+        let bgr = rgb8_to_bgr_mat(2, 2, 8, &data).unwrap();
+        assert_eq!(*bgr.at_2d::<core::Vec3b>(0, 0).unwrap(), core::Vec3b::from([0, 0, 255]));
+        assert_eq!(*bgr.at_2d::<core::Vec3b>(1, 1).unwrap(), core::Vec3b::from([3, 2, 1]));
+        assert!(rgb8_to_bgr_mat(2, 2, 8, &data[..12]).is_err());
+    }
+
+    #[test]
+    fn test_gif_frames_are_capped() {
+        let frames = [10u8, 120, 240].map(|value| {
+            image::Frame::new(image::RgbaImage::from_pixel(8, 6, image::Rgba([value, value, value, 255])))
+        });
+        let mut gif = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut gif).encode_frames(frames).unwrap();
+        assert!(is_gif(&gif));
+
+        let (decoded, truncated) = decode_gif_frames(&gif, 2).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert!(truncated);
+        assert_eq!((decoded[1].cols(), decoded[1].rows()), (8, 6));
+        assert!(decoded[1].at_2d::<core::Vec3b>(0, 0).unwrap()[0] > 100);
+
+        let (all, truncated) = decode_gif_frames(&gif, 30).unwrap();
+        assert_eq!((all.len(), truncated), (3, false));
+    }
+
+    #[test]
+    fn test_apply_orientation_turns_sideways_images_upright() {
+        // 2 rows x 3 columns, values 0..6 in row-major order
+        let mut image = Mat::new_rows_cols_with_default(2, 3, core::CV_8UC1, core::Scalar::all(0.0)).unwrap();
+        for i in 0..6 {
+            *image.at_2d_mut::<u8>(i / 3, i % 3).unwrap() = i as u8;
+        }
+        let pixel = |m: &Mat, row, col| *m.at_2d::<u8>(row, col).unwrap();
+
+        // Orientation 6 is stored rotated 90 degrees counter-clockwise
+        let rotated = apply_orientation(&image, 6).unwrap();
+        assert_eq!((rotated.rows(), rotated.cols()), (3, 2));
+        assert_eq!(pixel(&rotated, 0, 1), 0);
+        assert_eq!(pixel(&rotated, 0, 0), 3);
+
+        assert_eq!(pixel(&apply_orientation(&image, 3).unwrap(), 0, 0), 5);
+        assert_eq!(pixel(&apply_orientation(&image, 2).unwrap(), 0, 0), 2);
+        let transverse = apply_orientation(&image, 7).unwrap();
+        assert_eq!((transverse.rows(), pixel(&transverse, 0, 0)), (3, 5));
+        assert_eq!(pixel(&apply_orientation(&image, 1).unwrap(), 1, 2), 5);
+    }
+}
@@ -3,10 +3,12 @@ use opencv::{
     imgproc,
     prelude::*,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use ndarray::Array4;
+use crate::common::config::InputSize;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PreprocessingConfig {
     pub brightness: f64,      // -1.0 to 1.0
     pub contrast: f64,        // 0.0 to 3.0
@@ -183,4 +185,68 @@ impl ImagePreprocessor {
 
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+/// Resize a face crop to `size`, scale to 0..1 and lay it out as a
+/// `(1, 3, height, width)` tensor. Grayscale crops are expanded to BGR.
+pub fn image_to_chw(face_mat: &Mat, size: InputSize) -> Result<Array4<f32>> {
+    if face_mat.empty() || face_mat.cols() < 1 || face_mat.rows() < 1 {
+        anyhow::bail!("Cannot convert an empty image to a tensor");
+    }
+
+    let mut resized = Mat::default();
+    imgproc::resize(
+        face_mat,
+        &mut resized,
+        core::Size::new(size.width, size.height),
+        0.0,
+        0.0,
+        imgproc::INTER_LINEAR,
+    )?;
+
+    let mut bgr = Mat::default();
+    match resized.channels() {
+        1 => imgproc::cvt_color(&resized, &mut bgr, imgproc::COLOR_GRAY2BGR, 0)?,
+        3 => bgr = resized,
+        4 => imgproc::cvt_color(&resized, &mut bgr, imgproc::COLOR_BGRA2BGR, 0)?,
+        channels => anyhow::bail!("Unsupported image with {} channels", channels),
+    }
+
+    let mut float_mat = Mat::default();
+    bgr.convert_to(&mut float_mat, core::CV_32F, 1.0 / 255.0, 0.0)?;
+
+    let (width, height) = (size.width as usize, size.height as usize);
+    let mut chw = vec![0f32; 3 * height * width];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = float_mat.at_2d::<core::Vec3f>(y as i32, x as i32)?;
+            for c in 0..3 {
+                chw[c * height * width + y * width + x] = pixel[c];
+            }
+        }
+    }
+
+    Ok(Array4::from_shape_vec((1, 3, height, width), chw)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_to_chw_uses_configured_size() {
+        let face = Mat::new_rows_cols_with_default(
+            150,
+            120,
+            core::CV_8UC3,
+            core::Scalar::new(255.0, 0.0, 0.0, 0.0),
+        ).unwrap();
+
+        let tensor = image_to_chw(&face, InputSize::new(96, 96)).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, 96, 96]);
+        assert_eq!(tensor[[0, 0, 10, 10]], 1.0);
+        assert_eq!(tensor[[0, 2, 10, 10]], 0.0);
+
+        assert!(image_to_chw(&Mat::default(), InputSize::new(96, 96)).is_err());
+    }
+}
@@ -3,10 +3,11 @@ use opencv::{
     imgproc,
     prelude::*,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use crate::attributes::eyes::EyeState;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QualityMetrics {
     pub brightness: f32,      // 0.0 to 1.0
     pub contrast: f32,        // 0.0 to 1.0
@@ -17,9 +18,17 @@ pub struct QualityMetrics {
     pub occlusion: f32,      // Estimated face occlusion (0.0 to 1.0)
     pub symmetry: f32,       // Face symmetry score (0.0 to 1.0)
     pub overall_score: f32,  // Combined quality score (0.0 to 1.0)
+    pub eyes_closed: Option<bool>,  // Both eyes closed; None until set from landmarks
 }
 
 impl QualityMetrics {
+    /// Record the eye state derived from landmarks, which the pixel-based
+    /// metrics cannot see.
+    pub fn with_eye_state(mut self, eye_state: &EyeState) -> Self {
+        self.eyes_closed = Some(eye_state.both_closed());
+        self
+    }
+
     pub fn get_quality_description(&self) -> String {
         let mut issues = Vec::new();
 
@@ -53,6 +62,10 @@ impl QualityMetrics {
             issues.push("asymmetric face pose");
         }
 
+        if self.eyes_closed == Some(true) {
+            issues.push("eyes closed");
+        }
+
         if issues.is_empty() {
             format!("Good quality image (score: {:.0}%)", self.overall_score * 100.0)
         } else {
@@ -66,6 +79,7 @@ impl QualityMetrics {
 pub struct QualityAssessor {
     min_face_size: f32,
     max_angle: f32,
+    reject_closed_eyes: bool,
 }
 
 impl Default for QualityAssessor {
@@ -73,11 +87,27 @@ impl Default for QualityAssessor {
         Self {
             min_face_size: 0.1,  // Face should be at least 10% of image size
             max_angle: 30.0,     // Maximum 30 degrees deviation from frontal
+            reject_closed_eyes: false,
         }
     }
 }
 
 impl QualityAssessor {
+    /// Make `accepts` reject faces whose eyes are known to be closed, e.g.
+    /// for enrollment or ID photos.
+    pub fn with_closed_eye_rejection(mut self, reject: bool) -> Self {
+        self.reject_closed_eyes = reject;
+        self
+    }
+
+    /// Quality gate: whether a face meets the assessor's size and angle
+    /// limits and, when enabled, has its eyes open.
+    pub fn accepts(&self, metrics: &QualityMetrics) -> bool {
+        metrics.face_size >= self.min_face_size
+            && metrics.face_angle <= self.max_angle
+            && !(self.reject_closed_eyes && metrics.eyes_closed == Some(true))
+    }
+
     pub fn assess_quality(&self, face_mat: &Mat, face_rect: &core::Rect) -> Result<QualityMetrics> {
         // Calculate basic image statistics
         let brightness = self.calculate_brightness(face_mat)?;
@@ -115,6 +145,7 @@ impl QualityAssessor {
             occlusion,
             symmetry,
             overall_score,
+            eyes_closed: None,
         })
     }
 
@@ -217,4 +248,36 @@ impl QualityAssessor {
 
         (weighted_sum / weight_sum).min(1.0)
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> QualityMetrics {
+        QualityMetrics {
+            brightness: 0.5,
+            contrast: 0.5,
+            sharpness: 0.5,
+            blur_score: 0.8,
+            face_size: 0.3,
+            face_angle: 5.0,
+            occlusion: 0.0,
+            symmetry: 0.9,
+            overall_score: 0.7,
+            eyes_closed: None,
+        }
+    }
+
+    #[test]
+    fn test_closed_eye_rejection_is_opt_in() {
+        let closed = EyeState { left_eye_open: false, right_eye_open: false, ear: 0.08 };
+        let blinking = metrics().with_eye_state(&closed);
+        assert!(blinking.get_quality_description().contains("eyes closed"));
+
+        assert!(QualityAssessor::default().accepts(&blinking));
+        let strict = QualityAssessor::default().with_closed_eye_rejection(true);
+        assert!(!strict.accepts(&blinking));
+        assert!(strict.accepts(&metrics()));
+    }
+}
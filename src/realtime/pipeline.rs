@@ -0,0 +1,255 @@
+use opencv::{core, imgproc, prelude::*};
+use ort::Session;
+use anyhow::Result;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::common::config::{Config, InputSize, ModelInputSizes};
+use crate::common::types::clamp_rect_to_image;
+use crate::database::embeddings::EmbeddingGenerator;
+use crate::face::{analyze_face_with, AttributeDetectors, FaceAttributes};
+use crate::processing::detectors::FaceDetector;
+use crate::processing::quality::QualityAssessor;
+use crate::realtime::tracking::{TrackedFace, Tracker, TrackerConfig};
+use crate::realtime::video::{VideoConfig, VideoProcessor, VideoSink};
+use crate::realtime::visualization::Visualizer;
+
+const FRAME_BUFFER: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct VideoAnalysisSummary {
+    pub frames: u64,
+    pub faces: u64,
+    pub tracks: u64,
+}
+
+/// Runs detection and attribute analysis over decoded video frames.
+pub struct VideoAnalyzer {
+    detector: FaceDetector,
+    session: Session,
+    input_size: InputSize,
+    detectors: AttributeDetectors,
+    embedding_generator: Option<EmbeddingGenerator>,
+    quality_assessor: Option<QualityAssessor>,
+    tracker_config: TrackerConfig,
+    output_fourcc: [char; 4],
+}
+
+impl VideoAnalyzer {
+    pub fn new(detector: FaceDetector, session: Session) -> Self {
+        let input_size = ModelInputSizes::resolve(ModelInputSizes::default().attributes, &session);
+        Self {
+            detector,
+            session,
+            input_size,
+            detectors: AttributeDetectors::default(),
+            embedding_generator: None,
+            quality_assessor: None,
+            tracker_config: TrackerConfig::default(),
+            output_fourcc: VideoSink::DEFAULT_FOURCC,
+        }
+    }
+
+    /// `new`, with the attribute model's fallback input size taken from
+    /// `models.input_sizes.attributes` in `config` and the optional
+    /// attribute detectors configured under `models`.
+    pub fn from_config(detector: FaceDetector, session: Session, config: &Config) -> Result<Self> {
+        Ok(Self::new(detector, session)
+            .with_input_size(config.models.input_sizes.attributes)
+            .with_attribute_detectors(AttributeDetectors::from_config(config)?))
+    }
+
+    /// Input size to use when the model does not declare one. Sizes in the
+    /// model metadata still take precedence.
+    pub fn with_input_size(mut self, configured: InputSize) -> Self {
+        self.input_size = ModelInputSizes::resolve(configured, &self.session);
+        self
+    }
+
+    /// Optional per-face models, such as liveness, run on every face.
+    pub fn with_attribute_detectors(mut self, detectors: AttributeDetectors) -> Self {
+        self.detectors = detectors;
+        self
+    }
+
+    /// Use face embeddings alongside box overlap when associating tracks,
+    /// which keeps IDs stable when people cross paths.
+    pub fn with_embedding_generator(mut self, generator: EmbeddingGenerator) -> Self {
+        self.embedding_generator = Some(generator);
+        self
+    }
+
+    /// Codec used when writing annotated output, e.g. `['a', 'v', 'c', '1']`.
+    pub fn with_output_fourcc(mut self, fourcc: [char; 4]) -> Self {
+        self.output_fourcc = fourcc;
+        self
+    }
+
+    /// Assess each face's quality so the visualizer can badge it.
+    pub fn with_quality_assessor(mut self, assessor: QualityAssessor) -> Self {
+        self.quality_assessor = Some(assessor);
+        self
+    }
+
+    pub fn with_tracker_config(mut self, config: TrackerConfig) -> Self {
+        self.tracker_config = config;
+        self
+    }
+
+    pub fn analyze_frame(&self, frame: &Mat) -> Result<Vec<(core::Rect, FaceAttributes)>> {
+        let mut faces = Vec::new();
+        for detection in self.detector.detect(frame)? {
+            let bbox = clamp_rect_to_image(detection.bbox.rect(), frame.cols(), frame.rows());
+            if bbox.area() == 0 {
+                continue;
+            }
+            let face_roi = Mat::roi(frame, bbox)?;
+            if let Ok(attributes) = analyze_face_with(&face_roi, &self.session, self.input_size, &self.detectors) {
+                faces.push((bbox, attributes));
+            }
+        }
+        Ok(faces)
+    }
+
+    /// Analyze `frame` and assign each face a persistent track ID.
+    pub fn analyze_tracked(&self, frame: &Mat, tracker: &mut Tracker) -> Result<Vec<TrackedFace>> {
+        let faces = self.analyze_frame(frame)?;
+        let embeddings: Vec<Option<Vec<f32>>> = match &self.embedding_generator {
+            Some(generator) => faces
+                .iter()
+                .map(|(bbox, _)| {
+                    let face_roi = Mat::roi(frame, *bbox).ok()?;
+                    generator.generate(&face_roi).ok()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut tracked = tracker.track(faces, &embeddings);
+        if let Some(assessor) = &self.quality_assessor {
+            for face in &mut tracked {
+                let bbox = clamp_rect_to_image(face.bbox, frame.cols(), frame.rows());
+                face.quality = Mat::roi(frame, bbox)
+                    .ok()
+                    .and_then(|roi| assessor.assess_quality(&roi, &bbox).ok())
+                    .map(|quality| match &face.attributes.eye_state {
+                        Some(eye_state) => quality.with_eye_state(eye_state),
+                        None => quality,
+                    });
+            }
+        }
+        Ok(tracked)
+    }
+
+    /// Decode `video_path` on a background thread and analyze every frame it
+    /// yields. Frames are shown through `visualizer` when given, and the
+    /// annotated frames are written to `output_path` when given. The
+    /// `target_fps`, `start_time` and `end_time` settings of `video_config`
+    /// are applied by the decoder, and the output file is written at the
+    /// decoder's output frame rate so its duration matches what was analyzed.
+    pub fn run_analysis(
+        &self,
+        video_path: &Path,
+        video_config: VideoConfig,
+        mut visualizer: Option<&mut Visualizer>,
+        output_path: Option<&Path>,
+    ) -> Result<VideoAnalysisSummary> {
+        let processor = VideoProcessor::new(video_path, video_config)?;
+        let output_fps = processor.output_fps();
+
+        let (tx, mut rx) = mpsc::channel(FRAME_BUFFER);
+        let running = Arc::new(Mutex::new(true));
+        let decoder = {
+            let running = running.clone();
+            std::thread::spawn(move || processor.process_video(tx, running))
+        };
+
+        let result = self.consume_frames(&mut rx, &mut visualizer, output_path, output_fps, &running);
+
+        *running.lock().unwrap() = false;
+        drop(rx);
+        let decoded = decoder
+            .join()
+            .map_err(|_| anyhow::anyhow!("Video decode thread panicked"))?;
+
+        let summary = result?;
+        decoded?;
+        Ok(summary)
+    }
+
+    fn consume_frames(
+        &self,
+        rx: &mut mpsc::Receiver<Mat>,
+        visualizer: &mut Option<&mut Visualizer>,
+        output_path: Option<&Path>,
+        output_fps: f64,
+        running: &Arc<Mutex<bool>>,
+    ) -> Result<VideoAnalysisSummary> {
+        let mut summary = VideoAnalysisSummary::default();
+        let mut sink: Option<VideoSink> = None;
+        let mut tracker = Tracker::new(self.tracker_config.clone());
+
+        while let Some(frame) = rx.blocking_recv() {
+            let faces = self.analyze_tracked(&frame, &mut tracker)?;
+            summary.frames += 1;
+            summary.faces += faces.len() as u64;
+            if let Some(max_id) = faces.iter().map(|face| face.track_id).max() {
+                summary.tracks = summary.tracks.max(max_id);
+            }
+
+            let annotated = match visualizer.as_deref() {
+                Some(visualizer) => visualizer.render_tracked(&frame, &faces)?,
+                None if output_path.is_some() => draw_boxes(&frame, &faces)?,
+                None => frame,
+            };
+
+            if let Some(visualizer) = visualizer.as_deref_mut() {
+                visualizer.show(&annotated)?;
+                if !visualizer.handle_key_events()? {
+                    *running.lock().unwrap() = false;
+                    break;
+                }
+            }
+
+            if let Some(path) = output_path {
+                if sink.is_none() {
+                    sink = Some(VideoSink::new(path, self.output_fourcc, output_fps, annotated.size()?)?);
+                }
+                if let Some(sink) = sink.as_mut() {
+                    sink.write(&annotated)?;
+                }
+            }
+        }
+
+        if let Some(sink) = sink {
+            sink.finish()?;
+        }
+        Ok(summary)
+    }
+}
+
+fn draw_boxes(frame: &Mat, faces: &[TrackedFace]) -> Result<Mat> {
+    let mut annotated = frame.clone();
+    for face in faces {
+        imgproc::rectangle(
+            &mut annotated,
+            face.bbox,
+            core::Scalar::new(0.0, 255.0, 0.0, 0.0),
+            2,
+            imgproc::LINE_8,
+            0,
+        )?;
+        imgproc::put_text(
+            &mut annotated,
+            &format!("ID {}", face.track_id),
+            core::Point::new(face.bbox.x, (face.bbox.y - 6).max(12)),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.6,
+            core::Scalar::new(0.0, 255.0, 255.0, 0.0),
+            2,
+            imgproc::LINE_8,
+            false,
+        )?;
+    }
+    Ok(annotated)
+}
@@ -0,0 +1,162 @@
+use opencv::{core, imgcodecs, prelude::*};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::attributes::pose::HeadPose;
+use crate::common::types::clamp_rect_to_image;
+use crate::processing::quality::{QualityAssessor, QualityMetrics};
+
+#[derive(Debug, Clone)]
+pub struct ThumbnailConfig {
+    pub quality_weight: f32,     // Weight of the overall quality score
+    pub frontality_weight: f32,  // Weight of how close the pose is to frontal
+    pub output_dir: PathBuf,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            quality_weight: 0.6,
+            frontality_weight: 0.4,
+            output_dir: PathBuf::from("thumbnails"),
+        }
+    }
+}
+
+pub struct ThumbnailCandidate {
+    pub frame_index: u64,
+    pub score: f32,
+    pub crop: Mat,
+}
+
+/// Keeps the best-scoring face crop seen so far for each track.
+pub struct ThumbnailSelector {
+    config: ThumbnailConfig,
+    assessor: QualityAssessor,
+    best: HashMap<u64, ThumbnailCandidate>,
+}
+
+/// 1.0 for a frontal face, falling to 0.0 at 90 degrees on every axis.
+pub fn frontality(pose: &HeadPose) -> f32 {
+    let deviation = (pose.yaw.abs() + pose.pitch.abs() + pose.roll.abs()) / (3.0 * 90.0);
+    (1.0 - deviation).clamp(0.0, 1.0)
+}
+
+impl ThumbnailSelector {
+    pub fn new(config: ThumbnailConfig) -> Self {
+        Self {
+            config,
+            assessor: QualityAssessor::default(),
+            best: HashMap::new(),
+        }
+    }
+
+    pub fn score(&self, quality: &QualityMetrics, pose: Option<&HeadPose>) -> f32 {
+        let frontal = pose
+            .map(frontality)
+            .unwrap_or_else(|| (1.0 - quality.face_angle / 90.0).clamp(0.0, 1.0));
+        let total_weight = self.config.quality_weight + self.config.frontality_weight;
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+        (self.config.quality_weight * quality.overall_score + self.config.frontality_weight * frontal)
+            / total_weight
+    }
+
+    /// Score the face at `bbox` in `frame` and keep it if it beats the
+    /// track's current thumbnail. Returns whether it was kept.
+    pub fn offer(
+        &mut self,
+        track_id: u64,
+        frame_index: u64,
+        frame: &Mat,
+        bbox: core::Rect,
+        pose: Option<&HeadPose>,
+    ) -> Result<bool> {
+        let bbox = clamp_rect_to_image(bbox, frame.cols(), frame.rows());
+        if bbox.area() == 0 {
+            return Ok(false);
+        }
+        let crop = Mat::roi(frame, bbox)?.try_clone()?;
+        let full = core::Rect::new(0, 0, crop.cols(), crop.rows());
+        let quality = self.assessor.assess_quality(&crop, &full)?;
+        let score = self.score(&quality, pose);
+
+        let improves = self
+            .best
+            .get(&track_id)
+            .map_or(true, |current| score > current.score);
+        if improves {
+            self.best.insert(track_id, ThumbnailCandidate { frame_index, score, crop });
+        }
+        Ok(improves)
+    }
+
+    pub fn best(&self, track_id: u64) -> Option<&ThumbnailCandidate> {
+        self.best.get(&track_id)
+    }
+
+    /// Write each track's thumbnail to `<output_dir>/track_<id>.jpg`.
+    pub fn save_all(&self) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(&self.config.output_dir)?;
+        let mut paths = Vec::with_capacity(self.best.len());
+        for (track_id, candidate) in &self.best {
+            let path = self.config.output_dir.join(format!("track_{}.jpg", track_id));
+            imgcodecs::imwrite(&path.to_string_lossy(), &candidate.crop, &core::Vector::new())?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(yaw: f32) -> HeadPose {
+        HeadPose {
+            yaw,
+            pitch: 0.0,
+            roll: 0.0,
+            yaw_confidence: 1.0,
+            pitch_confidence: 1.0,
+            roll_confidence: 1.0,
+        }
+    }
+
+    fn flat_frame() -> Mat {
+        Mat::new_rows_cols_with_default(64, 64, core::CV_8UC3, core::Scalar::all(128.0)).unwrap()
+    }
+
+    fn sharp_frame() -> Mat {
+        let mut frame = flat_frame();
+        for y in 0..64 {
+            for x in 0..64 {
+                let value = if (x / 8 + y / 8) % 2 == 0 { 0 } else { 255 };
+                *frame.at_2d_mut::<core::Vec3b>(y, x).unwrap() = core::Vec3b::all(value);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_sharp_frontal_frame_becomes_thumbnail() {
+        let mut selector = ThumbnailSelector::new(ThumbnailConfig::default());
+        let bbox = core::Rect::new(0, 0, 64, 64);
+
+        let frames = [
+            (flat_frame(), pose(0.0)),
+            (flat_frame(), pose(5.0)),
+            (sharp_frame(), pose(2.0)),   // The one high-quality frame
+            (sharp_frame(), pose(80.0)),  // Sharp but in profile
+            (flat_frame(), pose(0.0)),
+        ];
+        for (index, (frame, head_pose)) in frames.iter().enumerate() {
+            selector.offer(7, index as u64, frame, bbox, Some(head_pose)).unwrap();
+        }
+
+        assert_eq!(selector.best(7).unwrap().frame_index, 2);
+        assert!(selector.best(8).is_none());
+    }
+}
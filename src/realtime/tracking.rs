@@ -0,0 +1,254 @@
+use opencv::core;
+use serde::Serialize;
+
+use crate::attributes::eyes::BlinkCounter;
+use crate::common::types::BoundingBox;
+use crate::database::embeddings::EmbeddingComparator;
+use crate::face::FaceAttributes;
+use crate::processing::quality::QualityMetrics;
+
+#[derive(Debug, Clone)]
+pub struct TrackerConfig {
+    pub iou_weight: f32,        // Weight of box overlap in the association score
+    pub embedding_weight: f32,  // Weight of embedding similarity when both sides have one
+    pub min_score: f32,         // Pairs scoring below this are never associated
+    pub max_missed_frames: u32, // Frames a track survives without a matching detection
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            iou_weight: 0.5,
+            embedding_weight: 0.5,
+            min_score: 0.3,
+            max_missed_frames: 15,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackedFace {
+    pub track_id: u64,
+    #[serde(serialize_with = "serialize_rect")]
+    pub bbox: core::Rect,
+    pub attributes: FaceAttributes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<QualityMetrics>,
+    pub blinks: u32,  // Blinks completed on this track so far; stays 0 without `eye_state`
+}
+
+fn serialize_rect<S: serde::Serializer>(rect: &core::Rect, serializer: S) -> Result<S::Ok, S::Error> {
+    BoundingBox::from(*rect).serialize(serializer)
+}
+
+struct Track {
+    id: u64,
+    bbox: core::Rect,
+    embedding: Option<Vec<f32>>,
+    missed: u32,
+    blinks: BlinkCounter,
+}
+
+/// Assigns persistent ids to faces across frames. A simple SORT-style
+/// tracker without motion prediction: detections are greedily matched to
+/// existing tracks by a mix of IoU and embedding similarity.
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+}
+
+pub fn iou(a: &core::Rect, b: &core::Rect) -> f32 {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.width).min(b.x + b.width);
+    let y2 = (a.y + a.height).min(b.y + b.height);
+    let intersection = ((x2 - x1).max(0) * (y2 - y1).max(0)) as f32;
+    let union = (a.area() + b.area()) as f32 - intersection;
+    if union <= 0.0 {
+        0.0
+    } else {
+        intersection / union
+    }
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    fn score(&self, track: &Track, bbox: &core::Rect, embedding: Option<&[f32]>) -> f32 {
+        let overlap = iou(&track.bbox, bbox);
+        match (track.embedding.as_deref(), embedding) {
+            (Some(a), Some(b)) => {
+                let similarity = EmbeddingComparator::cosine_similarity(a, b).max(0.0);
+                let total = self.config.iou_weight + self.config.embedding_weight;
+                (self.config.iou_weight * overlap + self.config.embedding_weight * similarity) / total
+            }
+            _ => overlap,
+        }
+    }
+
+    /// Associate this frame's detections with tracks and return the track id
+    /// for each detection, in the same order.
+    pub fn update(&mut self, detections: &[(core::Rect, Option<&[f32]>)]) -> Vec<u64> {
+        let mut candidates = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (d, (bbox, embedding)) in detections.iter().enumerate() {
+                let score = self.score(track, bbox, *embedding);
+                if score >= self.config.min_score {
+                    candidates.push((score, t, d));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut assigned: Vec<Option<usize>> = vec![None; detections.len()];
+        let mut track_used = vec![false; self.tracks.len()];
+        for (_, t, d) in candidates {
+            if assigned[d].is_none() && !track_used[t] {
+                assigned[d] = Some(t);
+                track_used[t] = true;
+            }
+        }
+
+        for (t, used) in track_used.iter().enumerate() {
+            if !used {
+                self.tracks[t].missed += 1;
+            }
+        }
+
+        let mut ids = Vec::with_capacity(detections.len());
+        for (d, (bbox, embedding)) in detections.iter().enumerate() {
+            let id = match assigned[d] {
+                Some(t) => {
+                    let track = &mut self.tracks[t];
+                    track.bbox = *bbox;
+                    track.missed = 0;
+                    if let Some(embedding) = embedding {
+                        track.embedding = Some(embedding.to_vec());
+                    }
+                    track.id
+                }
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.tracks.push(Track {
+                        id,
+                        bbox: *bbox,
+                        embedding: embedding.map(|e| e.to_vec()),
+                        missed: 0,
+                        blinks: BlinkCounter::default(),
+                    });
+                    id
+                }
+            };
+            ids.push(id);
+        }
+
+        let max_missed = self.config.max_missed_frames;
+        self.tracks.retain(|track| track.missed <= max_missed);
+        ids
+    }
+
+    /// Convenience wrapper that tracks analyzed faces directly, feeding each
+    /// face's eye aspect ratio to its track's blink counter.
+    pub fn track(
+        &mut self,
+        faces: Vec<(core::Rect, FaceAttributes)>,
+        embeddings: &[Option<Vec<f32>>],
+    ) -> Vec<TrackedFace> {
+        let detections: Vec<(core::Rect, Option<&[f32]>)> = faces
+            .iter()
+            .enumerate()
+            .map(|(i, (bbox, _))| (*bbox, embeddings.get(i).and_then(|e| e.as_deref())))
+            .collect();
+        let ids = self.update(&detections);
+
+        faces
+            .into_iter()
+            .zip(ids)
+            .map(|((bbox, attributes), track_id)| {
+                let blinks = self.count_blinks(track_id, &attributes);
+                TrackedFace { track_id, bbox, attributes, quality: None, blinks }
+            })
+            .collect()
+    }
+
+    fn count_blinks(&mut self, track_id: u64, attributes: &FaceAttributes) -> u32 {
+        let Some(track) = self.tracks.iter_mut().find(|track| track.id == track_id) else {
+            return 0;
+        };
+        if let Some(eye_state) = &attributes.eye_state {
+            track.blinks.update(eye_state.ear);
+        }
+        track.blinks.blinks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32) -> core::Rect {
+        core::Rect::new(x, y, 50, 50)
+    }
+
+    #[test]
+    fn test_ids_persist_while_faces_move() {
+        let mut tracker = Tracker::new(TrackerConfig::default());
+
+        let first = tracker.update(&[(rect(0, 0), None), (rect(200, 0), None)]);
+        let second = tracker.update(&[(rect(205, 2), None), (rect(4, 3), None)]);
+        assert_eq!(second, vec![first[1], first[0]]);
+
+        let third = tracker.update(&[(rect(8, 5), None), (rect(400, 0), None)]);
+        assert_eq!(third[0], first[0]);
+        assert!(!first.contains(&third[1]));
+    }
+
+    #[test]
+    fn test_embeddings_keep_identity_when_boxes_cross() {
+        let mut tracker = Tracker::new(TrackerConfig { iou_weight: 0.3, embedding_weight: 0.7, ..Default::default() });
+        let alice = [1.0f32, 0.0, 0.0];
+        let bob = [0.0f32, 1.0, 0.0];
+
+        let first = tracker.update(&[(rect(100, 0), Some(&alice[..])), (rect(130, 0), Some(&bob[..]))]);
+        // The faces have swapped positions; overlap alone would swap the ids
+        let second = tracker.update(&[(rect(130, 0), Some(&alice[..])), (rect(100, 0), Some(&bob[..]))]);
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn test_blinks_are_counted_per_track() {
+        use crate::attributes::eyes::EyeState;
+
+        let mut tracker = Tracker::new(TrackerConfig::default());
+        let face = |x, ear| {
+            let mut attributes = FaceAttributes::new(30.0, "female".to_string());
+            attributes.eye_state = Some(EyeState { left_eye_open: ear > 0.2, right_eye_open: ear > 0.2, ear });
+            (rect(x, 0), attributes)
+        };
+
+        let mut blinks = Vec::new();
+        for (left_ear, right_ear) in [(0.3, 0.3), (0.1, 0.3), (0.1, 0.3), (0.3, 0.3)] {
+            let tracked = tracker.track(vec![face(0, left_ear), face(300, right_ear)], &[]);
+            blinks = tracked.iter().map(|face| face.blinks).collect();
+        }
+        assert_eq!(blinks, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_stale_tracks_expire() {
+        let mut tracker = Tracker::new(TrackerConfig { max_missed_frames: 1, ..Default::default() });
+        let first = tracker.update(&[(rect(0, 0), None)]);
+        tracker.update(&[]);
+        tracker.update(&[]);
+        let later = tracker.update(&[(rect(0, 0), None)]);
+        assert_ne!(later, first);
+    }
+}
@@ -1,4 +1,4 @@
-use opencv::{prelude::*, videoio, Result};
+use opencv::{core, imgproc, prelude::*, videoio, Result};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -11,6 +11,7 @@ pub struct VideoConfig {
     pub end_time: Option<f64>,    // End time in seconds
     pub resize_width: Option<i32>,
     pub resize_height: Option<i32>,
+    pub frame_stride: Option<usize>,  // Forward every Nth frame; skipped frames are grabbed, not decoded
 }
 
 impl Default for VideoConfig {
@@ -21,6 +22,7 @@ impl Default for VideoConfig {
             end_time: None,
             resize_width: None,
             resize_height: None,
+            frame_stride: None,
         }
     }
 }
@@ -80,20 +82,24 @@ impl VideoProcessor {
         })
     }
 
+    /// Decode frames on the calling thread and send them to `tx`, blocking
+    /// while the channel is full so every frame reaches the consumer. Must
+    /// not be called from within an async runtime.
     pub fn process_video(
         mut self,
         tx: mpsc::Sender<Mat>,
         running: Arc<Mutex<bool>>,
     ) -> anyhow::Result<()> {
-        println!("Starting video processing...");
+        log::info!("Starting video processing...");
 
         let start_frame = (self.config.start_time.unwrap_or(0.0) * self.info.fps) as i64;
         let end_frame = self.config.end_time
             .map(|t| (t * self.info.fps) as i64)
             .unwrap_or(self.info.frame_count);
         let total_frames = end_frame - start_frame;
+        let stride = self.stride();
 
-        let progress = ProgressBar::new(total_frames as u64);
+        let progress = ProgressBar::new(forwarded_frame_count(total_frames, stride));
         progress.set_style(ProgressStyle::default_bar()
             .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} frames ({percent}%) {msg}")
             .unwrap()
@@ -136,19 +142,46 @@ impl VideoProcessor {
                 frame = resized;
             }
 
-            // Send frame through channel
-            if tx.try_send(frame).is_err() {
-                println!("Frame processing is too slow, dropping frame");
+            // Wait for room rather than dropping frames, so a slow consumer
+            // slows decoding down instead of losing frames. The consumer
+            // hanging up ends decoding.
+            if tx.blocking_send(frame).is_err() {
+                log::info!("Frame consumer stopped, ending video processing");
+                break;
             }
 
             frame_count += 1;
             progress.inc(1);
+
+            // Skip ahead without decoding the frames in between
+            for _ in 1..stride {
+                if frame_count >= total_frames || !self.capture.grab()? {
+                    break;
+                }
+                frame_count += 1;
+            }
         }
 
         progress.finish_with_message("Video processing complete");
         Ok(())
     }
 
+    pub fn info(&self) -> &VideoInfo {
+        &self.info
+    }
+
+    /// Frame rate of the emitted frames: the target rate when throttled,
+    /// otherwise the source rate divided by the stride.
+    pub fn output_fps(&self) -> f64 {
+        self.config
+            .target_fps
+            .unwrap_or(self.info.fps / self.stride() as f64)
+    }
+
+    fn stride(&self) -> usize {
+        self.config.frame_stride.unwrap_or(1).max(1)
+    }
+
     pub fn get_video_info(&self) -> String {
         format!(
             "Video Info:\n  Resolution: {}x{}\n  FPS: {:.2}\n  Duration: {:.2}s\n  Total Frames: {}",
@@ -161,6 +194,81 @@ impl VideoProcessor {
     }
 }
 
+/// Writes frames to a video file. Frames whose size differs from the size
+/// the sink was opened with are resized, since `VideoWriter` silently drops
+/// mismatched frames.
+pub struct VideoSink {
+    writer: videoio::VideoWriter,
+    frame_size: core::Size,
+    frames_written: u64,
+}
+
+impl VideoSink {
+    pub const DEFAULT_FOURCC: [char; 4] = ['m', 'p', '4', 'v'];
+
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        fourcc: [char; 4],
+        fps: f64,
+        frame_size: core::Size,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if fps <= 0.0 || !fps.is_finite() {
+            return Err(anyhow::anyhow!("Invalid output frame rate: {}", fps));
+        }
+
+        let code = videoio::VideoWriter::fourcc(fourcc[0], fourcc[1], fourcc[2], fourcc[3])?;
+        let writer = videoio::VideoWriter::new(&path.to_string_lossy(), code, fps, frame_size, true)?;
+        if !writer.is_opened()? {
+            return Err(anyhow::anyhow!("Failed to open video writer for {}", path.display()));
+        }
+
+        Ok(Self {
+            writer,
+            frame_size,
+            frames_written: 0,
+        })
+    }
+
+    pub fn write(&mut self, frame: &Mat) -> anyhow::Result<()> {
+        if frame.size()? == self.frame_size {
+            self.writer.write(frame)?;
+        } else {
+            let mut resized = Mat::default();
+            imgproc::resize(frame, &mut resized, self.frame_size, 0.0, 0.0, imgproc::INTER_LINEAR)?;
+            self.writer.write(&resized)?;
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Flush and close the file so the container index is written and the
+    /// output is playable.
+    pub fn finish(mut self) -> anyhow::Result<u64> {
+        self.writer.release()?;
+        Ok(self.frames_written)
+    }
+}
+
+impl Drop for VideoSink {
+    fn drop(&mut self) {
+        if self.writer.is_opened().unwrap_or(false) {
+            let _ = self.writer.release();
+        }
+    }
+}
+
+/// Number of frames forwarded when every `stride`th of `total_frames` is kept.
+fn forwarded_frame_count(total_frames: i64, stride: usize) -> u64 {
+    let total = total_frames.max(0) as u64;
+    let stride = stride.max(1) as u64;
+    (total + stride - 1) / stride
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,6 +290,37 @@ mod tests {
         assert!(config.end_time.is_none());
         assert!(config.resize_width.is_none());
         assert!(config.resize_height.is_none());
+        assert!(config.frame_stride.is_none());
+    }
+
+    #[test]
+    fn test_forwarded_frame_count_accounts_for_stride() {
+        assert_eq!(forwarded_frame_count(300, 1), 300);
+        assert_eq!(forwarded_frame_count(300, 10), 30);
+        assert_eq!(forwarded_frame_count(301, 10), 31);
+        assert_eq!(forwarded_frame_count(5, 0), 5);
+        assert_eq!(forwarded_frame_count(-1, 3), 0);
+    }
+
+    #[test]
+    fn test_video_sink_writes_playable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.avi");
+        let size = core::Size::new(64, 48);
+
+        let mut sink = VideoSink::new(&path, ['M', 'J', 'P', 'G'], 10.0, size).unwrap();
+        let frame = Mat::new_size_with_default(size, core::CV_8UC3, core::Scalar::all(128.0)).unwrap();
+        let larger = Mat::new_size_with_default(core::Size::new(128, 96), core::CV_8UC3, core::Scalar::all(64.0)).unwrap();
+        for _ in 0..4 {
+            sink.write(&frame).unwrap();
+        }
+        sink.write(&larger).unwrap();
+        assert_eq!(sink.finish().unwrap(), 5);
+
+        let processor = VideoProcessor::new(&path, VideoConfig::default()).unwrap();
+        assert_eq!(processor.info().width, 64);
+        assert_eq!(processor.info().frame_count, 5);
+        assert!((processor.info().fps - 10.0).abs() < 0.5);
     }
 
     #[test]
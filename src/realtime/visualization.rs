@@ -3,22 +3,62 @@ use opencv::{
     highgui,
     imgproc,
     prelude::*,
-    types::VectorOfPoint,
+    types::{VectorOfPoint, VectorOfVec6f},
 };
 use crate::face::FaceAttributes;
+use crate::processing::quality::QualityMetrics;
+use crate::realtime::tracking::TrackedFace;
+use crate::security::anonymization::{AnonymizationMethod, Anonymizer};
 use crate::attributes::{
     landmarks::FacialLandmarks,
     pose::HeadPose,
 };
 use anyhow::Result;
 
+/// An RGB color; converted to OpenCV's BGR order when drawing.
+pub type Rgb = (u8, u8, u8);
+
+fn scalar(color: Rgb) -> core::Scalar {
+    core::Scalar::new(color.2 as f64, color.1 as f64, color.0 as f64, 0.0)
+}
+
+/// Badge color for a quality score: green when reliable, yellow when
+/// marginal, red when poor.
+pub fn quality_color(overall_score: f32) -> Rgb {
+    if overall_score >= 0.7 {
+        (0, 200, 0)
+    } else if overall_score >= 0.4 {
+        (230, 200, 0)
+    } else {
+        (220, 0, 0)
+    }
+}
+
+/// One face to draw: its box and attributes plus optional extras.
+pub struct FaceOverlay<'a> {
+    pub bbox: &'a core::Rect,
+    pub attributes: &'a FaceAttributes,
+    pub track_id: Option<u64>,
+    pub quality: Option<&'a QualityMetrics>,
+}
+
 pub struct VisualizationConfig {
     pub show_bounding_box: bool,
     pub show_landmarks: bool,
+    pub show_mesh: bool,
     pub show_pose: bool,
     pub show_attributes: bool,
+    pub show_quality: bool,
+    pub anonymize_faces: bool,  // Redact each face before drawing overlays
     pub font_scale: f64,
     pub line_thickness: i32,
+    pub headless: bool,  // Draw without opening a window, e.g. on servers with no display
+    pub bbox_color: Rgb,
+    pub landmark_color: Option<Rgb>,  // None colors jaw, eyes, nose and mouth separately
+    pub mesh_color: Rgb,
+    pub track_id_color: Rgb,
+    pub text_color: Rgb,
+    pub text_bg_color: Rgb,
 }
 
 impl Default for VisualizationConfig {
@@ -26,10 +66,20 @@ impl Default for VisualizationConfig {
         Self {
             show_bounding_box: true,
             show_landmarks: true,
+            show_mesh: false,
             show_pose: true,
             show_attributes: true,
+            show_quality: true,
+            anonymize_faces: false,
             font_scale: 0.5,
             line_thickness: 2,
+            headless: false,
+            bbox_color: (0, 255, 0),
+            landmark_color: None,
+            mesh_color: (0, 255, 255),
+            track_id_color: (255, 255, 0),
+            text_color: (255, 255, 255),
+            text_bg_color: (0, 0, 0),
         }
     }
 }
@@ -37,31 +87,111 @@ impl Default for VisualizationConfig {
 pub struct Visualizer {
     config: VisualizationConfig,
     window_name: String,
+    anonymizer: Anonymizer,
 }
 
 impl Visualizer {
     pub fn new(window_name: &str, config: VisualizationConfig) -> Self {
-        highgui::named_window(window_name, highgui::WINDOW_AUTOSIZE).unwrap();
+        if !config.headless {
+            highgui::named_window(window_name, highgui::WINDOW_AUTOSIZE).unwrap();
+        }
         Self {
             config,
             window_name: window_name.to_string(),
+            anonymizer: Anonymizer::new(AnonymizationMethod::Blur { kernel_size: 51 }),
         }
     }
 
+    /// Replace the default blur used when anonymization is toggled on.
+    pub fn with_anonymizer(mut self, anonymizer: Anonymizer) -> Self {
+        self.anonymizer = anonymizer;
+        self
+    }
+
     pub fn display_frame(&self, frame: &Mat, faces: &[(core::Rect, FaceAttributes)]) -> Result<()> {
+        let display = self.render_frame(frame, faces)?;
+        self.show(&display)
+    }
+
+    /// A visualizer that only draws; `show` and key handling are no-ops.
+    pub fn headless(config: VisualizationConfig) -> Self {
+        Self::new("", VisualizationConfig { headless: true, ..config })
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.config.headless
+    }
+
+    /// Show an already rendered frame in the window.
+    pub fn show(&self, rendered: &Mat) -> Result<()> {
+        if self.config.headless {
+            return Ok(());
+        }
+        highgui::imshow(&self.window_name, rendered)?;
+        Ok(())
+    }
+
+    /// Draw the enabled overlays onto a copy of `frame` without displaying
+    /// it. Works on headless visualizers.
+    pub fn annotate_frame(&self, frame: &Mat, faces: &[(core::Rect, FaceAttributes)]) -> Result<Mat> {
+        self.render_frame(frame, faces)
+    }
+
+    /// Draw the enabled overlays onto a copy of `frame`.
+    pub fn render_frame(&self, frame: &Mat, faces: &[(core::Rect, FaceAttributes)]) -> Result<Mat> {
+        let overlays: Vec<FaceOverlay> = faces
+            .iter()
+            .map(|(bbox, attributes)| FaceOverlay { bbox, attributes, track_id: None, quality: None })
+            .collect();
+        self.render_overlays(frame, &overlays)
+    }
+
+    /// Like `render_frame`, but labels each box with its track ID and, when
+    /// assessed, its quality badge.
+    pub fn render_tracked(&self, frame: &Mat, faces: &[TrackedFace]) -> Result<Mat> {
+        let overlays: Vec<FaceOverlay> = faces
+            .iter()
+            .map(|face| FaceOverlay {
+                bbox: &face.bbox,
+                attributes: &face.attributes,
+                track_id: Some(face.track_id),
+                quality: face.quality.as_ref(),
+            })
+            .collect();
+        self.render_overlays(frame, &overlays)
+    }
+
+    pub fn render_overlays(&self, frame: &Mat, faces: &[FaceOverlay]) -> Result<Mat> {
         let mut display = frame.clone();
 
-        for (bbox, attributes) in faces {
+        if self.config.anonymize_faces {
+            for face in faces {
+                display = self.anonymizer.anonymize(&display, *face.bbox)?;
+            }
+        }
+
+        for face in faces {
+            let (bbox, attributes) = (face.bbox, face.attributes);
             if self.config.show_bounding_box {
                 self.draw_bounding_box(&mut display, bbox)?;
             }
 
+            if let Some(track_id) = face.track_id {
+                self.draw_track_id(&mut display, bbox, track_id)?;
+            }
+
             if self.config.show_landmarks {
                 if let Some(landmarks) = &attributes.landmarks {
                     self.draw_landmarks(&mut display, landmarks)?;
                 }
             }
 
+            if self.config.show_mesh {
+                if let Some(landmarks) = &attributes.landmarks {
+                    self.draw_mesh(&mut display, landmarks)?;
+                }
+            }
+
             if self.config.show_pose {
                 if let Some(pose_est) = &attributes.pose {
                     self.draw_head_pose(&mut display, bbox, &pose_est.head_pose)?;
@@ -69,19 +199,19 @@ impl Visualizer {
             }
 
             if self.config.show_attributes {
-                self.draw_attributes(&mut display, bbox, attributes)?;
+                let quality = face.quality.filter(|_| self.config.show_quality);
+                self.draw_attributes(&mut display, bbox, attributes, quality)?;
             }
         }
 
-        highgui::imshow(&self.window_name, &display)?;
-        Ok(())
+        Ok(display)
     }
 
     fn draw_bounding_box(&self, image: &mut Mat, bbox: &core::Rect) -> Result<()> {
         imgproc::rectangle(
             image,
             *bbox,
-            core::Scalar::new(0.0, 255.0, 0.0, 0.0),
+            scalar(self.config.bbox_color),
             self.config.line_thickness,
             imgproc::LINE_8,
             0,
@@ -90,6 +220,8 @@ impl Visualizer {
     }
 
     fn draw_landmarks(&self, image: &mut Mat, landmarks: &FacialLandmarks) -> Result<()> {
+        let color = |default: Rgb| scalar(self.config.landmark_color.unwrap_or(default));
+
         // Draw face outline
         let jaw_points: Vec<core::Point> = landmarks.jaw_line.iter()
             .map(|p| core::Point::new(p.x as i32, p.y as i32))
@@ -99,7 +231,7 @@ impl Visualizer {
             image,
             &jaw_line,
             false,
-            core::Scalar::new(255.0, 0.0, 0.0, 0.0),
+            color((0, 0, 255)),
             self.config.line_thickness,
             imgproc::LINE_8,
             0,
@@ -115,7 +247,7 @@ impl Visualizer {
                 image,
                 &eye_line,
                 true,
-                core::Scalar::new(0.0, 255.0, 255.0, 0.0),
+                color((255, 255, 0)),
                 self.config.line_thickness,
                 imgproc::LINE_8,
                 0,
@@ -131,7 +263,7 @@ impl Visualizer {
             image,
             &nose_line,
             false,
-            core::Scalar::new(0.0, 255.0, 0.0, 0.0),
+            color((0, 255, 0)),
             self.config.line_thickness,
             imgproc::LINE_8,
             0,
@@ -146,7 +278,7 @@ impl Visualizer {
             image,
             &mouth_line,
             true,
-            core::Scalar::new(0.0, 0.0, 255.0, 0.0),
+            color((255, 0, 0)),
             self.config.line_thickness,
             imgproc::LINE_8,
             0,
@@ -155,6 +287,27 @@ impl Visualizer {
         Ok(())
     }
 
+    fn draw_mesh(&self, image: &mut Mat, landmarks: &FacialLandmarks) -> Result<()> {
+        let color = scalar(self.config.mesh_color);
+
+        for triangle in triangulate_landmarks(landmarks)? {
+            let points = VectorOfPoint::from_iter(
+                triangle.iter().map(|p| core::Point::new(p.x as i32, p.y as i32))
+            );
+            imgproc::polylines(
+                image,
+                &points,
+                true,
+                color,
+                1,
+                imgproc::LINE_AA,
+                0,
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn draw_head_pose(&self, image: &mut Mat, bbox: &core::Rect, pose: &HeadPose) -> Result<()> {
         let center = core::Point::new(
             bbox.x + bbox.width / 2,
@@ -199,11 +352,70 @@ impl Visualizer {
         Ok(())
     }
 
-    fn draw_attributes(&self, image: &mut Mat, bbox: &core::Rect, attrs: &FaceAttributes) -> Result<()> {
+    fn draw_track_id(&self, image: &mut Mat, bbox: &core::Rect, track_id: u64) -> Result<()> {
+        imgproc::put_text(
+            image,
+            &format!("ID {}", track_id),
+            core::Point::new(bbox.x, (bbox.y - 6).max(12)),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            self.config.font_scale * 1.2,
+            scalar(self.config.track_id_color),
+            self.config.line_thickness,
+            imgproc::LINE_8,
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn draw_quality_badge(&self, image: &mut Mat, bbox: &core::Rect, quality: &QualityMetrics) -> Result<()> {
+        let text = format!("{:.0}%", quality.overall_score * 100.0);
+        let font = imgproc::FONT_HERSHEY_SIMPLEX;
+        let mut baseline = 0;
+        let size = imgproc::get_text_size(&text, font, self.config.font_scale, 1, &mut baseline)?;
+
+        let badge = core::Rect::new(
+            bbox.x + bbox.width - size.width - 6,
+            bbox.y,
+            size.width + 6,
+            size.height + baseline + 6,
+        );
+        imgproc::rectangle(
+            image,
+            badge,
+            scalar(quality_color(quality.overall_score)),
+            -1,
+            imgproc::LINE_8,
+            0,
+        )?;
+        imgproc::put_text(
+            image,
+            &text,
+            core::Point::new(badge.x + 3, badge.y + size.height + 3),
+            font,
+            self.config.font_scale,
+            scalar(self.config.text_color),
+            1,
+            imgproc::LINE_8,
+            false,
+        )?;
+        Ok(())
+    }
+
+    fn draw_attributes(
+        &self,
+        image: &mut Mat,
+        bbox: &core::Rect,
+        attrs: &FaceAttributes,
+        quality: Option<&QualityMetrics>,
+    ) -> Result<()> {
+        if let Some(quality) = quality {
+            self.draw_quality_badge(image, bbox, quality)?;
+        }
+
         let mut y_offset = 0;
         let line_height = 20;
-        let text_color = core::Scalar::new(255.0, 255.0, 255.0, 0.0);
-        let bg_color = core::Scalar::new(0.0, 0.0, 0.0, 0.0);
+        let text_color = scalar(self.config.text_color);
+        let bg_color = scalar(self.config.text_bg_color);
 
         // Helper function to draw text with background
         let mut draw_text = |text: &str, y_pos: i32| -> Result<()> {
@@ -280,6 +492,9 @@ impl Visualizer {
     }
 
     pub fn handle_key_events(&mut self) -> Result<bool> {
+        if self.config.headless {
+            return Ok(true);
+        }
         let key = highgui::wait_key(1)?;
         match key as u8 as char {
             'q' => Ok(false),
@@ -291,6 +506,10 @@ impl Visualizer {
                 self.config.show_landmarks = !self.config.show_landmarks;
                 Ok(true)
             }
+            'm' => {
+                self.config.show_mesh = !self.config.show_mesh;
+                Ok(true)
+            }
             'p' => {
                 self.config.show_pose = !self.config.show_pose;
                 Ok(true)
@@ -299,11 +518,151 @@ impl Visualizer {
                 self.config.show_attributes = !self.config.show_attributes;
                 Ok(true)
             }
+            'k' => {
+                self.config.show_quality = !self.config.show_quality;
+                Ok(true)
+            }
+            'x' => {
+                self.config.anonymize_faces = !self.config.anonymize_faces;
+                Ok(true)
+            }
             _ => Ok(true)
         }
     }
 
     pub fn cleanup(&self) {
+        if self.config.headless {
+            return;
+        }
         highgui::destroy_window(&self.window_name).ok();
     }
-} 
\ No newline at end of file
+}
+
+/// Delaunay triangulation of all landmark points. Triangles that touch the
+/// virtual outer vertices used internally by `Subdiv2D` are discarded.
+pub fn triangulate_landmarks(landmarks: &FacialLandmarks) -> Result<Vec<[core::Point2f; 3]>> {
+    let points = landmarks.points();
+    if points.len() < 3 {
+        return Ok(Vec::new());
+    }
+
+    let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+    let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+    for p in &points {
+        min_x = min_x.min(p.x);
+        min_y = min_y.min(p.y);
+        max_x = max_x.max(p.x);
+        max_y = max_y.max(p.y);
+    }
+
+    let bounds = core::Rect::new(
+        min_x.floor() as i32 - 1,
+        min_y.floor() as i32 - 1,
+        (max_x - min_x).ceil() as i32 + 3,
+        (max_y - min_y).ceil() as i32 + 3,
+    );
+    let mut subdiv = imgproc::Subdiv2D::new(bounds)?;
+    for p in &points {
+        subdiv.insert(core::Point2f::new(p.x, p.y))?;
+    }
+
+    let mut triangle_list = VectorOfVec6f::new();
+    subdiv.get_triangle_list(&mut triangle_list)?;
+
+    let inside = |p: &core::Point2f| {
+        p.x >= bounds.x as f32
+            && p.y >= bounds.y as f32
+            && p.x <= (bounds.x + bounds.width) as f32
+            && p.y <= (bounds.y + bounds.height) as f32
+    };
+
+    Ok(triangle_list
+        .iter()
+        .map(|t| [
+            core::Point2f::new(t[0], t[1]),
+            core::Point2f::new(t[2], t[3]),
+            core::Point2f::new(t[4], t[5]),
+        ])
+        .filter(|triangle| triangle.iter().all(inside))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::landmarks::FacialLandmark;
+
+    fn landmark(x: f32, y: f32) -> FacialLandmark {
+        FacialLandmark { x, y, confidence: 1.0 }
+    }
+
+    #[test]
+    fn test_triangulate_square_with_center() {
+        // Four hull points plus one interior point: 2n - 2 - h = 4 triangles
+        let landmarks = FacialLandmarks {
+            jaw_line: vec![landmark(0.0, 0.0), landmark(100.0, 0.0)],
+            left_eye: vec![landmark(100.0, 100.0)],
+            right_eye: vec![landmark(0.0, 100.0)],
+            left_eyebrow: vec![],
+            right_eyebrow: vec![],
+            nose_bridge: vec![],
+            nose_tip: landmark(50.0, 50.0),
+            outer_lips: vec![],
+            inner_lips: vec![],
+        };
+
+        let triangles = triangulate_landmarks(&landmarks).unwrap();
+        assert_eq!(triangles.len(), 4);
+    }
+
+    fn lit_pixels(image: &Mat) -> i32 {
+        let mut gray = Mat::default();
+        imgproc::cvt_color(image, &mut gray, imgproc::COLOR_BGR2GRAY, 0).unwrap();
+        core::count_non_zero(&gray).unwrap()
+    }
+
+    #[test]
+    fn test_quality_color_bands() {
+        assert_eq!(quality_color(0.9), (0, 200, 0));
+        assert_eq!(quality_color(0.5), (230, 200, 0));
+        assert_eq!(quality_color(0.1), (220, 0, 0));
+    }
+
+    #[test]
+    fn test_colors_are_converted_to_bgr() {
+        let color = scalar((255, 128, 0));
+        assert_eq!((color[0], color[1], color[2]), (0.0, 128.0, 255.0));
+    }
+
+    #[test]
+    fn test_headless_visualizer_draws_without_window() {
+        let mut visualizer = Visualizer::headless(VisualizationConfig {
+            show_attributes: false,
+            ..Default::default()
+        });
+        let frame = Mat::new_rows_cols_with_default(120, 120, core::CV_8UC3, core::Scalar::all(0.0)).unwrap();
+        let attributes = FaceAttributes {
+            age: 30.0,
+            gender: "female".to_string(),
+            emotion: None,
+            landmarks: None,
+            pose: None,
+            ethnicity: None,
+            liveness: None,
+            mask: None,
+            accessories: None,
+            eye_state: None,
+            smile_intensity: None,
+            unreliable: Vec::new(),
+        };
+
+        let annotated = visualizer
+            .annotate_frame(&frame, &[(core::Rect::new(20, 20, 60, 60), attributes)])
+            .unwrap();
+        visualizer.show(&annotated).unwrap();
+        assert!(visualizer.handle_key_events().unwrap());
+
+        assert!(lit_pixels(&annotated) > 0);
+        assert_eq!(lit_pixels(&frame), 0);
+    }
+}
@@ -9,6 +9,8 @@ pub struct WebcamConfig {
     pub width: i32,
     pub height: i32,
     pub fps: f64,
+    pub warmup_frames: usize,               // Frames discarded while auto-exposure settles
+    pub warmup_duration: Option<Duration>,  // Keep discarding until this much time has passed
 }
 
 impl Default for WebcamConfig {
@@ -18,12 +20,31 @@ impl Default for WebcamConfig {
             width: 640,
             height: 480,
             fps: 30.0,
+            warmup_frames: 10,
+            warmup_duration: None,
         }
     }
 }
 
+/// Anything frames can be read from. Implemented for `VideoCapture`; tests
+/// substitute a scripted source.
+pub trait FrameSource: Send {
+    fn read_frame(&mut self, frame: &mut Mat) -> Result<bool>;
+    fn get(&self, property: i32) -> Result<f64>;
+}
+
+impl FrameSource for videoio::VideoCapture {
+    fn read_frame(&mut self, frame: &mut Mat) -> Result<bool> {
+        self.read(frame)
+    }
+
+    fn get(&self, property: i32) -> Result<f64> {
+        VideoCaptureTraitConst::get(self, property)
+    }
+}
+
 pub struct WebcamCapture {
-    camera: videoio::VideoCapture,
+    camera: Box<dyn FrameSource>,
     config: WebcamConfig,
     frame_time: Duration,
     last_frame: Instant,
@@ -42,12 +63,49 @@ impl WebcamCapture {
             return Err(opencv::Error::new(0, format!("Failed to open camera device {}", config.device_id)));
         }
 
-        Ok(Self {
+        Ok(Self::from_source(Box::new(camera), config))
+    }
+
+    pub fn from_source(camera: Box<dyn FrameSource>, config: WebcamConfig) -> Self {
+        Self {
             camera,
-            config,
             frame_time: Duration::from_secs_f64(1.0 / config.fps),
+            config,
             last_frame: Instant::now(),
-        })
+        }
+    }
+
+    /// Read and discard frames until both `warmup_frames` have been consumed
+    /// and `warmup_duration` (if set) has elapsed. Returns the number of
+    /// frames discarded.
+    fn warm_up(&mut self) -> Result<usize> {
+        let started = Instant::now();
+        let mut frame = Mat::default();
+        let mut discarded = 0;
+        let mut failed_reads = 0;
+
+        loop {
+            let frames_done = discarded >= self.config.warmup_frames;
+            let time_done = self
+                .config
+                .warmup_duration
+                .map_or(true, |duration| started.elapsed() >= duration);
+            if frames_done && time_done {
+                break;
+            }
+
+            if self.camera.read_frame(&mut frame)? && !frame.empty() {
+                discarded += 1;
+            } else {
+                // Give up rather than spin forever on a camera that never delivers
+                failed_reads += 1;
+                if failed_reads > self.config.warmup_frames.max(1) * 10 {
+                    break;
+                }
+            }
+        }
+
+        Ok(discarded)
     }
 
     pub fn start_capture(
@@ -55,8 +113,9 @@ impl WebcamCapture {
         tx: mpsc::Sender<Mat>,
         running: Arc<Mutex<bool>>,
     ) -> anyhow::Result<()> {
-        println!("Starting webcam capture...");
-        
+        let discarded = self.warm_up()?;
+        log::info!("Starting webcam capture after discarding {} warmup frames...", discarded);
+
         while *running.lock().unwrap() {
             // Maintain frame rate
             let elapsed = self.last_frame.elapsed();
@@ -67,23 +126,23 @@ impl WebcamCapture {
 
             // Capture frame
             let mut frame = Mat::default();
-            if !self.camera.read(&mut frame)? {
-                println!("Failed to read frame from camera");
+            if !self.camera.read_frame(&mut frame)? {
+                log::warn!("Failed to read frame from camera");
                 continue;
             }
 
             if frame.empty() {
-                println!("Empty frame received from camera");
+                log::warn!("Empty frame received from camera");
                 continue;
             }
 
             // Send frame through channel
             if tx.try_send(frame).is_err() {
-                println!("Frame processing is too slow, dropping frame");
+                log::warn!("Frame processing is too slow, dropping frame");
             }
         }
 
-        println!("Stopping webcam capture...");
+        log::info!("Stopping webcam capture...");
         Ok(())
     }
 
@@ -113,6 +172,54 @@ mod tests {
         assert_eq!(config.width, 640);
         assert_eq!(config.height, 480);
         assert_eq!(config.fps, 30.0);
+        assert_eq!(config.warmup_frames, 10);
+    }
+
+    /// Emits frames whose single pixel holds the frame index, and stops the
+    /// capture loop once it runs out.
+    struct ScriptedSource {
+        next: u8,
+        total: u8,
+        running: Arc<Mutex<bool>>,
+    }
+
+    impl FrameSource for ScriptedSource {
+        fn read_frame(&mut self, frame: &mut Mat) -> Result<bool> {
+            if self.next >= self.total {
+                *self.running.lock().unwrap() = false;
+                return Ok(false);
+            }
+            *frame = Mat::new_rows_cols_with_default(
+                1,
+                1,
+                opencv::core::CV_8UC1,
+                opencv::core::Scalar::all(self.next as f64),
+            )?;
+            self.next += 1;
+            Ok(true)
+        }
+
+        fn get(&self, _property: i32) -> Result<f64> {
+            Ok(0.0)
+        }
+    }
+
+    #[test]
+    fn test_warmup_frames_are_discarded() {
+        let running = Arc::new(Mutex::new(true));
+        let source = ScriptedSource { next: 0, total: 6, running: running.clone() };
+        let config = WebcamConfig { fps: 1000.0, warmup_frames: 4, ..Default::default() };
+        let (tx, mut rx) = mpsc::channel(16);
+
+        WebcamCapture::from_source(Box::new(source), config)
+            .start_capture(tx, running)
+            .unwrap();
+
+        let mut emitted = Vec::new();
+        while let Ok(frame) = rx.try_recv() {
+            emitted.push(*frame.at_2d::<u8>(0, 0).unwrap());
+        }
+        assert_eq!(emitted, vec![4, 5]);
     }
 
     #[test]
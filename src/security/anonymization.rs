@@ -1,13 +1,19 @@
 use opencv::{
     core,
+    imgcodecs,
     imgproc,
     prelude::*,
     types,
 };
 use anyhow::Result;
 
+use crate::common::types::clamp_rect_to_image;
+use crate::database::embeddings::{EmbeddingComparator, FaceEmbedding, Metric};
+use crate::processing::detectors::DetectionResult;
+
 pub enum AnonymizationMethod {
     Blur { kernel_size: i32 },
+    OvalBlur { kernel_size: i32, feather: i32 },  // Blur inside the inscribed ellipse, edge softened over `feather` px
     Pixelate { block_size: i32 },
     BlackOut,
     Emoji { emoji_path: String },
@@ -17,6 +23,31 @@ pub struct Anonymizer {
     method: AnonymizationMethod,
 }
 
+/// Identities that have consented to being shown. Faces whose embedding
+/// matches an allowlisted face above `threshold` are left visible.
+pub struct ConsentFilter {
+    allowlist: Vec<FaceEmbedding>,
+    threshold: f32,
+}
+
+impl ConsentFilter {
+    pub fn new(allowlist: Vec<FaceEmbedding>, threshold: f32) -> Self {
+        Self { allowlist, threshold }
+    }
+
+    /// Id of the allowlisted face this embedding matches, if any.
+    pub fn matching_identity(&self, embedding: &[f32]) -> Option<String> {
+        EmbeddingComparator::find_matches(embedding, &self.allowlist, self.threshold, Metric::Cosine)
+            .into_iter()
+            .next()
+            .map(|(face_id, _)| face_id)
+    }
+
+    pub fn is_consenting(&self, embedding: &[f32]) -> bool {
+        self.matching_identity(embedding).is_some()
+    }
+}
+
 impl Anonymizer {
     pub fn new(method: AnonymizationMethod) -> Self {
         Self { method }
@@ -24,20 +55,42 @@ impl Anonymizer {
 
     pub fn anonymize(&self, image: &Mat, face_rect: core::Rect) -> Result<Mat> {
         let mut output = image.clone();
-        let roi = Mat::roi(&output, face_rect)?;
+        let face_rect = clamp_rect_to_image(face_rect, image.cols(), image.rows());
+        if face_rect.area() == 0 {
+            return Ok(output);
+        }
 
+        if let AnonymizationMethod::BlackOut = &self.method {
+            let color = core::Scalar::new(0.0, 0.0, 0.0, 255.0);
+            imgproc::rectangle(
+                &mut output,
+                face_rect,
+                color,
+                -1,
+                imgproc::LINE_8,
+                0,
+            )?;
+            return Ok(output);
+        }
+
+        let roi = Mat::roi(image, face_rect)?;
+        let redacted = self.redact_region(&roi)?;
+
+        // Write the redacted pixels back into the output in place
+        let mut target = Mat::roi_mut(&mut output, face_rect)?;
+        redacted.copy_to(&mut target)?;
+
+        Ok(output)
+    }
+
+    /// Produce the redacted replacement for `roi`, the same size as it.
+    fn redact_region(&self, roi: &Mat) -> Result<Mat> {
         match &self.method {
-            AnonymizationMethod::Blur { kernel_size } => {
-                let mut blurred = Mat::default();
-                imgproc::gaussian_blur(
-                    &roi,
-                    &mut blurred,
-                    core::Size::new(*kernel_size, *kernel_size),
-                    0.0,
-                    0.0,
-                    core::BORDER_DEFAULT,
-                )?;
-                blurred.copy_to(&mut roi)?;
+            AnonymizationMethod::Blur { kernel_size } => blur(roi, *kernel_size),
+            AnonymizationMethod::OvalBlur { kernel_size, feather } => {
+                let blurred = blur(roi, *kernel_size)?;
+                let mask = oval_mask(roi.size()?, *feather)?;
+                composite(roi, &blurred, &mask)
             }
             AnonymizationMethod::Pixelate { block_size } => {
                 let scale = 1.0 / *block_size as f64;
@@ -46,7 +99,7 @@ impl Anonymizer {
 
                 // Resize down
                 imgproc::resize(
-                    &roi,
+                    roi,
                     &mut small,
                     core::Size::new(0, 0),
                     scale,
@@ -64,18 +117,10 @@ impl Anonymizer {
                     imgproc::INTER_NEAREST,
                 )?;
 
-                pixelated.copy_to(&mut roi)?;
+                Ok(pixelated)
             }
             AnonymizationMethod::BlackOut => {
-                let color = core::Scalar::new(0.0, 0.0, 0.0, 255.0);
-                imgproc::rectangle(
-                    &mut output,
-                    face_rect,
-                    color,
-                    -1,
-                    imgproc::LINE_8,
-                    0,
-                )?;
+                Ok(Mat::new_size_with_default(roi.size()?, roi.typ(), core::Scalar::all(0.0))?)
             }
             AnonymizationMethod::Emoji { emoji_path } => {
                 let emoji = imgcodecs::imread(emoji_path, imgcodecs::IMREAD_UNCHANGED)?;
@@ -88,53 +133,51 @@ impl Anonymizer {
                 imgproc::resize(
                     &emoji,
                     &mut resized_emoji,
-                    face_rect.size(),
+                    roi.size()?,
                     0.0,
                     0.0,
                     imgproc::INTER_LINEAR,
                 )?;
 
                 // Handle alpha channel if present
-                if resized_emoji.channels() == 4 {
-                    let mut channels = types::VectorOfMat::new();
-                    core::split(&resized_emoji, &mut channels)?;
-
-                    let alpha = channels.get(3)?;
-                    let mut rgb_channels = types::VectorOfMat::new();
-                    for i in 0..3 {
-                        rgb_channels.push(channels.get(i)?);
-                    }
-
-                    let mut rgb = Mat::default();
-                    core::merge(&rgb_channels, &mut rgb)?;
-
-                    // Apply alpha blending
-                    let mut alpha_norm = Mat::default();
-                    let mut inv_alpha = Mat::default();
-                    core::normalize(&alpha, &mut alpha_norm, 0.0, 1.0, core::NORM_MINMAX, -1, &core::no_array())?;
-                    core::subtract(&core::Scalar::new(1.0, 1.0, 1.0, 1.0), &alpha_norm, &mut inv_alpha, &core::no_array(), -1)?;
-
-                    let mut face_float = Mat::default();
-                    roi.convert_to(&mut face_float, core::CV_32F, 1.0/255.0, 0.0)?;
-
-                    let mut emoji_float = Mat::default();
-                    rgb.convert_to(&mut emoji_float, core::CV_32F, 1.0/255.0, 0.0)?;
-
-                    let mut blended = Mat::default();
-                    core::add_weighted(&face_float, 1.0, &emoji_float, -1.0, 0.0, &mut blended, -1)?;
-                    core::multiply(&blended, &inv_alpha, &mut blended, 1.0, -1)?;
-                    core::add_weighted(&emoji_float, 1.0, &blended, 1.0, 0.0, &mut blended, -1)?;
-
-                    let mut result = Mat::default();
-                    blended.convert_to(&mut result, core::CV_8U, 255.0, 0.0)?;
-                    result.copy_to(&mut roi)?;
-                } else {
-                    resized_emoji.copy_to(&mut roi)?;
+                if resized_emoji.channels() != 4 {
+                    return Ok(resized_emoji);
+                }
+
+                let mut channels = types::VectorOfMat::new();
+                core::split(&resized_emoji, &mut channels)?;
+
+                let alpha = channels.get(3)?;
+                let mut rgb_channels = types::VectorOfMat::new();
+                for i in 0..3 {
+                    rgb_channels.push(channels.get(i)?);
                 }
+
+                let mut rgb = Mat::default();
+                core::merge(&rgb_channels, &mut rgb)?;
+
+                // Apply alpha blending
+                let mut alpha_norm = Mat::default();
+                let mut inv_alpha = Mat::default();
+                core::normalize(&alpha, &mut alpha_norm, 0.0, 1.0, core::NORM_MINMAX, -1, &core::no_array())?;
+                core::subtract(&core::Scalar::new(1.0, 1.0, 1.0, 1.0), &alpha_norm, &mut inv_alpha, &core::no_array(), -1)?;
+
+                let mut face_float = Mat::default();
+                roi.convert_to(&mut face_float, core::CV_32F, 1.0/255.0, 0.0)?;
+
+                let mut emoji_float = Mat::default();
+                rgb.convert_to(&mut emoji_float, core::CV_32F, 1.0/255.0, 0.0)?;
+
+                let mut blended = Mat::default();
+                core::add_weighted(&face_float, 1.0, &emoji_float, -1.0, 0.0, &mut blended, -1)?;
+                core::multiply(&blended, &inv_alpha, &mut blended, 1.0, -1)?;
+                core::add_weighted(&emoji_float, 1.0, &blended, 1.0, 0.0, &mut blended, -1)?;
+
+                let mut result = Mat::default();
+                blended.convert_to(&mut result, core::CV_8U, 255.0, 0.0)?;
+                Ok(result)
             }
         }
-
-        Ok(output)
     }
 
     pub fn batch_anonymize(
@@ -148,4 +191,220 @@ impl Anonymizer {
         }
         Ok(output)
     }
-} 
\ No newline at end of file
+
+    /// Redact every detected face in `image`.
+    pub fn anonymize_image(&self, image: &Mat, detections: &[DetectionResult]) -> Result<Mat> {
+        let face_rects: Vec<core::Rect> = detections
+            .iter()
+            .map(|detection| detection.bbox.rect())
+            .collect();
+        self.batch_anonymize(image, &face_rects)
+    }
+
+    /// Anonymize every face except those the consent filter recognizes.
+    /// `faces` pairs each detected box with its embedding.
+    pub fn anonymize_except(
+        &self,
+        image: &Mat,
+        faces: &[(core::Rect, Vec<f32>)],
+        consent: &ConsentFilter,
+    ) -> Result<Mat> {
+        let redact: Vec<core::Rect> = faces
+            .iter()
+            .filter(|(_, embedding)| !consent.is_consenting(embedding))
+            .map(|(rect, _)| *rect)
+            .collect();
+        self.batch_anonymize(image, &redact)
+    }
+}
+
+fn blur(roi: &Mat, kernel_size: i32) -> Result<Mat> {
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur(
+        roi,
+        &mut blurred,
+        core::Size::new(kernel_size, kernel_size),
+        0.0,
+        0.0,
+        core::BORDER_DEFAULT,
+    )?;
+    Ok(blurred)
+}
+
+/// Single-channel float mask that is 1.0 inside the ellipse inscribed in a
+/// rect of `size` and 0.0 outside, with the edge blurred over `feather` px.
+fn oval_mask(size: core::Size, feather: i32) -> Result<Mat> {
+    let mut mask = Mat::new_size_with_default(size, core::CV_32F, core::Scalar::all(0.0))?;
+    let center = core::Point::new(size.width / 2, size.height / 2);
+    let axes = core::Size::new(
+        (size.width / 2 - feather / 2).max(1),
+        (size.height / 2 - feather / 2).max(1),
+    );
+    imgproc::ellipse(
+        &mut mask,
+        center,
+        axes,
+        0.0,
+        0.0,
+        360.0,
+        core::Scalar::all(1.0),
+        -1,
+        imgproc::LINE_AA,
+        0,
+    )?;
+
+    if feather > 0 {
+        // Gaussian kernels must be odd
+        let kernel = feather | 1;
+        let mut feathered = Mat::default();
+        imgproc::gaussian_blur(
+            &mask,
+            &mut feathered,
+            core::Size::new(kernel, kernel),
+            0.0,
+            0.0,
+            core::BORDER_CONSTANT,
+        )?;
+        mask = feathered;
+    }
+    Ok(mask)
+}
+
+/// Blend `foreground` over `background` using a single-channel float mask.
+fn composite(background: &Mat, foreground: &Mat, mask: &Mat) -> Result<Mat> {
+    let mut alpha = Mat::default();
+    if background.channels() == 1 {
+        alpha = mask.clone();
+    } else {
+        let mut planes = types::VectorOfMat::new();
+        for _ in 0..background.channels() {
+            planes.push(mask.clone());
+        }
+        core::merge(&planes, &mut alpha)?;
+    }
+
+    let mut inv_alpha = Mat::default();
+    core::subtract(&core::Scalar::all(1.0), &alpha, &mut inv_alpha, &core::no_array(), -1)?;
+
+    let (mut bg, mut fg) = (Mat::default(), Mat::default());
+    background.convert_to(&mut bg, core::CV_32F, 1.0, 0.0)?;
+    foreground.convert_to(&mut fg, core::CV_32F, 1.0, 0.0)?;
+
+    let (mut bg_part, mut fg_part, mut blended) = (Mat::default(), Mat::default(), Mat::default());
+    core::multiply(&bg, &inv_alpha, &mut bg_part, 1.0, -1)?;
+    core::multiply(&fg, &alpha, &mut fg_part, 1.0, -1)?;
+    core::add(&bg_part, &fg_part, &mut blended, &core::no_array(), -1)?;
+
+    let mut result = Mat::default();
+    blended.convert_to(&mut result, background.typ(), 1.0, 0.0)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlisted(face_id: &str, embedding: Vec<f32>) -> FaceEmbedding {
+        FaceEmbedding {
+            face_id: face_id.to_string(),
+            embedding,
+            metadata: crate::database::embeddings::FaceMetadata {
+                name: None,
+                tags: vec![],
+                timestamp: chrono::Utc::now(),
+                source_image: String::new(),
+                confidence: 1.0,
+                details: Default::default(),
+            },
+        }
+    }
+
+    fn textured_image() -> Mat {
+        let mut image = Mat::new_rows_cols_with_default(60, 120, core::CV_8UC3, core::Scalar::all(0.0)).unwrap();
+        for y in 0..60 {
+            for x in 0..120 {
+                let value = if (x / 4 + y / 4) % 2 == 0 { 0 } else { 255 };
+                *image.at_2d_mut::<core::Vec3b>(y, x).unwrap() = core::Vec3b::all(value);
+            }
+        }
+        image
+    }
+
+    fn region_changed(before: &Mat, after: &Mat, rect: core::Rect) -> bool {
+        let a = Mat::roi(before, rect).unwrap();
+        let b = Mat::roi(after, rect).unwrap();
+        let mut diff = Mat::default();
+        core::absdiff(&a, &b, &mut diff).unwrap();
+        core::sum_elems(&diff).unwrap()[0] > 0.0
+    }
+
+    #[test]
+    fn test_anonymize_only_touches_face_rect() {
+        let image = textured_image();
+        let face = core::Rect::new(30, 10, 40, 40);
+        let outside = [
+            core::Rect::new(0, 0, 30, 60),
+            core::Rect::new(70, 0, 50, 60),
+            core::Rect::new(30, 0, 40, 10),
+            core::Rect::new(30, 50, 40, 10),
+        ];
+
+        for method in [
+            AnonymizationMethod::Blur { kernel_size: 15 },
+            AnonymizationMethod::Pixelate { block_size: 8 },
+            AnonymizationMethod::BlackOut,
+        ] {
+            let output = Anonymizer::new(method).anonymize(&image, face).unwrap();
+            assert!(region_changed(&image, &output, face));
+            for rect in outside {
+                assert!(!region_changed(&image, &output, rect));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oval_blur_leaves_corners_untouched() {
+        let image = textured_image();
+        let face = core::Rect::new(30, 0, 60, 60);
+        let anonymizer = Anonymizer::new(AnonymizationMethod::OvalBlur { kernel_size: 15, feather: 6 });
+
+        let output = anonymizer.anonymize(&image, face).unwrap();
+
+        assert!(region_changed(&image, &output, core::Rect::new(50, 20, 20, 20)));
+        assert!(!region_changed(&image, &output, core::Rect::new(30, 0, 4, 4)));
+        assert!(!region_changed(&image, &output, core::Rect::new(86, 56, 4, 4)));
+    }
+
+    #[test]
+    fn test_anonymize_image_redacts_each_detection() {
+        let image = textured_image();
+        let detections: Vec<DetectionResult> = [(0, 0, 40, 40), (80, 20, 40, 40)]
+            .into_iter()
+            .map(|bbox| DetectionResult { bbox: bbox.into(), confidence: 0.9, landmarks: None })
+            .collect();
+
+        let output = Anonymizer::new(AnonymizationMethod::BlackOut)
+            .anonymize_image(&image, &detections)
+            .unwrap();
+
+        assert!(region_changed(&image, &output, core::Rect::new(0, 0, 40, 40)));
+        assert!(region_changed(&image, &output, core::Rect::new(80, 20, 40, 40)));
+        assert!(!region_changed(&image, &output, core::Rect::new(40, 0, 40, 60)));
+    }
+
+    #[test]
+    fn test_consenting_face_stays_visible() {
+        let consent = ConsentFilter::new(vec![allowlisted("alice", vec![1.0, 0.0, 0.0])], 0.8);
+        let anonymizer = Anonymizer::new(AnonymizationMethod::Blur { kernel_size: 15 });
+
+        let image = textured_image();
+        let alice = core::Rect::new(0, 0, 60, 60);
+        let stranger = core::Rect::new(60, 0, 60, 60);
+        let faces = vec![(alice, vec![0.97, 0.1, 0.0]), (stranger, vec![0.0, 1.0, 0.0])];
+
+        let output = anonymizer.anonymize_except(&image, &faces, &consent).unwrap();
+
+        assert!(!region_changed(&image, &output, alice));
+        assert!(region_changed(&image, &output, stranger));
+    }
+}
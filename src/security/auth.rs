@@ -0,0 +1,174 @@
+use actix_web::{dev::ServiceRequest, web, Error};
+use actix_web_httpauth::{extractors::bearer::BearerAuth, middleware::HttpAuthentication};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::future::{ready, Ready};
+
+use crate::api::error::ApiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,   // GET endpoints, compare and search
+    Write,  // Enrollment, updates and deletes
+    Admin,  // Maintenance such as cleanup; implies every other scope
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub key: String,
+    pub scopes: HashSet<Scope>,
+}
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>, scopes: impl IntoIterator<Item = Scope>) -> Self {
+        Self {
+            key: key.into(),
+            scopes: scopes.into_iter().collect(),
+        }
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    pub api_keys: Vec<ApiKey>,  // Accepted as `Authorization: Bearer <key>`
+    pub protect_reads: bool,    // Also require a `read` key for read endpoints
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthDecision {
+    Allowed,
+    UnknownKey,    // 401
+    MissingScope,  // 403
+}
+
+impl AuthConfig {
+    pub fn find_key(&self, token: &str) -> Option<&ApiKey> {
+        // Compare against every key so the time taken doesn't reveal which one matched
+        self.api_keys.iter().fold(None, |found, key| {
+            let matches = constant_time_eq(key.key.as_bytes(), token.as_bytes());
+            if matches && found.is_none() {
+                Some(key)
+            } else {
+                found
+            }
+        })
+    }
+
+    pub fn authorize(&self, token: &str, scope: Scope) -> AuthDecision {
+        match self.find_key(token) {
+            None => AuthDecision::UnknownKey,
+            Some(key) if key.allows(scope) => AuthDecision::Allowed,
+            Some(_) => AuthDecision::MissingScope,
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn check_scope(
+    req: ServiceRequest,
+    credentials: &BearerAuth,
+    scope: Scope,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let decision = req
+        .app_data::<web::Data<AuthConfig>>()
+        .map(|config| config.authorize(credentials.token(), scope))
+        .unwrap_or(AuthDecision::UnknownKey);
+
+    match decision {
+        AuthDecision::Allowed => Ok(req),
+        AuthDecision::UnknownKey => Err((ApiError::unauthorized("Invalid API key").into(), req)),
+        AuthDecision::MissingScope => Err((
+            ApiError::forbidden(format!("API key lacks the {} scope", scope)).into(),
+            req,
+        )),
+    }
+}
+
+/// Bearer-token middleware requiring `scope`. Requests without an
+/// `Authorization` header are rejected with 401 by the middleware itself.
+pub fn require_scope(
+    scope: Scope,
+) -> HttpAuthentication<
+    BearerAuth,
+    impl Fn(ServiceRequest, BearerAuth) -> Ready<Result<ServiceRequest, (Error, ServiceRequest)>> + Clone,
+> {
+    HttpAuthentication::bearer(move |req, credentials: BearerAuth| {
+        ready(check_scope(req, &credentials, scope))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App, HttpResponse};
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            api_keys: vec![
+                ApiKey::new("dashboard-key", [Scope::Read]),
+                ApiKey::new("writer-key", [Scope::Read, Scope::Write]),
+                ApiKey::new("admin-key", [Scope::Admin]),
+            ],
+            protect_reads: false,
+        }
+    }
+
+    #[test]
+    fn test_authorize_by_scope() {
+        let config = config();
+        assert_eq!(config.authorize("dashboard-key", Scope::Read), AuthDecision::Allowed);
+        assert_eq!(config.authorize("dashboard-key", Scope::Write), AuthDecision::MissingScope);
+        assert_eq!(config.authorize("writer-key", Scope::Admin), AuthDecision::MissingScope);
+        assert_eq!(config.authorize("admin-key", Scope::Write), AuthDecision::Allowed);
+        assert_eq!(config.authorize("dashboard-kez", Scope::Read), AuthDecision::UnknownKey);
+        assert_eq!(AuthConfig::default().authorize("", Scope::Read), AuthDecision::UnknownKey);
+    }
+
+    #[actix_web::test]
+    async fn test_write_route_status_codes() {
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(config())).service(
+                web::resource("/faces/{id}")
+                    .wrap(require_scope(Scope::Write))
+                    .route(web::delete().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        let delete = |key: Option<&str>| {
+            let request = test::TestRequest::delete().uri("/faces/1");
+            match key {
+                Some(key) => request.insert_header(("Authorization", format!("Bearer {}", key))),
+                None => request,
+            }
+            .to_request()
+        };
+
+        assert_eq!(test::call_service(&app, delete(None)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(test::call_service(&app, delete(Some("wrong"))).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(test::call_service(&app, delete(Some("dashboard-key"))).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, delete(Some("writer-key"))).await.status(), StatusCode::OK);
+    }
+}
@@ -4,11 +4,14 @@ use aes_gcm::{
 };
 use anyhow::Result;
 use rand::{rngs::OsRng, RngCore};
-use sha2::{Sha256, Digest};
+use pbkdf2::pbkdf2_hmac;
+use sha2::Sha256;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use serde::{Serialize, Deserialize};
+use std::borrow::Cow;
 use std::path::Path;
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncryptedData {
@@ -17,30 +20,99 @@ pub struct EncryptedData {
     pub salt: String,       // Base64 encoded
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct KdfConfig {
+    pub iterations: u32,  // PBKDF2-HMAC-SHA256 rounds
+}
+
+impl Default for KdfConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 600_000,  // OWASP recommendation for PBKDF2-HMAC-SHA256
+        }
+    }
+}
+
+const SALT_LEN: usize = 32;
+const SALT_FILE: &str = ".salt";
+
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// Key lifecycle: an encryptor built from a password derives one key for
+/// its own salt and stamps that salt on everything it encrypts. Decrypting
+/// data stamped with a different salt re-derives the key from the password
+/// and that salt, so an `EncryptedData` can be opened with only the
+/// password. Encryptors built with `from_key` have no password and can only
+/// decrypt data encrypted under that same key.
 pub struct Encryptor {
     key: Vec<u8>,
+    salt: Vec<u8>,  // Salt the key was derived with; empty for raw keys
+    password: Option<String>,
+    kdf: KdfConfig,
 }
 
 impl Encryptor {
     pub fn new(password: &str) -> Result<Self> {
-        let mut salt = [0u8; 32];
-        OsRng.fill_bytes(&mut salt);
-        let key = Self::derive_key(password, &salt)?;
-        Ok(Self { key })
+        Self::with_kdf(password, &KdfConfig::default())
+    }
+
+    /// Derive a key under a fresh random salt.
+    pub fn with_kdf(password: &str, kdf: &KdfConfig) -> Result<Self> {
+        Self::with_salt(password, &generate_salt(), kdf)
+    }
+
+    /// Re-derive the key for a known salt, e.g. one read back from an
+    /// `EncryptedData` or persisted next to the encrypted files.
+    pub fn with_salt(password: &str, salt: &[u8], kdf: &KdfConfig) -> Result<Self> {
+        let key = Self::derive_key(password, salt, kdf)?;
+        Ok(Self {
+            key,
+            salt: salt.to_vec(),
+            password: Some(password.to_string()),
+            kdf: *kdf,
+        })
     }
 
     pub fn from_key(key: Vec<u8>) -> Result<Self> {
         if key.len() != 32 {
             return Err(anyhow::anyhow!("Invalid key length"));
         }
-        Ok(Self { key })
+        Ok(Self {
+            key,
+            salt: Vec::new(),
+            password: None,
+            kdf: KdfConfig::default(),
+        })
+    }
+
+    /// Key that encrypted data stamped with `salt`.
+    fn key_for_salt(&self, salt: &[u8]) -> Result<Cow<'_, [u8]>> {
+        if salt == self.salt.as_slice() {
+            return Ok(Cow::Borrowed(&self.key));
+        }
+        match &self.password {
+            Some(password) => Ok(Cow::Owned(Self::derive_key(password, salt, &self.kdf)?)),
+            None => Err(anyhow::anyhow!(
+                "Data was encrypted under a different salt and no password is available to derive its key"
+            )),
+        }
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
     }
 
-    fn derive_key(password: &str, salt: &[u8]) -> Result<Vec<u8>> {
-        let mut hasher = Sha256::new();
-        hasher.update(password.as_bytes());
-        hasher.update(salt);
-        Ok(hasher.finalize().to_vec())
+    fn derive_key(password: &str, salt: &[u8], kdf: &KdfConfig) -> Result<Vec<u8>> {
+        if kdf.iterations == 0 {
+            return Err(anyhow::anyhow!("KDF iteration count must be positive"));
+        }
+        let mut key = vec![0u8; 32];
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, kdf.iterations, &mut key);
+        Ok(key)
     }
 
     pub fn encrypt(&self, data: &[u8]) -> Result<EncryptedData> {
@@ -51,10 +123,6 @@ impl Encryptor {
         OsRng.fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
 
-        // Generate random salt for key derivation
-        let mut salt = [0u8; 32];
-        OsRng.fill_bytes(&mut salt);
-
         // Encrypt data
         let ciphertext = cipher
             .encrypt(nonce, data)
@@ -63,15 +131,19 @@ impl Encryptor {
         Ok(EncryptedData {
             ciphertext: BASE64.encode(ciphertext),
             nonce: BASE64.encode(nonce),
-            salt: BASE64.encode(salt),
+            salt: BASE64.encode(&self.salt),
         })
     }
 
     pub fn decrypt(&self, encrypted: &EncryptedData) -> Result<Vec<u8>> {
-        let cipher = Aes256Gcm::new_from_slice(&self.key)?;
-        
+        let salt = BASE64.decode(&encrypted.salt)?;
+        let cipher = Aes256Gcm::new_from_slice(&self.key_for_salt(&salt)?)?;
+
         let ciphertext = BASE64.decode(&encrypted.ciphertext)?;
         let nonce = BASE64.decode(&encrypted.nonce)?;
+        if nonce.len() != 12 {
+            return Err(anyhow::anyhow!("Invalid nonce length"));
+        }
         let nonce = Nonce::from_slice(&nonce);
 
         cipher
@@ -79,35 +151,164 @@ impl Encryptor {
             .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
     }
 
+    /// Encrypt a file of any size in fixed-size chunks so memory use stays
+    /// bounded. See `STREAM_MAGIC` for the file layout.
     pub async fn encrypt_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
-        // Read file
-        let data = fs::read(input_path).await?;
-        
-        // Encrypt data
-        let encrypted = self.encrypt(&data)?;
-        
-        // Serialize and write encrypted data
-        let json = serde_json::to_string(&encrypted)?;
-        fs::write(output_path, json).await?;
-        
+        self.encrypt_file_chunked(input_path, output_path, STREAM_CHUNK_SIZE).await
+    }
+
+    pub async fn encrypt_file_chunked(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        chunk_size: usize,
+    ) -> Result<()> {
+        if chunk_size == 0 || chunk_size > u32::MAX as usize {
+            return Err(anyhow::anyhow!("Invalid chunk size: {}", chunk_size));
+        }
+        let cipher = Aes256Gcm::new_from_slice(&self.key)?;
+        let mut input = fs::File::open(input_path).await?;
+        let mut output = BufWriter::new(fs::File::create(output_path).await?);
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+
+        // Header
+        output.write_all(STREAM_MAGIC).await?;
+        output.write_u8(self.salt.len() as u8).await?;
+        output.write_all(&self.salt).await?;
+        output.write_all(&nonce_prefix).await?;
+        output.write_u32(chunk_size as u32).await?;
+
+        // Read one chunk ahead so the final chunk can be marked as such
+        let mut current = vec![0u8; chunk_size];
+        let mut current_len = read_full(&mut input, &mut current).await?;
+        let mut next = vec![0u8; chunk_size];
+        let mut counter: u32 = 0;
+
+        loop {
+            let next_len = if current_len == chunk_size {
+                read_full(&mut input, &mut next).await?
+            } else {
+                0
+            };
+            let last = next_len == 0;
+
+            let nonce = chunk_nonce(&nonce_prefix, counter, last);
+            let ciphertext = cipher
+                .encrypt(Nonce::from_slice(&nonce), &current[..current_len])
+                .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+            output.write_u32(ciphertext.len() as u32).await?;
+            output.write_all(&ciphertext).await?;
+
+            if last {
+                break;
+            }
+            counter = counter
+                .checked_add(1)
+                .ok_or_else(|| anyhow::anyhow!("File too large for chunk counter"))?;
+            std::mem::swap(&mut current, &mut next);
+            current_len = next_len;
+        }
+
+        output.flush().await?;
         Ok(())
     }
 
+    /// Decrypt a file written by `encrypt_file`. Files in the older
+    /// single-blob JSON format are still accepted.
     pub async fn decrypt_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
-        // Read encrypted file
+        let mut input = BufReader::new(fs::File::open(input_path).await?);
+
+        let mut magic = [0u8; STREAM_MAGIC.len()];
+        let magic_len = read_full(&mut input, &mut magic).await?;
+        if magic_len < magic.len() || &magic != STREAM_MAGIC {
+            return self.decrypt_json_file(input_path, output_path).await;
+        }
+
+        let salt_len = input.read_u8().await? as usize;
+        let mut salt = vec![0u8; salt_len];
+        input.read_exact(&mut salt).await?;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        input.read_exact(&mut nonce_prefix).await?;
+        let chunk_size = input.read_u32().await? as usize;
+        let max_frame = chunk_size + TAG_LEN;
+
+        let cipher = Aes256Gcm::new_from_slice(&self.key_for_salt(&salt)?)?;
+        let mut output = BufWriter::new(fs::File::create(output_path).await?);
+        let mut counter: u32 = 0;
+
+        loop {
+            let frame_len = input
+                .read_u32()
+                .await
+                .map_err(|_| anyhow::anyhow!("Encrypted file is truncated"))? as usize;
+            if frame_len > max_frame {
+                return Err(anyhow::anyhow!("Corrupt chunk length: {}", frame_len));
+            }
+            let mut frame = vec![0u8; frame_len];
+            input.read_exact(&mut frame).await?;
+
+            // The final chunk was encrypted with the last-chunk flag set, so
+            // truncating or extending the file makes decryption fail
+            let last = input.fill_buf().await?.is_empty();
+            let nonce = chunk_nonce(&nonce_prefix, counter, last);
+            let plaintext = cipher
+                .decrypt(Nonce::from_slice(&nonce), frame.as_ref())
+                .map_err(|e| anyhow::anyhow!("Decryption failed at chunk {}: {}", counter, e))?;
+            output.write_all(&plaintext).await?;
+
+            if last {
+                break;
+            }
+            counter = counter
+                .checked_add(1)
+                .ok_or_else(|| anyhow::anyhow!("Too many chunks"))?;
+        }
+
+        output.flush().await?;
+        Ok(())
+    }
+
+    async fn decrypt_json_file(&self, input_path: &Path, output_path: &Path) -> Result<()> {
         let json = fs::read_to_string(input_path).await?;
         let encrypted: EncryptedData = serde_json::from_str(&json)?;
-        
-        // Decrypt data
         let decrypted = self.decrypt(&encrypted)?;
-        
-        // Write decrypted data
         fs::write(output_path, decrypted).await?;
-        
         Ok(())
     }
 }
 
+/// Streamed files start with this magic, then the salt length (u8), salt,
+/// a random 7-byte nonce prefix and the chunk size (u32 BE). Each chunk
+/// follows as its ciphertext length (u32 BE) and AES-GCM ciphertext. A
+/// chunk's nonce is the prefix, its index (u32 BE) and a last-chunk flag.
+const STREAM_MAGIC: &[u8; 8] = b"FAENCv01";
+const STREAM_CHUNK_SIZE: usize = 1024 * 1024;
+const NONCE_PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Fill `buf` from `reader`, stopping early only at end of file.
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader.read(&mut buf[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
 pub struct SecureStorage {
     encryptor: Encryptor,
     storage_dir: String,
@@ -115,13 +316,35 @@ pub struct SecureStorage {
 
 impl SecureStorage {
     pub fn new(password: &str, storage_dir: String) -> Result<Self> {
-        let encryptor = Encryptor::new(password)?;
+        Self::with_kdf(password, storage_dir, &KdfConfig::default())
+    }
+
+    /// The salt is persisted in `storage_dir` on first use so the same
+    /// password derives the same key after a restart.
+    pub fn with_kdf(password: &str, storage_dir: String, kdf: &KdfConfig) -> Result<Self> {
+        let salt = Self::load_or_create_salt(Path::new(&storage_dir))?;
+        let encryptor = Encryptor::with_salt(password, &salt, kdf)?;
         Ok(Self {
             encryptor,
             storage_dir,
         })
     }
 
+    fn load_or_create_salt(storage_dir: &Path) -> Result<Vec<u8>> {
+        let salt_path = storage_dir.join(SALT_FILE);
+        match std::fs::read(&salt_path) {
+            Ok(salt) if salt.len() == SALT_LEN => Ok(salt),
+            Ok(_) => Err(anyhow::anyhow!("Corrupt salt file: {}", salt_path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::create_dir_all(storage_dir)?;
+                let salt = generate_salt();
+                std::fs::write(&salt_path, &salt)?;
+                Ok(salt)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn store(&self, key: &str, data: &[u8]) -> Result<()> {
         let encrypted = self.encryptor.encrypt(data)?;
         let path = Path::new(&self.storage_dir).join(format!("{}.enc", key));
@@ -176,6 +399,75 @@ mod tests {
         assert_eq!(data.as_ref(), decrypted.as_slice());
     }
 
+    #[tokio::test]
+    async fn test_streaming_file_round_trip() {
+        let dir = tempdir().unwrap();
+        let kdf = KdfConfig { iterations: 1_000 };
+        let encryptor = Encryptor::with_kdf("test_password", &kdf).unwrap();
+
+        let plain_path = dir.path().join("video.bin");
+        let encrypted_path = dir.path().join("video.enc");
+        let decrypted_path = dir.path().join("video.out");
+
+        for size in [0usize, 100, 256, 1000] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            std::fs::write(&plain_path, &data).unwrap();
+
+            encryptor.encrypt_file_chunked(&plain_path, &encrypted_path, 128).await.unwrap();
+            let reopened = Encryptor::with_kdf("test_password", &kdf).unwrap();
+            reopened.decrypt_file(&encrypted_path, &decrypted_path).await.unwrap();
+            assert_eq!(std::fs::read(&decrypted_path).unwrap(), data);
+        }
+
+        // Dropping the final chunk must be detected
+        let encrypted = std::fs::read(&encrypted_path).unwrap();
+        let frame = 4 + (1000 - 7 * 128) + TAG_LEN;
+        std::fs::write(&encrypted_path, &encrypted[..encrypted.len() - frame]).unwrap();
+        assert!(encryptor.decrypt_file(&encrypted_path, &decrypted_path).await.is_err());
+    }
+
+    #[test]
+    fn test_encrypted_data_decrypts_with_password_alone() {
+        let kdf = KdfConfig { iterations: 1_000 };
+        let encrypted = Encryptor::with_kdf("test_password", &kdf)
+            .unwrap()
+            .encrypt(b"face image")
+            .unwrap();
+
+        // Rebuilt from the password and the salt stored in the blob
+        let salt = BASE64.decode(&encrypted.salt).unwrap();
+        let rebuilt = Encryptor::with_salt("test_password", &salt, &kdf).unwrap();
+        assert_eq!(rebuilt.decrypt(&encrypted).unwrap(), b"face image");
+
+        // A new encryptor with its own salt re-derives the key for the blob
+        let fresh = Encryptor::with_kdf("test_password", &kdf).unwrap();
+        assert_ne!(fresh.salt(), salt.as_slice());
+        assert_eq!(fresh.decrypt(&encrypted).unwrap(), b"face image");
+
+        let wrong = Encryptor::with_kdf("wrong_password", &kdf).unwrap();
+        assert!(wrong.decrypt(&encrypted).is_err());
+
+        let raw = Encryptor::from_key(vec![7u8; 32]).unwrap();
+        assert!(raw.decrypt(&encrypted).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_secure_storage_survives_restart() {
+        let dir = tempdir().unwrap();
+        let kdf = KdfConfig { iterations: 1_000 };
+        let storage_dir = dir.path().to_str().unwrap().to_string();
+
+        let storage = SecureStorage::with_kdf("test_password", storage_dir.clone(), &kdf).unwrap();
+        storage.store("face", b"pixels").await.unwrap();
+        drop(storage);
+
+        let reopened = SecureStorage::with_kdf("test_password", storage_dir.clone(), &kdf).unwrap();
+        assert_eq!(reopened.retrieve("face").await.unwrap(), b"pixels");
+
+        let wrong = SecureStorage::with_kdf("other_password", storage_dir, &kdf).unwrap();
+        assert!(wrong.retrieve("face").await.is_err());
+    }
+
     #[tokio::test]
     async fn test_secure_storage() {
         let dir = tempdir().unwrap();
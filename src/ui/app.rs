@@ -2,10 +2,15 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 use gloo_net::http::Request;
 use gloo_file::File;
+use gloo_storage::{LocalStorage, Storage};
 use web_sys::{HtmlInputElement, FileList};
 use wasm_bindgen::{JsCast, UnwrapThrowExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ui::config::ServerConfigPage;
+use crate::ui::dashboard::Dashboard;
 
 // Route definition
 #[derive(Clone, Routable, PartialEq)]
@@ -16,12 +21,16 @@ pub enum Route {
     Faces,
     #[at("/faces/:id")]
     FaceDetails { id: String },
+    #[at("/dashboard")]
+    Dashboard,
     #[at("/settings")]
     Settings,
+    #[at("/config")]
+    ServerConfig,
 }
 
 // API types
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Face {
     face_id: String,
     name: Option<String>,
@@ -29,28 +38,77 @@ pub struct Face {
     confidence: f32,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// `POST /api/v1/analyze` returns one entry per face in the upload.
+#[derive(Deserialize)]
+struct UploadResponse {
+    faces: Vec<Face>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     min_confidence: f32,
     include_embeddings: bool,
     auto_cleanup_days: i32,
 }
 
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.8,
+            include_embeddings: false,
+            auto_cleanup_days: 30,
+        }
+    }
+}
+
+/// localStorage key the settings form persists to.
+const SETTINGS_KEY: &str = "face-analyzer.settings";
+
+impl Settings {
+    /// Saved settings, or the defaults when nothing (or something
+    /// unreadable) is stored.
+    fn load() -> Self {
+        LocalStorage::get(SETTINGS_KEY).unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        LocalStorage::set(SETTINGS_KEY, self).map_err(|err| err.to_string())
+    }
+
+    fn faces_url(&self) -> String {
+        format!(
+            "/api/v1/faces?min_confidence={}&include_embeddings={}",
+            self.min_confidence, self.include_embeddings
+        )
+    }
+}
+
+/// Shared with the routed pages, which `switch` renders without access to
+/// `App` itself. `dispatch` feeds messages back into `App::update`.
+#[derive(Clone, PartialEq)]
+pub struct AppContext {
+    pub dispatch: Callback<Msg>,
+    pub faces: Rc<Vec<Face>>,
+    pub settings: Settings,
+}
+
 // Main app component
 pub struct App {
-    faces: Vec<Face>,
+    faces: Rc<Vec<Face>>,
     settings: Settings,
     loading: bool,
     error: Option<String>,
+    dispatch: Callback<Msg>,
 }
 
 pub enum Msg {
     LoadFaces,
     FacesLoaded(Vec<Face>),
     UploadFace(File),
-    FaceUploaded(Face),
+    FacesUploaded(Vec<Face>),
     DeleteFace(String),
     FaceDeleted(String),
+    FaceUpdated(Face),
     UpdateSettings(Settings),
     Error(String),
 }
@@ -63,14 +121,11 @@ impl Component for App {
         ctx.link().send_message(Msg::LoadFaces);
         
         Self {
-            faces: Vec::new(),
-            settings: Settings {
-                min_confidence: 0.8,
-                include_embeddings: false,
-                auto_cleanup_days: 30,
-            },
+            faces: Rc::new(Vec::new()),
+            settings: Settings::load(),
             loading: true,
             error: None,
+            dispatch: ctx.link().callback(|msg: Msg| msg),
         }
     }
 
@@ -79,8 +134,9 @@ impl Component for App {
             Msg::LoadFaces => {
                 self.loading = true;
                 let link = ctx.link().clone();
+                let url = self.settings.faces_url();
                 wasm_bindgen_futures::spawn_local(async move {
-                    match Request::get("/api/v1/faces")
+                    match Request::get(&url)
                         .send()
                         .await
                         .and_then(|resp| resp.json::<Vec<Face>>().await)
@@ -92,7 +148,7 @@ impl Component for App {
                 false
             }
             Msg::FacesLoaded(faces) => {
-                self.faces = faces;
+                self.faces = Rc::new(faces);
                 self.loading = false;
                 true
             }
@@ -107,16 +163,22 @@ impl Component for App {
                         .body(form_data)
                         .send()
                         .await
-                        .and_then(|resp| resp.json::<Face>().await)
+                        .and_then(|resp| resp.json::<UploadResponse>().await)
                     {
-                        Ok(face) => link.send_message(Msg::FaceUploaded(face)),
+                        Ok(upload) => link.send_message(Msg::FacesUploaded(upload.faces)),
                         Err(err) => link.send_message(Msg::Error(err.to_string())),
                     }
                 });
                 false
             }
-            Msg::FaceUploaded(face) => {
-                self.faces.push(face);
+            Msg::FacesUploaded(uploaded) => {
+                // Duplicates come back with the id of the face already listed
+                let faces = Rc::make_mut(&mut self.faces);
+                for face in uploaded {
+                    if !faces.iter().any(|f| f.face_id == face.face_id) {
+                        faces.push(face);
+                    }
+                }
                 self.loading = false;
                 true
             }
@@ -135,12 +197,27 @@ impl Component for App {
                 false
             }
             Msg::FaceDeleted(id) => {
-                self.faces.retain(|face| face.face_id != id);
+                Rc::make_mut(&mut self.faces).retain(|face| face.face_id != id);
                 self.loading = false;
                 true
             }
+            Msg::FaceUpdated(face) => {
+                let faces = Rc::make_mut(&mut self.faces);
+                match faces.iter_mut().find(|f| f.face_id == face.face_id) {
+                    Some(existing) => *existing = face,
+                    None => faces.push(face),
+                }
+                true
+            }
             Msg::UpdateSettings(settings) => {
+                if let Err(err) = settings.save() {
+                    self.error = Some(format!("Failed to save settings: {}", err));
+                }
+                let reload = settings.faces_url() != self.settings.faces_url();
                 self.settings = settings;
+                if reload {
+                    ctx.link().send_message(Msg::LoadFaces);
+                }
                 true
             }
             Msg::Error(error) => {
@@ -152,43 +229,53 @@ impl Component for App {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        let context = AppContext {
+            dispatch: self.dispatch.clone(),
+            faces: Rc::clone(&self.faces),
+            settings: self.settings.clone(),
+        };
+
         html! {
-            <BrowserRouter>
-                <div class="app">
-                    <nav class="navbar">
-                        <Link<Route> to={Route::Home}>{ "Home" }</Link<Route>>
-                        <Link<Route> to={Route::Faces}>{ "Faces" }</Link<Route>>
-                        <Link<Route> to={Route::Settings}>{ "Settings" }</Link<Route>>
-                    </nav>
-
-                    {if let Some(error) = &self.error {
-                        html! {
-                            <div class="error-banner">
-                                { error }
-                                <button onclick={ctx.link().callback(|_| Msg::Error(String::new()))}>
-                                    { "✕" }
-                                </button>
-                            </div>
-                        }
-                    } else {
-                        html! {}
-                    }}
+            <ContextProvider<AppContext> context={context}>
+                <BrowserRouter>
+                    <div class="app">
+                        <nav class="navbar">
+                            <Link<Route> to={Route::Home}>{ "Home" }</Link<Route>>
+                            <Link<Route> to={Route::Faces}>{ "Faces" }</Link<Route>>
+                            <Link<Route> to={Route::Dashboard}>{ "Dashboard" }</Link<Route>>
+                            <Link<Route> to={Route::Settings}>{ "Settings" }</Link<Route>>
+                            <Link<Route> to={Route::ServerConfig}>{ "Server" }</Link<Route>>
+                        </nav>
 
-                    <main>
-                        <Switch<Route> render={switch} />
-                    </main>
+                        {if let Some(error) = &self.error {
+                            html! {
+                                <div class="error-banner">
+                                    { error }
+                                    <button onclick={ctx.link().callback(|_| Msg::Error(String::new()))}>
+                                        { "✕" }
+                                    </button>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }}
 
-                    {if self.loading {
-                        html! {
-                            <div class="loading-overlay">
-                                <div class="spinner"></div>
-                            </div>
-                        }
-                    } else {
-                        html! {}
-                    }}
-                </div>
-            </BrowserRouter>
+                        <main>
+                            <Switch<Route> render={switch} />
+                        </main>
+
+                        {if self.loading {
+                            html! {
+                                <div class="loading-overlay">
+                                    <div class="spinner"></div>
+                                </div>
+                            }
+                        } else {
+                            html! {}
+                        }}
+                    </div>
+                </BrowserRouter>
+            </ContextProvider<AppContext>>
         }
     }
 }
@@ -199,16 +286,19 @@ fn switch(routes: Route) -> Html {
         Route::Home => html! { <Home /> },
         Route::Faces => html! { <FacesList /> },
         Route::FaceDetails { id } => html! { <FaceDetails id={id} /> },
-        Route::Settings => html! { <Settings /> },
+        Route::Dashboard => html! { <Dashboard /> },
+        Route::Settings => html! { <SettingsPage /> },
+        Route::ServerConfig => html! { <ServerConfigPage /> },
     }
 }
 
 // Home component
 #[function_component(Home)]
 fn home() -> Html {
-    let onupload = Callback::from(|files: FileList| {
+    let app = use_context::<AppContext>().expect("Home must be rendered inside App");
+    let onupload = Callback::from(move |files: FileList| {
         if let Some(file) = files.get(0) {
-            // Handle file upload
+            app.dispatch.emit(Msg::UploadFace(File::from(file)));
         }
     });
 
@@ -238,14 +328,95 @@ fn home() -> Html {
 // Faces list component
 #[function_component(FacesList)]
 fn faces_list() -> Html {
+    let app = use_context::<AppContext>().expect("FacesList must be rendered inside App");
+
     html! {
-        <div class="faces-list">
+        <div class="faces-page">
             <h2>{ "Detected Faces" }</h2>
-            // Face grid will be populated here
+            if app.faces.is_empty() {
+                <p class="empty-state">{ "No faces yet. Upload an image to get started." }</p>
+            } else {
+                <div class="faces-list">
+                    { for app.faces.iter().map(|face| html! {
+                        <FaceCard key={face.face_id.clone()} face={face.clone()} dispatch={app.dispatch.clone()} />
+                    }) }
+                </div>
+            }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct FaceCardProps {
+    face: Face,
+    dispatch: Callback<Msg>,
+}
+
+#[function_component(FaceCard)]
+fn face_card(props: &FaceCardProps) -> Html {
+    let face = &props.face;
+    let ondelete = {
+        let dispatch = props.dispatch.clone();
+        let id = face.face_id.clone();
+        Callback::from(move |_: MouseEvent| dispatch.emit(Msg::DeleteFace(id.clone())))
+    };
+    let label = display_name(face);
+
+    html! {
+        <div class="face-card">
+            <Link<Route> to={Route::FaceDetails { id: face.face_id.clone() }}>
+                { face_thumbnail(face, &label) }
+            </Link<Route>>
+            <div class="face-info">
+                <div class="face-name">{ label }</div>
+                <div class="face-confidence">{ format!("Confidence: {:.0}%", face.confidence * 100.0) }</div>
+                <div class="face-tags">
+                    { for face.tags.iter().map(|tag| html! { <span class="tag">{ tag }</span> }) }
+                </div>
+                <button class="delete-button" onclick={ondelete}>{ "Delete" }</button>
+            </div>
         </div>
     }
 }
 
+fn display_name(face: &Face) -> String {
+    face.name.clone().unwrap_or_else(|| "Unnamed".to_string())
+}
+
+fn face_image_url(face_id: &str) -> String {
+    format!("/api/v1/faces/{}/image", face_id)
+}
+
+fn face_thumbnail(face: &Face, label: &str) -> Html {
+    html! {
+        <img class="face-image" src={face_image_url(&face.face_id)} alt={label.to_string()} loading="lazy" />
+    }
+}
+
+/// Split the comma separated tags field, dropping blanks.
+fn parse_tags(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Serialize)]
+struct FaceUpdate {
+    name: Option<String>,
+    tags: Vec<String>,
+}
+
+#[derive(Clone, PartialEq)]
+enum DetailState {
+    Loading,
+    NotFound,
+    Failed(String),
+    Loaded(Face),
+}
+
 // Face details component
 #[derive(Properties, PartialEq)]
 struct FaceDetailsProps {
@@ -254,34 +425,256 @@ struct FaceDetailsProps {
 
 #[function_component(FaceDetails)]
 fn face_details(props: &FaceDetailsProps) -> Html {
+    let app = use_context::<AppContext>().expect("FaceDetails must be rendered inside App");
+    let state = use_state(|| DetailState::Loading);
+    let name = use_state(String::new);
+    let tags = use_state(String::new);
+    let status = use_state(|| None::<String>);
+
+    {
+        let state = state.clone();
+        let name = name.clone();
+        let tags = tags.clone();
+        use_effect_with_deps(
+            move |id: &String| {
+                state.set(DetailState::Loading);
+                let url = format!("/api/v1/faces/{}?include_embeddings=false", id);
+                wasm_bindgen_futures::spawn_local(async move {
+                    match Request::get(&url).send().await {
+                        Ok(resp) if resp.status() == 404 => state.set(DetailState::NotFound),
+                        Ok(resp) if !resp.ok() => {
+                            state.set(DetailState::Failed(format!("Request failed with status {}", resp.status())))
+                        }
+                        Ok(resp) => match resp.json::<Face>().await {
+                            Ok(face) => {
+                                name.set(face.name.clone().unwrap_or_default());
+                                tags.set(face.tags.join(", "));
+                                state.set(DetailState::Loaded(face));
+                            }
+                            Err(err) => state.set(DetailState::Failed(err.to_string())),
+                        },
+                        Err(err) => state.set(DetailState::Failed(err.to_string())),
+                    }
+                });
+                || ()
+            },
+            props.id.clone(),
+        );
+    }
+
+    let face = match &*state {
+        DetailState::Loading => {
+            return html! {
+                <div class="face-details">
+                    <div class="spinner"></div>
+                </div>
+            };
+        }
+        DetailState::NotFound => {
+            return html! {
+                <div class="face-details">
+                    <h2>{ "Face not found" }</h2>
+                    <p>{ format!("No face with id {} exists.", props.id) }</p>
+                    <Link<Route> to={Route::Faces}>{ "Back to faces" }</Link<Route>>
+                </div>
+            };
+        }
+        DetailState::Failed(error) => {
+            return html! {
+                <div class="face-details">
+                    <h2>{ "Could not load face" }</h2>
+                    <p class="error">{ error }</p>
+                </div>
+            };
+        }
+        DetailState::Loaded(face) => face.clone(),
+    };
+
+    let onname = {
+        let name = name.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            name.set(input.value());
+        })
+    };
+    let ontags = {
+        let tags = tags.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            tags.set(input.value());
+        })
+    };
+    let onsubmit = {
+        let id = face.face_id.clone();
+        let name = name.clone();
+        let tags = tags.clone();
+        let state = state.clone();
+        let status = status.clone();
+        let dispatch = app.dispatch.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let trimmed = name.trim();
+            let update = FaceUpdate {
+                name: (!trimmed.is_empty()).then(|| trimmed.to_string()),
+                tags: parse_tags(&tags),
+            };
+            let url = format!("/api/v1/faces/{}", id);
+            let state = state.clone();
+            let status = status.clone();
+            let dispatch = dispatch.clone();
+            status.set(Some("Saving...".to_string()));
+            wasm_bindgen_futures::spawn_local(async move {
+                let result = match Request::put(&url).json(&update) {
+                    Ok(request) => request.send().await,
+                    Err(err) => Err(err),
+                };
+                match result {
+                    Ok(resp) if resp.ok() => {
+                        if let DetailState::Loaded(face) = &*state {
+                            let updated = Face {
+                                name: update.name,
+                                tags: update.tags,
+                                ..face.clone()
+                            };
+                            dispatch.emit(Msg::FaceUpdated(updated.clone()));
+                            state.set(DetailState::Loaded(updated));
+                        }
+                        status.set(Some("Saved".to_string()));
+                    }
+                    Ok(resp) => status.set(Some(format!("Save failed with status {}", resp.status()))),
+                    Err(err) => status.set(Some(format!("Save failed: {}", err))),
+                }
+            });
+        })
+    };
+
+    let label = display_name(&face);
+
     html! {
         <div class="face-details">
-            <h2>{ format!("Face Details: {}", props.id) }</h2>
-            // Face details will be displayed here
+            <h2>{ label.clone() }</h2>
+            { face_thumbnail(&face, &label) }
+            <div class="face-metadata">
+                <div class="metadata-group">
+                    <div class="metadata-label">{ "Face ID" }</div>
+                    <div>{ &face.face_id }</div>
+                </div>
+                <div class="metadata-group">
+                    <div class="metadata-label">{ "Confidence" }</div>
+                    <div>{ format!("{:.0}%", face.confidence * 100.0) }</div>
+                </div>
+                <form onsubmit={onsubmit}>
+                    <div class="form-group">
+                        <label for="face-name">{ "Name" }</label>
+                        <input id="face-name" type="text" value={(*name).clone()} oninput={onname} />
+                    </div>
+                    <div class="form-group">
+                        <label for="face-tags">{ "Tags (comma separated)" }</label>
+                        <input id="face-tags" type="text" value={(*tags).clone()} oninput={ontags} />
+                    </div>
+                    <button type="submit">{ "Save" }</button>
+                    if let Some(message) = &*status {
+                        <span class="save-status">{ message }</span>
+                    }
+                </form>
+            </div>
         </div>
     }
 }
 
-// Settings component
-#[function_component(Settings)]
-fn settings() -> Html {
+// Settings component. Named apart from the `Settings` data type.
+#[function_component(SettingsPage)]
+fn settings_page() -> Html {
+    let app = use_context::<AppContext>().expect("SettingsPage must be rendered inside App");
+    let draft = {
+        let saved = app.settings.clone();
+        use_state(move || saved)
+    };
+    let saved = use_state(|| false);
+
+    let onconfidence = {
+        let draft = draft.clone();
+        let saved = saved.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(min_confidence) = input.value().parse() {
+                draft.set(Settings { min_confidence, ..(*draft).clone() });
+                saved.set(false);
+            }
+        })
+    };
+    let onembeddings = {
+        let draft = draft.clone();
+        let saved = saved.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            draft.set(Settings { include_embeddings: input.checked(), ..(*draft).clone() });
+            saved.set(false);
+        })
+    };
+    let oncleanup = {
+        let draft = draft.clone();
+        let saved = saved.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(days) = input.value().parse::<i32>() {
+                draft.set(Settings { auto_cleanup_days: days.max(1), ..(*draft).clone() });
+                saved.set(false);
+            }
+        })
+    };
+    let onsubmit = {
+        let draft = draft.clone();
+        let saved = saved.clone();
+        let dispatch = app.dispatch.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            dispatch.emit(Msg::UpdateSettings((*draft).clone()));
+            saved.set(true);
+        })
+    };
+
     html! {
         <div class="settings">
             <h2>{ "Settings" }</h2>
-            <form>
+            <form onsubmit={onsubmit}>
                 <div class="form-group">
-                    <label>{ "Minimum Confidence" }</label>
-                    <input type="range" min="0" max="1" step="0.1" />
+                    <label for="min-confidence">
+                        { format!("Minimum Confidence ({:.0}%)", draft.min_confidence * 100.0) }
+                    </label>
+                    <input
+                        id="min-confidence"
+                        type="range"
+                        min="0"
+                        max="1"
+                        step="0.1"
+                        value={draft.min_confidence.to_string()}
+                        oninput={onconfidence}
+                    />
                 </div>
                 <div class="form-group">
-                    <label>{ "Include Embeddings" }</label>
-                    <input type="checkbox" />
+                    <label for="include-embeddings">{ "Include Embeddings" }</label>
+                    <input
+                        id="include-embeddings"
+                        type="checkbox"
+                        checked={draft.include_embeddings}
+                        onchange={onembeddings}
+                    />
                 </div>
                 <div class="form-group">
-                    <label>{ "Auto Cleanup (days)" }</label>
-                    <input type="number" min="1" />
+                    <label for="auto-cleanup">{ "Auto Cleanup (days)" }</label>
+                    <input
+                        id="auto-cleanup"
+                        type="number"
+                        min="1"
+                        value={draft.auto_cleanup_days.to_string()}
+                        oninput={oncleanup}
+                    />
                 </div>
                 <button type="submit">{ "Save Settings" }</button>
+                if *saved {
+                    <span class="save-status">{ "Settings saved" }</span>
+                }
             </form>
         </div>
     }
@@ -0,0 +1,293 @@
+use gloo_net::http::{Request, Response};
+use serde::{Deserialize, Serialize};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+const CONFIG_URL: &str = "/api/v1/config";
+const DETECTOR_TYPES: [&str; 4] = ["Haar", "DNN", "MTCNN", "RetinaFace"];
+
+// Mirrors `api::runtime::RuntimeConfig`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ServerConfig {
+    detector_type: String,
+    confidence_threshold: f32,
+    min_face_size: i32,
+    scale_factor: f32,
+    preprocess: bool,
+    preprocessing: Preprocessing,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Preprocessing {
+    brightness: f64,
+    contrast: f64,
+    blur_size: i32,
+    sharpen: bool,
+    equalize: bool,
+    denoise: bool,
+    normalize: bool,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+/// Turn a response into the config it carries or a message for the user.
+async fn read_config(response: Result<Response, gloo_net::Error>) -> Result<ServerConfig, String> {
+    let response = response.map_err(|err| err.to_string())?;
+    match response.status() {
+        200 => response.json::<ServerConfig>().await.map_err(|err| err.to_string()),
+        401 | 403 => Err("An API key with the admin scope is required".to_string()),
+        status => Err(match response.json::<ErrorBody>().await {
+            Ok(body) => body.error.message,
+            Err(_) => format!("Request failed with status {}", status),
+        }),
+    }
+}
+
+fn bearer(api_key: &str) -> String {
+    format!("Bearer {}", api_key.trim())
+}
+
+type ConfigState = UseStateHandle<Option<ServerConfig>>;
+
+/// Callback for a text or number input that edits one field of the draft.
+fn on_value(config: &ConfigState, apply: impl Fn(&mut ServerConfig, &str) + 'static) -> Callback<InputEvent> {
+    let config = config.clone();
+    Callback::from(move |e: InputEvent| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        if let Some(mut draft) = (*config).clone() {
+            apply(&mut draft, &input.value());
+            config.set(Some(draft));
+        }
+    })
+}
+
+/// Callback for a checkbox that edits one flag of the draft.
+fn on_toggle(config: &ConfigState, apply: impl Fn(&mut ServerConfig, bool) + 'static) -> Callback<Event> {
+    let config = config.clone();
+    Callback::from(move |e: Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        if let Some(mut draft) = (*config).clone() {
+            apply(&mut draft, input.checked());
+            config.set(Some(draft));
+        }
+    })
+}
+
+fn checkbox(id: &'static str, label: &'static str, checked: bool, onchange: Callback<Event>) -> Html {
+    html! {
+        <div class="form-group">
+            <label for={id}>
+                <input id={id} type="checkbox" checked={checked} onchange={onchange} />
+                { label }
+            </label>
+        </div>
+    }
+}
+
+/// Admin view of the server's detection settings.
+#[function_component(ServerConfigPage)]
+pub fn server_config_page() -> Html {
+    let api_key = use_state(String::new);
+    let config: ConfigState = use_state(|| None);
+    let status = use_state(|| None::<String>);
+
+    let onkey = {
+        let api_key = api_key.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            api_key.set(input.value());
+        })
+    };
+    let onload = {
+        let api_key = api_key.clone();
+        let config = config.clone();
+        let status = status.clone();
+        Callback::from(move |_: MouseEvent| {
+            let authorization = bearer(&api_key);
+            let config = config.clone();
+            let status = status.clone();
+            status.set(Some("Loading...".to_string()));
+            wasm_bindgen_futures::spawn_local(async move {
+                let response = Request::get(CONFIG_URL)
+                    .header("Authorization", &authorization)
+                    .send()
+                    .await;
+                match read_config(response).await {
+                    Ok(loaded) => {
+                        config.set(Some(loaded));
+                        status.set(None);
+                    }
+                    Err(err) => status.set(Some(err)),
+                }
+            });
+        })
+    };
+    let onsubmit = {
+        let api_key = api_key.clone();
+        let config = config.clone();
+        let status = status.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            let draft = match &*config {
+                Some(draft) => draft.clone(),
+                None => return,
+            };
+            let authorization = bearer(&api_key);
+            let config = config.clone();
+            let status = status.clone();
+            status.set(Some("Saving...".to_string()));
+            wasm_bindgen_futures::spawn_local(async move {
+                let response = match Request::post(CONFIG_URL)
+                    .header("Authorization", &authorization)
+                    .json(&draft)
+                {
+                    Ok(request) => request.send().await,
+                    Err(err) => Err(err),
+                };
+                match read_config(response).await {
+                    Ok(applied) => {
+                        config.set(Some(applied));
+                        status.set(Some("Configuration applied".to_string()));
+                    }
+                    Err(err) => status.set(Some(err)),
+                }
+            });
+        })
+    };
+
+    let form = match &*config {
+        None => html! {},
+        Some(current) => {
+            let ondetector = {
+                let config = config.clone();
+                Callback::from(move |e: Event| {
+                    let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+                    if let Some(mut draft) = (*config).clone() {
+                        draft.detector_type = select.value();
+                        config.set(Some(draft));
+                    }
+                })
+            };
+            let prep = &current.preprocessing;
+
+            html! {
+                <form onsubmit={onsubmit}>
+                    <h3>{ "Detector" }</h3>
+                    <div class="form-group">
+                        <label for="detector-type">{ "Detector Type" }</label>
+                        <select id="detector-type" onchange={ondetector}>
+                            { for DETECTOR_TYPES.iter().map(|name| html! {
+                                <option value={*name} selected={current.detector_type == *name}>{ name }</option>
+                            }) }
+                        </select>
+                    </div>
+                    <div class="form-group">
+                        <label for="confidence-threshold">
+                            { format!("Confidence Threshold ({:.2})", current.confidence_threshold) }
+                        </label>
+                        <input
+                            id="confidence-threshold"
+                            type="range"
+                            min="0"
+                            max="1"
+                            step="0.05"
+                            value={current.confidence_threshold.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.confidence_threshold = v })}
+                        />
+                    </div>
+                    <div class="form-group">
+                        <label for="min-face-size">{ "Minimum Face Size (px)" }</label>
+                        <input
+                            id="min-face-size"
+                            type="number"
+                            min="1"
+                            value={current.min_face_size.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.min_face_size = v })}
+                        />
+                    </div>
+                    <div class="form-group">
+                        <label for="scale-factor">{ "Scale Factor" }</label>
+                        <input
+                            id="scale-factor"
+                            type="number"
+                            min="1.01"
+                            step="0.01"
+                            value={current.scale_factor.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.scale_factor = v })}
+                        />
+                    </div>
+
+                    <h3>{ "Preprocessing" }</h3>
+                    { checkbox("preprocess", "Preprocess uploads before detection", current.preprocess,
+                        on_toggle(&config, |c, v| c.preprocess = v)) }
+                    <div class="form-group">
+                        <label for="brightness">{ format!("Brightness ({:.1})", prep.brightness) }</label>
+                        <input
+                            id="brightness"
+                            type="range"
+                            min="-1"
+                            max="1"
+                            step="0.1"
+                            value={prep.brightness.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.preprocessing.brightness = v })}
+                        />
+                    </div>
+                    <div class="form-group">
+                        <label for="contrast">{ format!("Contrast ({:.1})", prep.contrast) }</label>
+                        <input
+                            id="contrast"
+                            type="range"
+                            min="0"
+                            max="3"
+                            step="0.1"
+                            value={prep.contrast.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.preprocessing.contrast = v })}
+                        />
+                    </div>
+                    <div class="form-group">
+                        <label for="blur-size">{ "Blur Kernel (odd, 0 to disable)" }</label>
+                        <input
+                            id="blur-size"
+                            type="number"
+                            min="0"
+                            step="1"
+                            value={prep.blur_size.to_string()}
+                            oninput={on_value(&config, |c, v| if let Ok(v) = v.parse() { c.preprocessing.blur_size = v })}
+                        />
+                    </div>
+                    { checkbox("sharpen", "Sharpen", prep.sharpen, on_toggle(&config, |c, v| c.preprocessing.sharpen = v)) }
+                    { checkbox("equalize", "Histogram equalization", prep.equalize,
+                        on_toggle(&config, |c, v| c.preprocessing.equalize = v)) }
+                    { checkbox("denoise", "Denoise", prep.denoise, on_toggle(&config, |c, v| c.preprocessing.denoise = v)) }
+                    { checkbox("normalize", "Normalize", prep.normalize,
+                        on_toggle(&config, |c, v| c.preprocessing.normalize = v)) }
+
+                    <button type="submit">{ "Apply" }</button>
+                </form>
+            }
+        }
+    };
+
+    html! {
+        <div class="settings server-config">
+            <h2>{ "Server Configuration" }</h2>
+            <div class="form-group">
+                <label for="admin-key">{ "Admin API Key" }</label>
+                <input id="admin-key" type="password" value={(*api_key).clone()} oninput={onkey} />
+                <button type="button" class="load-button" onclick={onload}>{ "Load" }</button>
+            </div>
+            if let Some(message) = &*status {
+                <p class="save-status">{ message }</p>
+            }
+            { form }
+        </div>
+    }
+}
@@ -0,0 +1,280 @@
+use futures::{SinkExt, StreamExt};
+use gloo_net::websocket::{futures::WebSocket, Message};
+use gloo_timers::future::TimeoutFuture;
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use yew::prelude::*;
+
+/// How many events the recent detections list keeps.
+const RECENT_LIMIT: usize = 20;
+const INITIAL_BACKOFF_MS: u32 = 1_000;
+const MAX_BACKOFF_MS: u32 = 30_000;
+
+/// Sent right after connecting so the server only forwards face events.
+const SUBSCRIBE: &str =
+    r#"{"action":"subscribe","events":["face_detected","face_updated","face_deleted"]}"#;
+
+// Mirrors the JSON encoding of `api::websocket::WsMessage`, keeping only the
+// fields the dashboard shows.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct FaceMetadata {
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    timestamp: String,
+    confidence: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct FaceEvent {
+    face_id: String,
+    metadata: FaceMetadata,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+enum WsEvent {
+    FaceDetected(FaceEvent),
+    FaceUpdated(FaceEvent),
+    FaceDeleted(String),
+}
+
+impl WsEvent {
+    /// `None` for messages the dashboard does not track (progress, errors)
+    /// or frames that fail to parse.
+    fn parse(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok()
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            WsEvent::FaceDetected(_) => "Detected",
+            WsEvent::FaceUpdated(_) => "Updated",
+            WsEvent::FaceDeleted(_) => "Deleted",
+        }
+    }
+}
+
+/// Delay before reconnect attempt `attempt` (0-based): doubles from one
+/// second up to `MAX_BACKOFF_MS`.
+fn backoff_delay(attempt: u32) -> u32 {
+    INITIAL_BACKOFF_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_BACKOFF_MS)
+}
+
+/// `ws://` or `wss://` URL for `/ws` on the host serving the UI.
+fn websocket_url() -> Option<String> {
+    let location = web_sys::window()?.location();
+    let scheme = if location.protocol().ok()? == "https:" { "wss" } else { "ws" };
+    Some(format!("{}://{}/ws", scheme, location.host().ok()?))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Connection {
+    Connecting,
+    Connected,
+    Reconnecting { retry_in_ms: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DashboardState {
+    connection: Connection,
+    detected: u64,
+    updated: u64,
+    deleted: u64,
+    recent: VecDeque<WsEvent>,
+}
+
+impl Default for DashboardState {
+    fn default() -> Self {
+        Self {
+            connection: Connection::Connecting,
+            detected: 0,
+            updated: 0,
+            deleted: 0,
+            recent: VecDeque::with_capacity(RECENT_LIMIT),
+        }
+    }
+}
+
+enum Action {
+    Connection(Connection),
+    Event(WsEvent),
+}
+
+impl Reducible for DashboardState {
+    type Action = Action;
+
+    fn reduce(self: Rc<Self>, action: Action) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            Action::Connection(connection) => state.connection = connection,
+            Action::Event(event) => {
+                match &event {
+                    WsEvent::FaceDetected(_) => state.detected += 1,
+                    WsEvent::FaceUpdated(_) => state.updated += 1,
+                    WsEvent::FaceDeleted(_) => state.deleted += 1,
+                }
+                state.recent.push_front(event);
+                state.recent.truncate(RECENT_LIMIT);
+            }
+        }
+        Rc::new(state)
+    }
+}
+
+/// Read events until the socket closes or errors.
+async fn run_socket(url: &str, dispatch: &UseReducerDispatcher<DashboardState>) -> Result<(), String> {
+    let socket = WebSocket::open(url).map_err(|err| err.to_string())?;
+    let (mut write, mut read) = socket.split();
+    write
+        .send(Message::Text(SUBSCRIBE.to_string()))
+        .await
+        .map_err(|err| err.to_string())?;
+    dispatch.dispatch(Action::Connection(Connection::Connected));
+
+    while let Some(frame) = read.next().await {
+        match frame {
+            Ok(Message::Text(text)) => {
+                if let Some(event) = WsEvent::parse(&text) {
+                    dispatch.dispatch(Action::Event(event));
+                }
+            }
+            Ok(Message::Bytes(_)) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    Ok(())
+}
+
+#[function_component(Dashboard)]
+pub fn dashboard() -> Html {
+    let state = use_reducer(DashboardState::default);
+
+    {
+        let dispatch = state.dispatcher();
+        use_effect_with_deps(
+            move |_| {
+                let active = Rc::new(Cell::new(true));
+                let running = Rc::clone(&active);
+                wasm_bindgen_futures::spawn_local(async move {
+                    let url = match websocket_url() {
+                        Some(url) => url,
+                        None => return,
+                    };
+                    let mut attempt = 0;
+                    while running.get() {
+                        match run_socket(&url, &dispatch).await {
+                            // A clean close after a working session starts the backoff over.
+                            Ok(()) => attempt = 0,
+                            Err(err) => log::warn!("Dashboard websocket error: {}", err),
+                        }
+                        if !running.get() {
+                            break;
+                        }
+                        let delay = backoff_delay(attempt);
+                        dispatch.dispatch(Action::Connection(Connection::Reconnecting { retry_in_ms: delay }));
+                        TimeoutFuture::new(delay).await;
+                        attempt += 1;
+                    }
+                });
+                move || active.set(false)
+            },
+            (),
+        );
+    }
+
+    let status = match state.connection {
+        Connection::Connecting => "Connecting...".to_string(),
+        Connection::Connected => "Live".to_string(),
+        Connection::Reconnecting { retry_in_ms } => {
+            format!("Disconnected, retrying in {}s", retry_in_ms / 1000)
+        }
+    };
+
+    html! {
+        <div class="dashboard">
+            <h2>{ "Live Dashboard" }</h2>
+            <div class={classes!("connection-status", (state.connection == Connection::Connected).then(|| "live"))}>
+                { status }
+            </div>
+            <div class="dashboard-counters">
+                <div class="counter">
+                    <div class="counter-value">{ state.detected }</div>
+                    <div class="counter-label">{ "Detected" }</div>
+                </div>
+                <div class="counter">
+                    <div class="counter-value">{ state.updated }</div>
+                    <div class="counter-label">{ "Updated" }</div>
+                </div>
+                <div class="counter">
+                    <div class="counter-value">{ state.deleted }</div>
+                    <div class="counter-label">{ "Deleted" }</div>
+                </div>
+            </div>
+            <h3>{ "Recent Activity" }</h3>
+            if state.recent.is_empty() {
+                <p class="empty-state">{ "Waiting for events..." }</p>
+            } else {
+                <ul class="recent-events">
+                    { for state.recent.iter().map(recent_item) }
+                </ul>
+            }
+        </div>
+    }
+}
+
+fn recent_item(event: &WsEvent) -> Html {
+    let detail = match event {
+        WsEvent::FaceDetected(face) | WsEvent::FaceUpdated(face) => format!(
+            "{} ({:.0}%) at {}",
+            face.metadata.name.as_deref().unwrap_or(&face.face_id),
+            face.metadata.confidence * 100.0,
+            face.metadata.timestamp,
+        ),
+        WsEvent::FaceDeleted(id) => id.clone(),
+    };
+    let tags = match event {
+        WsEvent::FaceDetected(face) | WsEvent::FaceUpdated(face) => face.metadata.tags.as_slice(),
+        WsEvent::FaceDeleted(_) => &[],
+    };
+
+    html! {
+        <li class="recent-event">
+            <span class="event-label">{ event.label() }</span>
+            <span class="event-detail">{ detail }</span>
+            { for tags.iter().map(|tag| html! { <span class="tag">{ tag }</span> }) }
+        </li>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        assert_eq!(backoff_delay(0), 1_000);
+        assert_eq!(backoff_delay(1), 2_000);
+        assert_eq!(backoff_delay(3), 8_000);
+        assert_eq!(backoff_delay(5), MAX_BACKOFF_MS);
+        assert_eq!(backoff_delay(100), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn test_parse_server_events() {
+        let detected = r#"{"FaceDetected":{"embedding":[0.1],"face_id":"abc","metadata":{"name":null,"tags":["door"],"timestamp":"2024-01-01T00:00:00Z","source_image":"x.jpg","confidence":0.9}}}"#;
+        match WsEvent::parse(detected) {
+            Some(WsEvent::FaceDetected(face)) => {
+                assert_eq!(face.face_id, "abc");
+                assert_eq!(face.metadata.tags, vec!["door"]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert_eq!(WsEvent::parse(r#"{"FaceDeleted":"abc"}"#), Some(WsEvent::FaceDeleted("abc".into())));
+        assert_eq!(WsEvent::parse(r#"{"Error":"boom"}"#), None);
+    }
+}
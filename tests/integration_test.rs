@@ -1,4 +1,4 @@
-use image_analyze::analysis::analyze_image;
+use face_analyzer::analysis::analyze_image;
 use std::fs;
 
 #[test]